@@ -1,7 +1,13 @@
+use crate::error::AssemblyError;
 use crate::line_translator::LineTranslator;
 
+/// Number of words of the ROM of the Hack computer.
+const ROM_SIZE: usize = 32768;
+
 pub struct Assembler {
     lines: Vec<String>,
+    // line of the source of each instruction
+    line_numbers: Vec<usize>,
     translator: LineTranslator,
 }
 
@@ -11,15 +17,30 @@ impl Assembler {
             translator: LineTranslator::new(),
             // ASM Input
             lines,
+            line_numbers: vec![],
         }
     }
 
-    fn first_pass(&mut self) {
-        self.lines = self
+    /// Collects the labels and keeps the lines with instructions.
+    /// Returns the errors of labels that can't be addressed.
+    fn first_pass(&mut self) -> Vec<AssemblyError> {
+        let mut errors = vec![];
+        (self.line_numbers, self.lines) = self
             .lines
             .iter()
-            .filter_map(|line| self.translator.preprocess_line(line))
-            .collect();
+            .enumerate()
+            .filter_map(|(i, line)| match self.translator.preprocess_line(line) {
+                Ok(line) => Some((i + 1, line?)),
+                Err(message) => {
+                    errors.push(AssemblyError {
+                        line: i + 1,
+                        message,
+                    });
+                    None
+                }
+            })
+            .unzip();
+        errors
     }
 
     fn second_pass(&mut self) -> Vec<String> {
@@ -29,15 +50,51 @@ impl Assembler {
             .collect()
     }
 
+    /// Translates the program to binary words, one per instruction.
+    /// Panics at the first label or line that can't be translated, see `try_compile`.
     pub fn compile(&mut self) -> Vec<String> {
-        self.first_pass();
+        if let Some(error) = self.first_pass().first() {
+            panic!("{error}");
+        }
         self.second_pass()
     }
+
+    /// Like `compile`, but reports the lines that can't be translated
+    /// and the program not fitting in the ROM instead of panicking.
+    pub fn try_compile(&mut self) -> Result<Vec<String>, Vec<AssemblyError>> {
+        let label_errors = self.first_pass();
+        let mut errors = vec![];
+        if let Some(&line) = self.line_numbers.get(ROM_SIZE) {
+            errors.push(AssemblyError {
+                line,
+                message: format!(
+                    "The program takes {} words, more than the {ROM_SIZE} of the ROM",
+                    self.lines.len()
+                ),
+            });
+        }
+        errors.extend(label_errors);
+        let mut compiled = vec![];
+        for (line, &number) in self.lines.iter().zip(&self.line_numbers) {
+            match self.translator.try_compile_line(line) {
+                Ok(binary) => compiled.push(binary),
+                Err(message) => errors.push(AssemblyError {
+                    line: number,
+                    message,
+                }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(compiled)
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Assembler;
+    use super::{Assembler, ROM_SIZE};
 
     #[test]
     fn it_works() {
@@ -71,4 +128,38 @@ M=!M
                 .collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn errors_of_all_lines_are_reported() {
+        let lines = ["@1", "", "D=X", "// Comment", "@40000", "0;JMP"];
+        let errors = Assembler::new(lines.map(str::to_string).to_vec())
+            .try_compile()
+            .unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "Line 3: Invalid instruction \"D=X\"",
+                "Line 5: Constant 40000 is out of 0..=32767"
+            ]
+        );
+
+        let lines = vec!["D=D+1".to_string(); ROM_SIZE + 1];
+        let errors = Assembler::new(lines).try_compile().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Line 32769: The program takes 32769 words, more than the 32768 of the ROM"
+        );
+
+        // labels past the ROM are reported rather than wrapped around to the start of it
+        let mut lines = vec!["D=D+1".to_string(); 65536];
+        lines.push("(END)".to_string());
+        lines.push("@END".to_string());
+        let errors = Assembler::new(lines).try_compile().unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors[1..],
+            ["Line 65537: Label END is at address 65536, out of 0..=32767"]
+        );
+    }
 }
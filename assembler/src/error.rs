@@ -0,0 +1,17 @@
+use std::error::Error;
+use std::fmt;
+
+/// A line of assembly that can't be translated to machine code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    /// Line of the source, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error for AssemblyError {}
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
@@ -0,0 +1,6 @@
+mod assembler;
+mod error;
+mod line_translator;
+
+pub use crate::assembler::Assembler;
+pub use crate::error::AssemblyError;
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 pub struct LineTranslator {
     map: HashMap<String, u16>,
     reg_counter: u16,
-    line_number: u16,
+    line_number: usize,
 }
 
 impl LineTranslator {
@@ -46,28 +46,43 @@ impl LineTranslator {
         }
     }
 
-    pub fn preprocess_line(&mut self, mut line: &str) -> Option<String> {
+    /// The instruction of a line, without comments and whitespace, if there is one.
+    /// Fails for a label past the addresses A instructions can hold.
+    pub fn preprocess_line(&mut self, mut line: &str) -> Result<Option<String>, String> {
         let comment_pos = line.find("//");
         if let Some(comment_pos) = comment_pos {
             line = &line[..comment_pos]
         }
         line = line.trim();
-        if line.len() == 0 {
-            None
+        if line.is_empty() {
+            Ok(None)
         } else if line.starts_with("(") {
-            let label = line.trim_matches(|c| c == '(' || c == ')');
-            self.map.insert(label.trim().to_string(), self.line_number);
-            None
+            let label = line.trim_matches(|c| c == '(' || c == ')').trim();
+            match u16::try_from(self.line_number) {
+                Ok(address) if address <= i16::MAX as u16 => {
+                    self.map.insert(label.to_string(), address);
+                    Ok(None)
+                }
+                _ => Err(format!(
+                    "Label {label} is at address {}, out of 0..=32767",
+                    self.line_number
+                )),
+            }
         } else {
             let mut nline = line.to_string();
             // remove whitespace
             nline.retain(|c| !char::is_whitespace(c));
             self.line_number += 1;
-            Some(nline)
+            Ok(Some(nline))
         }
     }
 
     pub fn compile_line(&mut self, line: &str) -> String {
+        self.try_compile_line(line)
+            .unwrap_or_else(|message| panic!("{message}"))
+    }
+
+    pub fn try_compile_line(&mut self, line: &str) -> Result<String, String> {
         if line.starts_with('@') {
             // A instruction
             self.compile_a_instruction(line)
@@ -76,19 +91,24 @@ impl LineTranslator {
         }
     }
 
-    fn compile_a_instruction(&mut self, line: &str) -> String {
+    fn compile_a_instruction(&mut self, line: &str) -> Result<String, String> {
         let value = line.trim_start_matches('@');
-        if let Ok(value) = value.parse::<u16>() {
-            return Self::format_a_instruction(value);
+        if value.starts_with(|c: char| c.is_ascii_digit()) {
+            match value.parse::<u16>() {
+                Ok(value) if value <= i16::MAX as u16 => Ok(Self::format_a_instruction(value)),
+                _ => Err(format!("Constant {value} is out of 0..=32767")),
+            }
+        } else if value.is_empty() {
+            Err("Missing the value of the A instruction".to_string())
         } else {
             if let Some(value) = self.map.get(value) {
-                return Self::format_a_instruction(*value);
+                Ok(Self::format_a_instruction(*value))
             } else {
                 // found new variable
                 self.map.insert(value.to_string(), self.reg_counter);
                 let ret = Self::format_a_instruction(self.reg_counter);
                 self.reg_counter += 1;
-                return ret;
+                Ok(ret)
             }
         }
     }
@@ -96,13 +116,13 @@ impl LineTranslator {
     fn format_a_instruction(value: u16) -> String {
         // zero out highest bit
         let instruction = value & (u16::MAX >> 1);
-        return format!("{instruction:016b}");
+        format!("{instruction:016b}")
     }
 
-    fn compile_c_instruction(&mut self, line: &str) -> String {
+    fn compile_c_instruction(&mut self, line: &str) -> Result<String, String> {
         lazy_static! {
             static ref RE: Regex =
-                Regex::new(r"((?P<dest>[ADM]+)=)?(?P<comp>[ADM+-01!&|]+)(;(?P<jmp>\w+))?").unwrap();
+                Regex::new(r"^((?P<dest>[ADM]+)=)?(?P<comp>[ADM01!&|+-]+)(;(?P<jmp>\w+))?$").unwrap();
             static ref CMAP: HashMap<&'static str, u8> = HashMap::from([
                 ("0", 0b0101010u8),
                 ("1", 0b0111111),
@@ -131,12 +151,19 @@ impl LineTranslator {
                 ("D-M", 0b1010011),
                 ("M-D", 0b1000111),
                 ("D&M", 0b1000000),
-                ("D|M", 0b1010101)
+                ("D|M", 0b1010101),
+                // commutative forms accepted by the CPU emulator
+                ("A+D", 0b0000010),
+                ("A&D", 0b0000000),
+                ("A|D", 0b0010101),
+                ("M+D", 0b1000010),
+                ("M&D", 0b1000000),
+                ("M|D", 0b1010101)
             ]);
         }
         let captures = RE
             .captures(line)
-            .expect("No value got captured! Invalid C instruction!");
+            .ok_or_else(|| format!("Invalid instruction \"{line}\""))?;
         let mut dest_bits = [false; 3]; // dest bits: ADM
         if let Some(dest) = captures.name("dest") {
             let dest = dest.as_str();
@@ -155,14 +182,13 @@ impl LineTranslator {
                 "JNE" => [true, false, true],
                 "JLE" => [true, true, false],
                 "JMP" => [true, true, true],
-                _ => panic!("Invalid jump command!"),
+                _ => return Err(format!("Invalid jump \"{jump}\"")),
             }
         }
-        let comp = captures
-            .name("comp")
-            .expect("Invalid comp part of C instruction!")
-            .as_str();
-        let comp_bin = *CMAP.get(comp).expect("Invalid computation operation!");
+        let comp = captures.name("comp").unwrap().as_str();
+        let comp_bin = *CMAP
+            .get(comp)
+            .ok_or_else(|| format!("Invalid computation \"{comp}\""))?;
         let mut binary = (0b111u16 << 13) + ((comp_bin as u16) << 6);
         binary |= (dest_bits[0] as u16) << 5;
         binary |= (dest_bits[1] as u16) << 4;
@@ -170,7 +196,7 @@ impl LineTranslator {
         binary |= (jump_bits[0] as u16) << 2;
         binary |= (jump_bits[1] as u16) << 1;
         binary |= jump_bits[2] as u16;
-        return format!("{binary:016b}");
+        Ok(format!("{binary:016b}"))
     }
 }
 
@@ -180,7 +206,7 @@ mod tests {
 
     fn check_a_instruction_with_number(line: &str, compare: &str) {
         let mut translator = LineTranslator::new();
-        let preprocessed = translator.preprocess_line(line).unwrap();
+        let preprocessed = translator.preprocess_line(line).unwrap().unwrap();
         assert_eq!(preprocessed, line);
         assert_eq!(translator.line_number, 1);
         let compiled = translator.compile_line(&preprocessed);
@@ -189,7 +215,7 @@ mod tests {
 
     fn check_c_instruction(line: &str, compare: &str) {
         let mut translator = LineTranslator::new();
-        let preprocessed = translator.preprocess_line(line).unwrap();
+        let preprocessed = translator.preprocess_line(line).unwrap().unwrap();
         assert_eq!(translator.line_number, 1);
         let compiled = translator.compile_line(&preprocessed);
         assert_eq!(compiled, compare);
@@ -207,18 +233,26 @@ mod tests {
         check_c_instruction("   D= D & M", "1111000000010000");
     }
 
+    #[test]
+    fn commutative_computations() {
+        check_c_instruction("D=A+D", "1110000010010000");
+        check_c_instruction("D=M+D", "1111000010010000");
+        check_c_instruction("D=A&D", "1110000000010000");
+        check_c_instruction("M=M|D", "1111010101001000");
+    }
+
     #[test]
     fn label() {
         let mut translator = LineTranslator::new();
         let preprocessed = translator.preprocess_line("   (  LABEL    )  ");
-        assert_eq!(preprocessed, None);
+        assert_eq!(preprocessed, Ok(None));
         assert_eq!(translator.line_number, 0);
         let compiled = translator.compile_line("@LABEL");
         assert_eq!(compiled, "0000000000000000");
-        translator.preprocess_line("0");
+        translator.preprocess_line("0").unwrap();
         translator.compile_line("0");
         let preprocessed = translator.preprocess_line("   (  L    )  ");
-        assert_eq!(preprocessed, None);
+        assert_eq!(preprocessed, Ok(None));
         assert_eq!(translator.line_number, 1);
         let compiled = translator.compile_line("@L");
         assert_eq!(compiled, "0000000000000001");
@@ -228,19 +262,35 @@ mod tests {
     fn empty() {
         let mut translator = LineTranslator::new();
         let preprocessed = translator.preprocess_line("  // Wow!  ");
-        assert_eq!(preprocessed, None);
+        assert_eq!(preprocessed, Ok(None));
         assert_eq!(translator.line_number, 0);
         let preprocessed = translator.preprocess_line("  ");
-        assert_eq!(preprocessed, None);
+        assert_eq!(preprocessed, Ok(None));
         assert_eq!(translator.line_number, 0);
     }
 
+    #[test]
+    fn errors() {
+        let mut translator = LineTranslator::new();
+        let mut error = |line| translator.try_compile_line(line).unwrap_err();
+        assert_eq!(error("@32768"), "Constant 32768 is out of 0..=32767");
+        assert_eq!(error("@99999"), "Constant 99999 is out of 0..=32767");
+        assert_eq!(error("@"), "Missing the value of the A instruction");
+        assert_eq!(error("D=M+2"), "Invalid instruction \"D=M+2\"");
+        assert_eq!(error("D;JNZ"), "Invalid jump \"JNZ\"");
+        assert_eq!(error("D=M+A"), "Invalid computation \"M+A\"");
+        assert_eq!(
+            translator.try_compile_line("@32767").unwrap(),
+            "0111111111111111"
+        );
+    }
+
     #[test]
     #[should_panic]
     fn invalid() {
         let mut translator = LineTranslator::new();
         let line = "#asdfjk*()O";
         let line = translator.preprocess_line(line);
-        let _compiled = translator.compile_line(&line.unwrap());
+        let _compiled = translator.compile_line(&line.unwrap().unwrap());
     }
 }
@@ -1,8 +1,6 @@
-mod assembler;
-mod line_translator;
-
+use assembler::Assembler;
 use clap::Parser;
-use std::fs;
+use std::{fs, process};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -19,13 +17,19 @@ struct Args {
 
 fn main() {
     let args = Args::parse();
-    let lines = fs::read_to_string(args.file)
+    let lines = fs::read_to_string(&args.file)
         .expect("Error reading source file!")
         .lines()
         .map(str::to_string)
         .collect();
-    let mut assembler = assembler::Assembler::new(lines);
-    let compiled = assembler.compile();
+    let mut assembler = Assembler::new(lines);
+    let compiled = assembler.try_compile().unwrap_or_else(|errors| {
+        for error in &errors {
+            eprintln!("{}:{}: error: {}", args.file, error.line, error.message);
+        }
+        eprintln!("Aborting due to {} error(s)", errors.len());
+        process::exit(1)
+    });
     fs::write(
         args.output.unwrap_or("a.out".to_string()),
         compiled.join("\n"),
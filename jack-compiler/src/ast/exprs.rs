@@ -73,7 +73,7 @@ impl TryFrom<&str> for UnaryOperator {
         match value {
             "-" => Ok(Self::ArthemiticNegation),
             "~" => Ok(Self::LogicalNegation),
-            _ => bail!("Invalid unary operator {}", value)
+            _ => bail!("Invalid unary operator {}", value),
         }
     }
 }
@@ -12,4 +12,4 @@ pub type NodeCollection<T> = Vec<T>;
 pub type NodeBox<T> = Box<T>;
 
 /// Each compilation unit is a class
-pub type AST = ClassNode;
+pub type Ast = ClassNode;
@@ -1,9 +1,26 @@
+use std::fmt;
+
 use super::kinds::*;
 use super::statements::*;
 use super::NodeCollection;
+use crate::token::Span;
+
+/// A name, and where it is in the source.
+#[derive(Debug, Clone)]
+pub struct IdentifierNode(pub(crate) String, pub(crate) Span);
+
+impl From<String> for IdentifierNode {
+    fn from(name: String) -> Self {
+        Self(name, Span::default())
+    }
+}
 
-#[derive(Debug, derive_more::From, PartialEq, Clone)]
-pub struct IdentifierNode(pub(crate) String);
+/// Names are equal when they are spelled the same, wherever they are.
+impl PartialEq for IdentifierNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
 #[derive(Debug, PartialEq)]
 
 pub struct ClassNode {
@@ -35,6 +52,17 @@ pub enum TypeNode {
     Class(IdentifierNode),
 }
 
+impl fmt::Display for TypeNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Char => write!(f, "char"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Class(name) => write!(f, "{}", name.0),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ParameterNode {
     pub(crate) r#type: TypeNode,
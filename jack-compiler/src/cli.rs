@@ -1,64 +1,201 @@
 use std::{
-    fs, io,
+    fs,
     path::{Path, PathBuf},
 };
 
-use clap::Parser as CmdlineParser;
+use anyhow::{anyhow, Result};
+use clap::{Parser as CmdlineParser, ValueEnum};
 
-use crate::tokenizer::Source;
+use crate::{diagnostic::ErrorFormat, tokenizer::Source};
+
+/// What the compiler writes for each class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Emit {
+    /// VM code, to `Main.vm`
+    Vm,
+    /// the tokens as XML, to `MainT.xml`
+    TokensXml,
+    /// the parse tree as XML, to `Main.xml`
+    ParseXml,
+}
 
 #[derive(CmdlineParser, Debug)]
 #[clap(author="kxxt", version, about="Jack compiler for nand2tetris course", long_about = None)]
 pub struct Args {
-    /// input file or folder
-    #[clap(value_parser)]
-    pub input: String,
+    /// input files or folders, or glob patterns like `src/*.jack`
+    #[clap(value_parser, required = true)]
+    pub input: Vec<String>,
 
     /// output file or folder
     #[clap(short, long, value_parser)]
     pub output: Option<String>,
+
+    /// emit the VM extension commands mul, div and shl instead of calling Math.multiply and Math.divide
+    /// (run the VM translator with --extensions). Dividing by zero then gives 32767, or -32767
+    /// for a negative dividend, instead of failing with Sys.error 3
+    #[clap(long, value_parser)]
+    pub extended: bool,
+
+    /// what to write for each class: VM code, or the XML files of project 10
+    #[clap(long, value_enum, default_value_t = Emit::Vm)]
+    pub emit: Emit,
+
+    /// how to print errors, `json` prints one JSON object per line for editors
+    #[clap(long, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format: ErrorFormat,
+
+    /// report at most this many syntax errors per file
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..), default_value_t = 20)]
+    pub max_errors: u16,
 }
 
 impl Args {
-    pub fn get_inputs(&self) -> Result<Vec<Source>, io::Error> {
-        let input = Path::new(&self.input);
-        let files = if input.is_dir() {
-            fs::read_dir(input)?
-                .filter_map(|s| {
-                    s.ok().and_then(|entry| {
-                        let path = entry.path();
-                        if path.is_file() && path.extension().is_some_and(|ext| *ext == "jack") {
-                            Some(path)
-                        } else {
-                            None
-                        }
-                    })
+    /// The Jack files of the inputs, in order, along with their paths.
+    /// Folders are read sorted by file name, and glob patterns expand to the paths they match, sorted,
+    /// so that the output doesn't depend on the order the file system lists files in.
+    pub fn get_inputs(&self) -> Result<Vec<(PathBuf, Source)>> {
+        let mut files: Vec<PathBuf> = vec![];
+        for input in &self.input {
+            if input.contains(['*', '?', '[']) {
+                let matches = glob::glob(input)?.collect::<Result<Vec<_>, _>>()?;
+                if matches.is_empty() {
+                    return Err(anyhow!("No file matches {input}!"));
+                }
+                files.extend(matches);
+            } else {
+                files.push(input.into());
+            }
+        }
+        let mut inputs = vec![];
+        for path in files {
+            for file in Self::jack_files(&path)? {
+                if !inputs.iter().any(|(read, _)| *read == file) {
+                    let source = Source {
+                        content: fs::read_to_string(&file)?,
+                        name: file.file_stem().unwrap().to_string_lossy().to_string(),
+                    };
+                    inputs.push((file, source));
+                }
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// `input` if it is a file, or the Jack files in it, sorted.
+    fn jack_files(input: &Path) -> Result<Vec<PathBuf>> {
+        if !input.is_dir() {
+            return Ok(vec![input.to_path_buf()]);
+        }
+        let mut files: Vec<_> = fs::read_dir(input)?
+            .filter_map(|s| {
+                s.ok().and_then(|entry| {
+                    let path = entry.path();
+                    if path.is_file() && path.extension().is_some_and(|ext| ext == "jack") {
+                        Some(path)
+                    } else {
+                        None
+                    }
                 })
-                .collect()
-        } else {
-            vec![PathBuf::from(input)]
-        };
+            })
+            .collect();
         if files.is_empty() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!(
-                    "No source code found in directory {}!",
-                    input.to_string_lossy()
-                ),
+            return Err(anyhow!(
+                "No source code found in directory {}!",
+                input.to_string_lossy()
             ));
         }
-        Ok(files
+        files.sort();
+        Ok(files)
+    }
+
+    /// Where to write what is emitted for the class compiled from `path`:
+    /// the output file when compiling a single file to it, in the output folder if there is one,
+    /// and next to the Jack file otherwise.
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let file_name = match self.emit {
+            Emit::Vm => format!("{stem}.vm"),
+            Emit::TokensXml => format!("{stem}T.xml"),
+            Emit::ParseXml => format!("{stem}.xml"),
+        };
+        match &self.output {
+            Some(output) if self.input.len() == 1 && Path::new(&self.input[0]).is_file() => {
+                output.into()
+            }
+            Some(output) => Path::new(output).join(file_name),
+            None => path.with_file_name(file_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+
+    fn args(input: &[&str]) -> Args {
+        let pong = Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/11/Pong");
+        Args {
+            input: input
+                .iter()
+                .map(|input| pong.join(input).to_string_lossy().to_string())
+                .collect(),
+            output: None,
+            extended: false,
+            emit: Emit::Vm,
+            error_format: ErrorFormat::Human,
+            max_errors: 20,
+        }
+    }
+
+    fn names(inputs: &[(PathBuf, Source)]) -> Vec<&str> {
+        inputs
             .iter()
-            .map(|file| {
-                Ok(Source {
-                    content: fs::read_to_string(&file)?,
-                    name: Path::new(file)
-                        .file_stem()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_string(),
-                })
+            .map(|(_, source)| source.name.as_str())
+            .collect()
+    }
+
+    fn compile(inputs: Vec<(PathBuf, Source)>) -> String {
+        inputs
+            .into_iter()
+            .map(|(_, source)| {
+                jack_vm_ir::to_vm_code(&Compiler::compile(source, false, usize::MAX).unwrap().0)
             })
-            .collect::<Result<Vec<Source>, io::Error>>()?)
+            .collect()
+    }
+
+    #[test]
+    fn inputs_are_read_in_order() {
+        let inputs = args(&[""]).get_inputs().unwrap();
+        assert_eq!(names(&inputs), ["Ball", "Bat", "Main", "PongGame"]);
+        let inputs = args(&["Main.jack", "B*.jack", "Main.jack", "PongGame.jack"])
+            .get_inputs()
+            .unwrap();
+        assert_eq!(names(&inputs), ["Main", "Ball", "Bat", "PongGame"]);
+        assert!(args(&["*.vm.jack"]).get_inputs().is_err());
+    }
+
+    #[test]
+    fn compilation_is_reproducible() {
+        let first = compile(args(&[""]).get_inputs().unwrap());
+        assert!(first == compile(args(&[""]).get_inputs().unwrap()));
+        assert!(first == compile(args(&["*.jack"]).get_inputs().unwrap()));
+    }
+
+    #[test]
+    fn output_paths() {
+        let mut args = args(&["Main.jack"]);
+        let main = PathBuf::from(&args.input[0]);
+        assert_eq!(args.output_path(&main), main.with_extension("vm"));
+        args.output = Some("out.vm".to_string());
+        assert_eq!(args.output_path(&main), Path::new("out.vm"));
+        args.input.push(args.input[0].clone());
+        args.output = Some("out".to_string());
+        assert_eq!(args.output_path(&main), Path::new("out/Main.vm"));
+        args.emit = Emit::TokensXml;
+        assert_eq!(args.output_path(&main), Path::new("out/MainT.xml"));
+        args.output = None;
+        args.emit = Emit::ParseXml;
+        assert_eq!(args.output_path(&main), main.with_extension("xml"));
     }
 }
@@ -1,17 +1,63 @@
 use crate::{
+    ast::Ast,
     emitter::Emitter,
     parser::Parser,
     tokenizer::{Source, Tokenizer},
+    xml::XmlWriter,
 };
 
 pub struct Compiler;
 
-pub type VMCode = String;
+pub type VMCode = Vec<jack_vm_ir::Command>;
 
 impl Compiler {
-    pub fn compile(source: Source) -> Result<(VMCode, String), anyhow::Error> {
-        let token_stream = Tokenizer::stream(&source);
-        let ast = Parser::new(token_stream, source.name.to_string()).parse()?;
-        Ok((Emitter::new().emit(&ast)?, source.name))
+    /// Compiles a class, using the VM extension commands if `extended`.
+    /// Fails with all the syntax errors of the class, up to `max_errors` of them,
+    /// or with the first semantic error.
+    pub fn compile(
+        source: Source,
+        extended: bool,
+        max_errors: usize,
+    ) -> Result<(VMCode, String), Vec<anyhow::Error>> {
+        let ast = Self::parse(&source, max_errors)?;
+        let mut emitter = if extended {
+            Emitter::extended()
+        } else {
+            Emitter::new()
+        };
+        Ok((emitter.emit(&ast).map_err(|e| vec![e])?, source.name))
+    }
+
+    /// The tokens of a class as XML, like `MainT.xml` of project 10.
+    pub fn tokens_xml(source: &Source) -> Result<String, Vec<anyhow::Error>> {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for token in Tokenizer::stream(source) {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error.into()),
+            }
+        }
+        if errors.is_empty() {
+            Ok(XmlWriter::tokens(&tokens))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The parse tree of a class as XML, like `Main.xml` of project 10.
+    pub fn parse_xml(source: &Source, max_errors: usize) -> Result<String, Vec<anyhow::Error>> {
+        Ok(XmlWriter::parse_tree(&Self::parse(source, max_errors)?))
+    }
+
+    fn parse(source: &Source, max_errors: usize) -> Result<Ast, Vec<anyhow::Error>> {
+        let token_stream = Tokenizer::stream(source);
+        let (ast, errors) = Parser::new(token_stream, source.name.to_string())
+            .max_errors(max_errors)
+            .parse_all();
+        match ast {
+            Some(ast) if errors.is_empty() => Ok(ast),
+            _ => Err(errors),
+        }
     }
 }
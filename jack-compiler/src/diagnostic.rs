@@ -0,0 +1,287 @@
+//! Errors of the compiler as diagnostics pointing at the source,
+//! rendered like rustc's or as JSON for editors.
+
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::errors::{type_name, EmitterError, ParserError, TokenizerError};
+use crate::token::Span;
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// A message, the source line and a caret under the problem.
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Path of the source file.
+    pub file: String,
+    pub message: String,
+    pub span: Option<Span>,
+    /// What the caret under the span says.
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// The diagnostic of an error compiling `file`.
+    pub fn from_error(file: &str, error: &anyhow::Error) -> Self {
+        let diagnostic = |message: String, span: Span, label: Option<String>| Self {
+            file: file.to_string(),
+            message,
+            // spans of nodes made up by the compiler point nowhere
+            span: (span.line > 0).then_some(span),
+            label,
+            notes: vec![],
+        };
+        if let Some(TokenizerError { kind, span }) = error.downcast_ref() {
+            diagnostic(kind.to_string(), *span, None)
+        } else if let Some(error) = error.downcast_ref::<ParserError>() {
+            match error {
+                ParserError::UnexpectedEndOfStream(span) => diagnostic(
+                    "unexpected end of file".to_string(),
+                    *span,
+                    Some("the class isn't finished after this".to_string()),
+                ),
+                ParserError::UnexpectedToken(token, expected) => diagnostic(
+                    format!("unexpected {token}"),
+                    token.span,
+                    Some(format!("expected {expected}")),
+                ),
+                ParserError::ClassNameMismatch(name, stem) => Self {
+                    notes: vec![format!(
+                        "a class must be in a file named after it, like {stem}.jack for {stem}"
+                    )],
+                    ..diagnostic(
+                        format!("class \"{}\" is in file \"{stem}.jack\"", name.0),
+                        name.1,
+                        Some("the class is named here".to_string()),
+                    )
+                },
+            }
+        } else if let Some(error) = error.downcast_ref::<EmitterError>() {
+            match error {
+                EmitterError::VariableNotFound(name) => Self {
+                    notes: vec![
+                        "a subroutine can use its parameters and locals, and the statics and fields of its class"
+                            .to_string(),
+                    ],
+                    ..diagnostic(
+                        format!("variable \"{}\" not found", name.0),
+                        name.1,
+                        Some("not found in this scope".to_string()),
+                    )
+                },
+                EmitterError::UnexpectedPrimitiveType(name, r#type) => diagnostic(
+                    format!("can't call a method of \"{}\"", name.0),
+                    name.1,
+                    Some(format!("its type is {type}, not a class")),
+                ),
+                EmitterError::MismatchedType(name, expected, found) => Self {
+                    notes: vec!["a constructor returns an object of its class".to_string()],
+                    ..diagnostic(
+                        format!(
+                            "constructor \"{}\" returns \"{}\" instead of \"{}\"",
+                            name.0,
+                            type_name(found),
+                            type_name(expected)
+                        ),
+                        name.1,
+                        Some(format!("should return \"{}\"", type_name(expected))),
+                    )
+                },
+                EmitterError::NotInASubroutine => {
+                    diagnostic(error.to_string(), Span::default(), None)
+                }
+            }
+        } else {
+            diagnostic(error.to_string(), Span::default(), None)
+        }
+    }
+
+    pub fn render(&self, format: ErrorFormat, source: &str) -> String {
+        match format {
+            ErrorFormat::Human => self.to_human(source),
+            ErrorFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Formats the diagnostic like rustc does:
+    ///
+    /// ```text
+    /// error: unexpected symbol ";"
+    ///  --> Main.jack:3:17
+    ///   |
+    /// 3 |         let x = ;
+    ///   |                 ^ expected an expression
+    /// ```
+    pub fn to_human(&self, source: &str) -> String {
+        let mut text = format!("error: {}\n", self.message);
+        let line = self
+            .span
+            .and_then(|span| Some((span, source.lines().nth(span.line as usize - 1)?)));
+        let Some((span, line)) = line else {
+            text += &format!(" --> {}", self.file);
+            for note in &self.notes {
+                text += &format!("\n  = note: {note}");
+            }
+            return text;
+        };
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // keep the tabs before the caret, so that it lines up with the line
+        let indent: String = line
+            .chars()
+            .take(span.column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = source[span.start..span.end]
+            .lines()
+            .next()
+            .map_or(0, |text| text.chars().count())
+            .max(1);
+        let label = self
+            .label
+            .as_ref()
+            .map_or(String::new(), |label| format!(" {label}"));
+        text += &format!(
+            "{gutter}--> {}:{}:{}\n{gutter} |\n{number} | {line}\n{gutter} | {indent}{}{label}",
+            self.file,
+            span.line,
+            span.column,
+            "^".repeat(width)
+        );
+        if !self.notes.is_empty() {
+            text += &format!("\n{gutter} |");
+        }
+        for note in &self.notes {
+            text += &format!("\n{gutter} = note: {note}");
+        }
+        text
+    }
+
+    /// The diagnostic as a JSON object on a single line.
+    pub fn to_json(&self) -> String {
+        json!({
+            "severity": "error",
+            "file": self.file,
+            "message": self.message,
+            "span": self.span.map(|span| json!({
+                "start": span.start,
+                "end": span.end,
+                "line": span.line,
+                "column": span.column,
+            })),
+            "label": self.label,
+            "notes": self.notes,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::Compiler, tokenizer::Source};
+
+    fn diagnose(name: &str, content: &str) -> Diagnostic {
+        let source = Source {
+            name: name.to_string(),
+            content: content.to_string(),
+        };
+        let errors = Compiler::compile(source, false, usize::MAX).unwrap_err();
+        Diagnostic::from_error(&format!("{name}.jack"), &errors[0])
+    }
+
+    #[test]
+    fn parser_errors() {
+        let source = "class Main {\n    function void main() {\n        let x = ;\n    }\n}\n";
+        assert_eq!(
+            diagnose("Main", source).to_human(source),
+            r#"error: unexpected symbol ";"
+ --> Main.jack:3:17
+  |
+3 |         let x = ;
+  |                 ^ expected an expression"#
+        );
+        let source = "class Main {\n  function void main() {\n    return;\n";
+        assert_eq!(
+            diagnose("Main", source).to_human(source),
+            r#"error: unexpected end of file
+ --> Main.jack:3:11
+  |
+3 |     return;
+  |           ^ the class isn't finished after this"#
+        );
+        let source = "class Game {\n}\n";
+        assert_eq!(
+            diagnose("Main", source).to_human(source),
+            r#"error: class "Game" is in file "Main.jack"
+ --> Main.jack:1:7
+  |
+1 | class Game {
+  |       ^^^^ the class is named here
+  |
+  = note: a class must be in a file named after it, like Main.jack for Main"#
+        );
+    }
+
+    #[test]
+    fn tokenizer_errors() {
+        let source = "class Main {\n\tfunction void main() {\n\t\tdo Output.printString(\"oops);\n";
+        assert_eq!(
+            diagnose("Main", source).to_human(source),
+            "error: unterminated string constant, strings end on the line they start on
+ --> Main.jack:3:25
+  |
+3 | \t\tdo Output.printString(\"oops);
+  | \t\t                      ^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn emitter_errors() {
+        let source = "class Main {\n    function void main() {\n        let count = count + 1;\n        return;\n    }\n}\n";
+        let diagnostic = diagnose("Main", source);
+        assert_eq!(
+            diagnostic.to_human(source),
+            r#"error: variable "count" not found
+ --> Main.jack:3:13
+  |
+3 |         let count = count + 1;
+  |             ^^^^^ not found in this scope
+  |
+  = note: a subroutine can use its parameters and locals, and the statics and fields of its class"#
+        );
+        let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json()).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "severity": "error",
+                "file": "Main.jack",
+                "message": "variable \"count\" not found",
+                "span": {"start": 52, "end": 57, "line": 3, "column": 13},
+                "label": "not found in this scope",
+                "notes": [
+                    "a subroutine can use its parameters and locals, and the statics and fields of its class"
+                ],
+            })
+        );
+
+        let source = "class Main {\n    constructor int new() {\n        return this;\n    }\n}\n";
+        assert_eq!(
+            diagnose("Main", source).to_human(source),
+            r#"error: constructor "new" returns "int" instead of "Main"
+ --> Main.jack:2:21
+  |
+2 |     constructor int new() {
+  |                     ^^^ should return "Main"
+  |
+  = note: a constructor returns an object of its class"#
+        );
+    }
+}
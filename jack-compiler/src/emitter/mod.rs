@@ -1,11 +1,12 @@
-use std::{collections::HashMap, fmt::Write};
+use std::collections::HashMap;
 
 use crate::{ast::*, compiler::VMCode, errors::EmitterError};
 use anyhow::Result;
+use jack_vm_ir::{Command, FunctionName, Label, Segment};
 
-use self::{segment::Segment, variable::VariableInfo};
+use self::variable::VariableInfo;
 
-mod segment;
+mod tests;
 mod variable;
 
 pub struct Emitter {
@@ -15,6 +16,8 @@ pub struct Emitter {
     field_counter: u16,
     label_counter: u16,
     subroutine_table: Option<HashMap<String, VariableInfo>>,
+    /// Emit the extension commands `mul`, `div` and `shl` instead of calling `Math`.
+    extended: bool,
 }
 
 impl Emitter {
@@ -26,17 +29,26 @@ impl Emitter {
             label_counter: 0,
             subroutine_table: None,
             class_name: None,
+            extended: false,
         }
     }
 
-    pub fn emit(&mut self, ast: &AST) -> Result<VMCode> {
-        let mut code = String::new();
+    /// An emitter for VM translators that accept the extension commands.
+    pub fn extended() -> Self {
+        Self {
+            extended: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn emit(&mut self, ast: &Ast) -> Result<VMCode> {
+        let mut code = vec![];
         self.class_name = Some(ast.name.0.to_string());
         for ele in &ast.variables {
             self.handle_class_var(ele);
         }
         for subroutine in &ast.subroutines {
-            code += &self.emit_subroutine(subroutine)?;
+            code.extend(self.emit_subroutine(subroutine)?);
         }
         Ok(code)
     }
@@ -53,14 +65,18 @@ impl Emitter {
         temp
     }
 
-    fn next_label(&mut self) -> String {
+    fn next_label(&mut self) -> Label {
         let label = format!(
             "{}.LABEL.{}",
             self.class_name.as_ref().unwrap(),
             self.label_counter
         );
         self.label_counter += 1;
-        label
+        label.into()
+    }
+
+    fn function_name(&self, name: &IdentifierNode) -> FunctionName {
+        format!("{}.{}", self.class_name.as_ref().unwrap(), name.0).into()
     }
 
     fn handle_class_var(&mut self, class_var: &ClassVariableDeclarationNode) {
@@ -103,12 +119,12 @@ impl Emitter {
         Ok(cnt)
     }
 
-    fn lookup_var(&self, name: &str) -> Result<&VariableInfo> {
+    fn lookup_var(&self, name: &IdentifierNode) -> Result<&VariableInfo> {
         self.subroutine_table
             .as_ref()
-            .and_then(|map| map.get(name))
-            .or_else(|| self.root_table.get(name))
-            .ok_or_else(|| EmitterError::VariableNotFound(name.to_string()).into())
+            .and_then(|map| map.get(&name.0))
+            .or_else(|| self.root_table.get(&name.0))
+            .ok_or_else(|| EmitterError::VariableNotFound(name.clone()).into())
     }
 
     fn emit_constructor(
@@ -128,19 +144,21 @@ impl Emitter {
         let class_name = self.class_name.as_ref().unwrap();
         let expected = Some(TypeNode::Class(class_name.clone().into()));
         if return_type != &expected {
-            return Err(EmitterError::MismatchedType(expected, return_type.clone()).into());
+            return Err(
+                EmitterError::MismatchedType(name.clone(), expected, return_type.clone()).into(),
+            );
         }
         // format VMCode
-        let name = &name.0;
-        let fields_cnt = self.field_counter;
-        let mut code = format!(
-            r#"
-function {class_name}.{name} {var_cnt}
-push constant {fields_cnt}
-call Memory.alloc 1
-pop pointer 0"#
-        );
-        write!(code, "{}", self.emit_statements(&body.statements)?)?;
+        let mut code = vec![
+            Command::Function {
+                name: self.function_name(name),
+                n_vars: var_cnt,
+            },
+            push(Segment::Constant, self.field_counter),
+            call("Memory.alloc", 1),
+            pop(Segment::Pointer, 0),
+        ];
+        code.extend(self.emit_statements(&body.statements)?);
         Ok(code)
     }
 
@@ -151,10 +169,11 @@ pop pointer 0"#
         // checks
         assert_eq!(kind, &SubroutineKind::Function);
         // format VMCode
-        let name = &name.0;
-        let class_name = self.class_name.as_deref().unwrap();
-        let mut code = format!("\nfunction {class_name}.{name} {var_cnt}");
-        write!(code, "{}", self.emit_statements(&body.statements)?)?;
+        let mut code = vec![Command::Function {
+            name: self.function_name(name),
+            n_vars: var_cnt,
+        }];
+        code.extend(self.emit_statements(&body.statements)?);
         Ok(code)
     }
 
@@ -165,15 +184,15 @@ pop pointer 0"#
         // checks
         assert_eq!(kind, &SubroutineKind::Method);
         // format VMCode
-        let name = &name.0;
-        let class_name = self.class_name.as_deref().unwrap();
-        let mut code = format!(
-            r#"
-function {class_name}.{name} {var_cnt}
-push argument 0
-pop pointer 0"#
-        );
-        write!(code, "{}", self.emit_statements(&body.statements)?)?;
+        let mut code = vec![
+            Command::Function {
+                name: self.function_name(name),
+                n_vars: var_cnt,
+            },
+            push(Segment::Argument, 0),
+            pop(Segment::Pointer, 0),
+        ];
+        code.extend(self.emit_statements(&body.statements)?);
         Ok(code)
     }
 
@@ -185,22 +204,18 @@ pop pointer 0"#
             cnt = self.handle_var(var, cnt)?;
         }
         // handle parameters
-        let mut param_cnt = if subroutine.kind == SubroutineKind::Method {
+        let first_param = if subroutine.kind == SubroutineKind::Method {
             1
         } else {
             0
         };
-        for param in &subroutine.parameters {
+        for (index, param) in (first_param..).zip(&subroutine.parameters) {
             self.subroutine_table.as_mut().unwrap().insert(
                 param.name.0.to_string(),
                 VariableInfo {
                     r#type: param.r#type.clone(),
                     segment: Segment::Argument,
-                    index: {
-                        let temp = param_cnt;
-                        param_cnt += 1;
-                        temp
-                    },
+                    index,
                 },
             );
         }
@@ -213,70 +228,69 @@ pop pointer 0"#
 
     fn emit_term(&self, term: &TermNode) -> Result<VMCode> {
         Ok(match term {
-            TermNode::IntegerConstant(i) => format!("\npush constant {i}"),
-            TermNode::StringConstant(s) => Self::emit_string(s.to_string()),
-            TermNode::KeywordConstant(v) => {
-                format!(
-                    "\n{}",
-                    match v {
-                        KeywordConstant::False | KeywordConstant::Null => "push constant 0",
-                        KeywordConstant::True => "push constant 0\nnot",
-                        KeywordConstant::This => "push pointer 0",
-                    }
-                )
-            }
+            TermNode::IntegerConstant(i) => vec![push(Segment::Constant, *i)],
+            TermNode::StringConstant(s) => Self::emit_string(s),
+            TermNode::KeywordConstant(v) => match v {
+                KeywordConstant::False | KeywordConstant::Null => {
+                    vec![push(Segment::Constant, 0)]
+                }
+                KeywordConstant::True => vec![push(Segment::Constant, 0), Command::Not],
+                KeywordConstant::This => vec![push(Segment::Pointer, 0)],
+            },
             TermNode::Variable(v) => {
-                let VariableInfo { segment, index, .. } = self.lookup_var(&v.0)?;
-                format!("\npush {} {}", segment, index)
+                let VariableInfo { segment, index, .. } = self.lookup_var(v)?;
+                vec![push(*segment, *index)]
             }
-            TermNode::Parentheses(expr) => self.emit_expr(&expr)?,
+            TermNode::Parentheses(expr) => self.emit_expr(expr)?,
             TermNode::SubroutineCall(call) => self.emit_call(call)?,
-            TermNode::UnaryOperation(op) => format!(
-                r"
-{}
-{}",
-                self.emit_term(&op.subject)?,
-                match op.operator {
-                    UnaryOperator::ArthemiticNegation => "neg",
-                    UnaryOperator::LogicalNegation => "not",
-                }
-            ),
+            TermNode::UnaryOperation(op) => {
+                let mut code = self.emit_term(&op.subject)?;
+                code.push(match op.operator {
+                    UnaryOperator::ArthemiticNegation => Command::Neg,
+                    UnaryOperator::LogicalNegation => Command::Not,
+                });
+                code
+            }
             TermNode::ArrayElement(ArrayElementNode { name, index }) => {
                 let VariableInfo {
                     segment,
                     index: var_index,
                     ..
-                } = self.lookup_var(&name.0)?;
-                format!(
-                    r"
-push {segment} {var_index}{}
-add
-pop pointer 1
-push that 0",
-                    self.emit_expr(index)?
-                )
+                } = self.lookup_var(name)?;
+                let mut code = vec![push(*segment, *var_index)];
+                code.extend(self.emit_expr(index)?);
+                code.extend([
+                    Command::Add,
+                    pop(Segment::Pointer, 1),
+                    push(Segment::That, 0),
+                ]);
+                code
             }
         })
     }
 
-    fn emit_call(&self, call: &SubroutineCallNode) -> Result<VMCode> {
+    fn emit_call(&self, node: &SubroutineCallNode) -> Result<VMCode> {
         let SubroutineCallNode {
             this,
             name,
             arguments,
-        } = call;
-        let mut code = String::new();
+        } = node;
+        let mut code = vec![];
         let mut r#type = self.class_name.as_ref().unwrap();
-        let mut arg_len = arguments.len();
+        let mut arg_len = arguments.len() as u16;
         if let Some(this) = this {
-            if let Ok(info) = self.lookup_var(&this.0) {
+            if let Ok(info) = self.lookup_var(this) {
                 // look up for variable
                 arg_len += 1;
-                write!(code, "\npush {} {}", info.segment, info.index)?;
+                code.push(push(info.segment, info.index));
                 if let TypeNode::Class(c) = &info.r#type {
                     r#type = &c.0;
                 } else {
-                    return Err(EmitterError::UnexpectedPrimitiveType(info.r#type.clone()).into());
+                    return Err(EmitterError::UnexpectedPrimitiveType(
+                        this.clone(),
+                        info.r#type.clone(),
+                    )
+                    .into());
                 }
             } else {
                 // variable not found. pretend it is a type
@@ -286,79 +300,86 @@ push that 0",
         } else {
             // method call
             arg_len += 1;
-            write!(code, "\npush pointer 0")?;
+            code.push(push(Segment::Pointer, 0));
         }
         for arg in arguments {
-            write!(code, "{}", self.emit_expr(arg)?)?;
+            code.extend(self.emit_expr(arg)?);
         }
-        write!(code, "\ncall {}.{} {arg_len}", r#type, &name.0)?;
+        code.push(call(&format!("{}.{}", r#type, &name.0), arg_len));
         Ok(code)
     }
 
     fn emit_expr(&self, expr: &ExpressionNode) -> Result<VMCode> {
-        if expr.parts.len() == 0 {
-            return Ok(self.emit_term(&expr.term)?);
-        }
         let mut code = self.emit_term(&expr.term)?;
-        let mut iter = expr.parts.iter();
-        while let Some(ExpressionPart { operator, term }) = iter.next() {
-            write!(code, "{}\n", self.emit_term(term)?)?;
-            code += match operator {
-                BinaryOperator::Plus => "add",
-                BinaryOperator::Minus => "sub",
-                BinaryOperator::Multiply => "call Math.multiply 2",
-                BinaryOperator::Divide => "call Math.divide 2",
-                BinaryOperator::And => "and",
-                BinaryOperator::Or => "or",
-                BinaryOperator::LessThan => "lt",
-                BinaryOperator::GreaterThan => "gt",
-                BinaryOperator::Equal => "eq",
+        for ExpressionPart { operator, term } in &expr.parts {
+            if let (BinaryOperator::Multiply, TermNode::IntegerConstant(i)) = (operator, &**term) {
+                if self.extended && *i > 1 && i.is_power_of_two() {
+                    code.extend([
+                        push(Segment::Constant, i.trailing_zeros() as u16),
+                        Command::Shl,
+                    ]);
+                    continue;
+                }
             }
+            code.extend(self.emit_term(term)?);
+            code.push(match operator {
+                BinaryOperator::Plus => Command::Add,
+                BinaryOperator::Minus => Command::Sub,
+                BinaryOperator::Multiply if self.extended => Command::Mul,
+                BinaryOperator::Divide if self.extended => Command::Div,
+                BinaryOperator::Multiply => call("Math.multiply", 2),
+                BinaryOperator::Divide => call("Math.divide", 2),
+                BinaryOperator::And => Command::And,
+                BinaryOperator::Or => Command::Or,
+                BinaryOperator::LessThan => Command::Lt,
+                BinaryOperator::GreaterThan => Command::Gt,
+                BinaryOperator::Equal => Command::Eq,
+            });
         }
         Ok(code)
     }
 
     fn emit_return(&self, node: &ReturnNode) -> Result<VMCode> {
-        let code = node
+        let mut code = node
             .value
             .as_ref()
-            .map(|expr| self.emit_expr(&expr))
+            .map(|expr| self.emit_expr(expr))
             .transpose()?
-            .unwrap_or("\npush constant 0".to_string());
-        Ok(format!("{code}\nreturn"))
+            .unwrap_or_else(|| vec![push(Segment::Constant, 0)]);
+        code.push(Command::Return);
+        Ok(code)
     }
 
     fn emit_let(&self, node: &LetNode) -> Result<VMCode> {
         let LetNode { name, index, value } = node;
-        if index.is_none() {
-            let VariableInfo { segment, index, .. } = self.lookup_var(&name.0)?;
-            let mut code = self.emit_expr(value)?;
-            write!(code, "\npop {} {}", segment, index)?;
-            Ok(code)
-        } else {
-            let LetNode { name, index, value } = node;
+        if let Some(index) = index {
             let VariableInfo {
                 segment,
                 index: var_index,
                 ..
-            } = self.lookup_var(&name.0)?;
-            let index = self.emit_expr(index.as_ref().unwrap())?;
-            let value = self.emit_expr(value)?;
-            Ok(format!(
-                r"
-push {segment} {var_index}{index}
-add{value}
-pop temp 4
-pop pointer 1
-push temp 4
-pop that 0"
-            ))
+            } = self.lookup_var(name)?;
+            let mut code = vec![push(*segment, *var_index)];
+            code.extend(self.emit_expr(index)?);
+            code.push(Command::Add);
+            code.extend(self.emit_expr(value)?);
+            code.extend([
+                pop(Segment::Temp, 4),
+                pop(Segment::Pointer, 1),
+                push(Segment::Temp, 4),
+                pop(Segment::That, 0),
+            ]);
+            Ok(code)
+        } else {
+            let VariableInfo { segment, index, .. } = self.lookup_var(name)?;
+            let mut code = self.emit_expr(value)?;
+            code.push(pop(*segment, *index));
+            Ok(code)
         }
     }
 
     fn emit_do(&self, node: &DoNode) -> Result<VMCode> {
         let mut code = self.emit_call(&node.call)?;
-        code += "\npop temp 3";
+        code.push(pop(Segment::Temp, 3));
         Ok(code)
     }
 
@@ -375,17 +396,13 @@ pop that 0"
         let code_else = if let Some(else_statements) = else_node {
             self.emit_statements(else_statements)?
         } else {
-            String::new()
+            vec![]
         };
-        write!(
-            code,
-            r#"
-not
-if-goto {label_else}{code_if}
-goto {label_end}
-label {label_else}{code_else}
-label {label_end}"#
-        )?;
+        code.extend([Command::Not, Command::IfGoTo(label_else.clone())]);
+        code.extend(code_if);
+        code.extend([Command::GoTo(label_end.clone()), Command::Label(label_else)]);
+        code.extend(code_else);
+        code.push(Command::Label(label_end));
         Ok(code)
     }
 
@@ -398,14 +415,12 @@ label {label_end}"#
         let label_end = self.next_label();
         let cond = self.emit_expr(condition)?;
         let body = self.emit_statements(statements)?;
-        Ok(format!(
-            r"
-label {label_cond}{cond}
-not
-if-goto {label_end}{body}
-goto {label_cond}
-label {label_end}"
-        ))
+        let mut code = vec![Command::Label(label_cond.clone())];
+        code.extend(cond);
+        code.extend([Command::Not, Command::IfGoTo(label_end.clone())]);
+        code.extend(body);
+        code.extend([Command::GoTo(label_cond), Command::Label(label_end)]);
+        Ok(code)
     }
 
     fn emit_statement(&mut self, statement: &StatementNode) -> Result<VMCode> {
@@ -418,32 +433,41 @@ label {label_end}"
         }
     }
 
-    fn emit_string(string: String) -> VMCode {
-        let len = string.len();
-        let push_chars: String = string
-            .chars()
-            .map(|x| {
-                format!(
-                    r"
-push temp 5
-push constant {}
-call String.appendChar 2
-pop temp 3",
-                    x as u16
-                )
-            })
-            .collect();
-        format!(
-            r"
-push constant {len}
-call String.new 1
-pop temp 5{push_chars}
-push temp 5"
-        )
+    fn emit_string(string: &str) -> VMCode {
+        let mut code = vec![
+            push(Segment::Constant, string.len() as u16),
+            call("String.new", 1),
+            pop(Segment::Temp, 5),
+        ];
+        for c in string.chars() {
+            code.extend([
+                push(Segment::Temp, 5),
+                push(Segment::Constant, c as u16),
+                call("String.appendChar", 2),
+                pop(Segment::Temp, 3),
+            ]);
+        }
+        code.push(push(Segment::Temp, 5));
+        code
     }
 
     fn emit_statements(&mut self, statements: &[StatementNode]) -> Result<VMCode> {
         let codes: Result<Vec<_>> = statements.iter().map(|s| self.emit_statement(s)).collect();
-        Ok(codes?.join(""))
+        Ok(codes?.concat())
+    }
+}
+
+fn push(segment: Segment, i: u16) -> Command {
+    Command::Push { segment, i }
+}
+
+fn pop(segment: Segment, i: u16) -> Command {
+    Command::Pop { segment, i }
+}
+
+fn call(name: &str, n_args: u16) -> Command {
+    Command::Call {
+        name: name.into(),
+        n_args,
     }
 }
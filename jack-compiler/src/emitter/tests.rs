@@ -0,0 +1,154 @@
+#![cfg(test)]
+
+use super::*;
+use crate::{
+    parser::Parser,
+    tokenizer::{Source, Tokenizer},
+};
+use pretty_assertions::assert_eq;
+
+fn emit(name: &str, content: &str) -> VMCode {
+    emit_with(Emitter::new(), name, content)
+}
+
+fn emit_with(mut emitter: Emitter, name: &str, content: &str) -> VMCode {
+    let source = Source {
+        name: name.to_string(),
+        content: content.to_string(),
+    };
+    let ast = Parser::new(Tokenizer::stream(&source), name.to_string())
+        .parse()
+        .unwrap();
+    emitter.emit(&ast).unwrap()
+}
+
+#[test]
+fn emits_commands() {
+    let code = emit(
+        "Main",
+        r#"
+class Main {
+    static int count;
+    field Array items;
+
+    constructor Main new() {
+        let items = Array.new(2);
+        let items[1] = "ok";
+        return this;
+    }
+
+    method int next() {
+        while (count < 3) {
+            let count = count + 1;
+        }
+        if (~(items = null)) {
+            do Output.printInt(-count * 2);
+        }
+        return count;
+    }
+}
+"#,
+    );
+    let expected = jack_vm_ir::parse(
+        "function Main.new 0
+push constant 1
+call Memory.alloc 1
+pop pointer 0
+push constant 2
+call Array.new 1
+pop this 0
+push this 0
+push constant 1
+add
+push constant 2
+call String.new 1
+pop temp 5
+push temp 5
+push constant 111
+call String.appendChar 2
+pop temp 3
+push temp 5
+push constant 107
+call String.appendChar 2
+pop temp 3
+push temp 5
+pop temp 4
+pop pointer 1
+push temp 4
+pop that 0
+push pointer 0
+return
+function Main.next 0
+push argument 0
+pop pointer 0
+label Main.LABEL.0
+push static 0
+push constant 3
+lt
+not
+if-goto Main.LABEL.1
+push static 0
+push constant 1
+add
+pop static 0
+goto Main.LABEL.0
+label Main.LABEL.1
+push this 0
+push constant 0
+eq
+not
+not
+if-goto Main.LABEL.2
+push static 0
+neg
+push constant 2
+call Math.multiply 2
+call Output.printInt 1
+pop temp 3
+goto Main.LABEL.3
+label Main.LABEL.2
+label Main.LABEL.3
+push static 0
+return
+",
+    )
+    .unwrap();
+    assert_eq!(code, expected);
+}
+
+#[test]
+fn extended_mode_uses_extension_commands() {
+    let code = emit_with(
+        Emitter::extended(),
+        "Main",
+        r#"
+class Main {
+    function int f(int x) {
+        return (x * 3) + (x / 2) + (x * 8) + (x * 1);
+    }
+}
+"#,
+    );
+    let expected = jack_vm_ir::parse(
+        "function Main.f 0
+push argument 0
+push constant 3
+mul
+push argument 0
+push constant 2
+div
+add
+push argument 0
+push constant 3
+shl
+add
+push argument 0
+push constant 1
+mul
+add
+return
+",
+    )
+    .unwrap();
+    assert_eq!(code, expected);
+}
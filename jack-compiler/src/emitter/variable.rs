@@ -1,6 +1,6 @@
 use crate::ast::TypeNode;
 
-use super::segment::Segment;
+use jack_vm_ir::Segment;
 
 #[derive(Debug)]
 pub struct VariableInfo {
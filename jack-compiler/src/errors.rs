@@ -1,39 +1,62 @@
 use thiserror::Error;
 
-use crate::{ast::TypeNode, token::Token};
+use crate::{
+    ast::{IdentifierNode, TypeNode},
+    token::{Span, Token},
+};
 
 #[derive(Error, Debug)]
 pub enum ParserError {
-    #[error("unexpected end of token stream")]
-    UnexpectedEndOfStream,
-    #[error("unexpected token {0}, expected {1}")]
+    /// The span is the one of the last token.
+    #[error("{0}: unexpected end of token stream")]
+    UnexpectedEndOfStream(Span),
+    #[error("{}: unexpected token {0}, expected {1}", .0.span)]
     UnexpectedToken(Token, String),
-    #[error("the class name \"{0}\" didn't match file name stem \"{1}\"")]
-    ClassNameMismatch(String, String),
+    #[error("{}: the class name \"{}\" didn't match file name stem \"{1}\"", .0 .1, .0 .0)]
+    ClassNameMismatch(IdentifierNode, String),
 }
 
+/// An error of the tokenizer, and where it happened.
 #[derive(Error, Debug)]
-pub enum TokenizerError {
+#[error("{span}: {kind}")]
+pub struct TokenizerError {
+    pub kind: TokenizerErrorKind,
+    pub span: Span,
+}
+
+#[derive(Error, Debug)]
+pub enum TokenizerErrorKind {
     #[error("unexpected EOF")]
     UnexpectedEOF,
     #[error("identifier should not start with a digit")]
     IdentifierStartsWithDigit,
-    #[error("invalid keyword")]
-    InvalidKeyword,
     #[error("integer {0} is out of range [0, 32767]")]
     IntegerOutOfRange(String),
     #[error("unexpected character '{0}', which should be {1}")]
     UnexpectedCharacter(char, String),
+    #[error("unterminated string constant, strings end on the line they start on")]
+    UnterminatedString,
+    #[error("unterminated block comment")]
+    UnterminatedComment,
 }
 
 #[derive(Error, Debug)]
 pub enum EmitterError {
     #[error("not in a subroutine")]
     NotInASubroutine,
-    #[error("variable \"{0}\" not found")]
-    VariableNotFound(String),
-    #[error("unexpected primitive type \"{0:?}\"")]
-    UnexpectedPrimitiveType(TypeNode),
-    #[error("expected type \"{0:?}\", found type \"{1:?}\"")]
-    MismatchedType(Option<TypeNode>, Option<TypeNode>),
+    #[error("{}: variable \"{}\" not found", .0 .1, .0 .0)]
+    VariableNotFound(IdentifierNode),
+    /// A method called on a variable whose type isn't a class.
+    #[error("{}: unexpected primitive type \"{1}\" of \"{}\"", .0 .1, .0 .0)]
+    UnexpectedPrimitiveType(IdentifierNode, TypeNode),
+    /// The return type of a constructor isn't its class.
+    #[error("{}: expected type \"{}\", found type \"{}\"", .0 .1, type_name(.1), type_name(.2))]
+    MismatchedType(IdentifierNode, Option<TypeNode>, Option<TypeNode>),
+}
+
+/// The name of a type, `void` for none.
+pub(crate) fn type_name(r#type: &Option<TypeNode>) -> String {
+    r#type
+        .as_ref()
+        .map_or("void".to_string(), |r#type| r#type.to_string())
 }
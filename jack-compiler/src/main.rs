@@ -1,56 +1,73 @@
-#![feature(is_some_with)]
 mod ast;
 mod cli;
 mod compiler;
+mod diagnostic;
 mod emitter;
 mod errors;
 mod parser;
 mod token;
 mod tokenizer;
+mod xml;
 
-use std::{fs, path::PathBuf};
+use std::{fs, process::exit};
 
 use anyhow::Result;
 use clap::Parser;
-use cli::Args;
+use cli::{Args, Emit};
 use compiler::Compiler;
+use diagnostic::{Diagnostic, ErrorFormat};
 
-fn run() -> Result<()> {
-    let args = Args::parse();
+/// Compiles every input, and writes the outputs only if all of them compiled.
+/// Returns the number of errors.
+fn run(args: &Args) -> Result<usize> {
     let inputs = args.get_inputs()?;
-    let vmcodes = inputs
-        .into_iter()
-        .map(Compiler::compile)
-        .collect::<Result<Vec<_>, anyhow::Error>>()?;
-    let mut input = PathBuf::from(args.input);
-    let input_is_single_file = input.is_file();
-    let output_path_base: PathBuf = if let Some(output_path) = args.output {
-        // --output provided
-        output_path.into()
-    } else {
-        // --output not provided
-        if input_is_single_file {
-            input.set_extension("vm");
-            input
-        } else {
-            input
+    let mut outputs = vec![];
+    let mut errors = 0;
+    for (path, source) in inputs {
+        let content = source.content.clone();
+        let max_errors = args.max_errors.into();
+        let output = match args.emit {
+            Emit::Vm => Compiler::compile(source, args.extended, max_errors)
+                .map(|(vmcode, _)| jack_vm_ir::to_vm_code(&vmcode)),
+            Emit::TokensXml => Compiler::tokens_xml(&source),
+            Emit::ParseXml => Compiler::parse_xml(&source, max_errors),
+        };
+        match output {
+            Ok(output) => outputs.push((path, output)),
+            Err(file_errors) => {
+                for e in file_errors {
+                    let diagnostic = Diagnostic::from_error(&path.display().to_string(), &e);
+                    eprintln!("{}", diagnostic.render(args.error_format, &content));
+                    if args.error_format == ErrorFormat::Human {
+                        eprintln!();
+                    }
+                    errors += 1;
+                }
+            }
+        }
+    }
+    if errors == 0 {
+        for (path, output) in outputs {
+            fs::write(args.output_path(&path), output)?;
         }
-    };
-    for (vmcode, name) in vmcodes {
-        fs::write(
-            if input_is_single_file {
-                output_path_base.clone()
-            } else {
-                output_path_base.clone().join(name + ".vm")
-            },
-            vmcode,
-        )?;
     }
-    Ok(())
+    Ok(errors)
 }
 
 fn main() {
-    if let Err(e) = run() {
-        println!("Error: {e}");
+    let args = Args::parse();
+    match run(&args) {
+        Ok(0) => {}
+        Ok(errors) => {
+            if args.error_format == ErrorFormat::Human {
+                let plural = if errors == 1 { "" } else { "s" };
+                eprintln!("error: aborting due to {errors} previous error{plural}");
+            }
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit(1);
+        }
     }
 }
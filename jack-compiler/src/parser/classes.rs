@@ -1,7 +1,7 @@
-use super::{unexpected_token, Parser};
-use crate::{ast::*, tokenizer::TokenResult};
+use super::{unexpected_token, Parser, STATEMENT_KEYWORDS, SUBROUTINE_KEYWORDS};
 use crate::errors::ParserError;
 use crate::token::*;
+use crate::{ast::*, tokenizer::TokenResult};
 use anyhow::{Ok, Result};
 
 impl<I: Iterator<Item = TokenResult>> Parser<I> {
@@ -11,23 +11,35 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
             unexpected_token!(token, "keyword \"class\"");
         }
         let name = self.eat_identifier()?;
-        if name != self.source_name() {
-            return Err(ParserError::ClassNameMismatch(
-                name.clone(),
-                self.source_name().to_string(),
-            )
-            .into());
+        if name.0 != self.source_name() {
+            let source_name = self.source_name().to_string();
+            self.report(ParserError::ClassNameMismatch(name.clone(), source_name).into());
         }
         self.eat_symbol("{")?;
-        let variables = self.parse_class_variable_declarations()?;
+        let variables = self.parse_class_variable_declarations();
         let mut subroutines = NodeCollection::new();
-        while self.look_ahead_for_subroutine_kind()?.is_some() {
-            subroutines.push(self.parse_subroutine_declaration()?);
+        loop {
+            match self.peek() {
+                Err(error) => self.report(error),
+                Result::Ok(None) => break,
+                Result::Ok(Some(token))
+                    if token.kind == TokenKind::Symbol && token.value == "}" =>
+                {
+                    break
+                }
+                Result::Ok(Some(_)) => match self.parse_subroutine_declaration() {
+                    Result::Ok(subroutine) => subroutines.push(subroutine),
+                    Err(error) => self.recover(error, &SUBROUTINE_KEYWORDS),
+                },
+            }
+        }
+        if let Err(error) = self.eat_symbol("}") {
+            self.report(error);
         }
         Ok(ClassNode {
             subroutines,
             variables,
-            name: name.into(),
+            name,
         })
     }
 
@@ -49,19 +61,24 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
 
     pub(super) fn parse_class_variable_declarations(
         &mut self,
-    ) -> Result<NodeCollection<ClassVariableDeclarationNode>> {
+    ) -> NodeCollection<ClassVariableDeclarationNode> {
+        const KEYWORDS: [&str; 5] = ["static", "field", "constructor", "function", "method"];
         let mut list = NodeCollection::new();
-        while let Some(TokenRef {
-            kind: TokenKind::Keyword,
-            value: "static" | "field",
-        }) = self.peek()?.map(|x| x.as_ref())
-        {
-            list.push(self.parse_class_variable_declaration()?)
+        loop {
+            match self.peek().map(|token| token.map(|t| t.as_ref())) {
+                Err(error) => self.report(error),
+                Result::Ok(Some(TokenRef {
+                    kind: TokenKind::Keyword,
+                    value: "static" | "field",
+                })) => match self.parse_class_variable_declaration() {
+                    Result::Ok(declaration) => list.push(declaration),
+                    Err(error) => self.recover(error, &KEYWORDS),
+                },
+                Result::Ok(_) => return list,
+            }
         }
-        Ok(list)
     }
 
-    
     pub(super) fn parse_variable_declaration(
         &mut self,
         skip_var_token: bool,
@@ -74,14 +91,14 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         }
         let r#type = self.parse_type(false)?.unwrap();
         let mut names = NodeCollection::new();
-        names.push(self.eat_identifier()?.into());
+        names.push(self.eat_identifier()?);
         while let Some(TokenRef {
             kind: TokenKind::Symbol,
             value: ",",
         }) = self.peek()?.map(|x| x.as_ref())
         {
             self.eat()?;
-            names.push(self.eat_identifier()?.into());
+            names.push(self.eat_identifier()?);
         }
         self.eat_symbol(";")?;
         Ok(VariableDeclarationNode { r#type, names })
@@ -119,7 +136,7 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         // parse (void|type)
         let return_type = self.parse_type(true)?;
         // parse subroutine name
-        let name = self.eat_identifier()?.into();
+        let name = self.eat_identifier()?;
         self.eat_symbol("(")?;
         // parse parameter list
         let parameters = self.parse_parameter_list()?;
@@ -137,7 +154,7 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
 
     pub(super) fn parse_parameter(&mut self) -> Result<ParameterNode> {
         let r#type = self.parse_type(false)?.unwrap();
-        let name = self.eat_identifier()?.into();
+        let name = self.eat_identifier()?;
         Ok(ParameterNode { r#type, name })
     }
 
@@ -162,15 +179,28 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         self.eat_symbol("{")?;
 
         let mut variables = NodeCollection::new();
-        while let Some(TokenRef {
-            kind: TokenKind::Keyword,
-            value: "var",
-        }) = self.peek()?.map(|x| x.as_ref())
-        {
-            variables.push(self.parse_variable_declaration(false)?);
+        let keywords: Vec<_> = ["var"]
+            .into_iter()
+            .chain(STATEMENT_KEYWORDS)
+            .chain(SUBROUTINE_KEYWORDS)
+            .collect();
+        loop {
+            match self.peek().map(|token| token.map(|t| t.as_ref())) {
+                Err(error) => self.report(error),
+                Result::Ok(Some(TokenRef {
+                    kind: TokenKind::Keyword,
+                    value: "var",
+                })) => match self.parse_variable_declaration(false) {
+                    Result::Ok(declaration) => variables.push(declaration),
+                    Err(error) => self.recover(error, &keywords),
+                },
+                Result::Ok(_) => break,
+            }
+        }
+        let statements = self.parse_statements();
+        if let Err(error) = self.eat_symbol("}") {
+            self.recover(error, &SUBROUTINE_KEYWORDS);
         }
-        let statements = self.parse_statements()?;
-        self.eat_symbol("}")?;
         Ok(SubroutineBody {
             statements,
             variables,
@@ -35,19 +35,26 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
             Token {
                 kind: TokenKind::IntegerConstant,
                 value,
+                ..
             } => Ok(NodeBox::new(value.parse::<u16>().unwrap().into())),
             Token {
                 kind: TokenKind::StringConstant,
                 value,
+                ..
             } => Ok(NodeBox::new(value.into())),
             Token {
                 kind: k @ TokenKind::Keyword,
                 value,
+                span,
             } => Ok(NodeBox::new(
                 KeywordConstant::try_from(value.as_str())
                     .map_err(|_| {
                         Into::<anyhow::Error>::into(ParserError::UnexpectedToken(
-                            Token { kind: k, value },
+                            Token {
+                                kind: k,
+                                value,
+                                span,
+                            },
                             r#""this", "null", "true" or "false""#.to_string(),
                         ))
                     })?
@@ -56,21 +63,25 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
             Token {
                 kind: TokenKind::Symbol,
                 value,
+                ..
             } if value == "(" => {
                 let expr = self.parse_expression()?;
                 self.eat_symbol(")")?;
-                Ok(NodeBox::new(TermNode::Parentheses(
-                    NodeBox::new(expr).into(),
-                )))
+                Ok(NodeBox::new(TermNode::Parentheses(NodeBox::new(expr))))
             }
             Token {
                 kind: k @ TokenKind::Symbol,
                 value,
+                span,
             } => {
                 let operator = UnaryOperator::try_from(value.as_str()).map_err(|_| {
                     ParserError::UnexpectedToken(
-                        Token { kind: k, value },
-                        "\"-\" or \"~\"".to_string(),
+                        Token {
+                            kind: k,
+                            value,
+                            span,
+                        },
+                        "an expression".to_string(),
                     )
                 })?;
                 let subject = self.parse_term()?;
@@ -81,27 +92,33 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
             Token {
                 kind: k @ TokenKind::Identifier,
                 value,
+                span,
             } => match self.peek()? {
                 Some(Token {
                     kind: TokenKind::Symbol,
                     value: symbol,
+                    ..
                 }) => Ok(NodeBox::new(match symbol.as_str() {
                     "." => {
-                        self.store_token(Token { kind: k, value });
+                        self.store_token(Token {
+                            kind: k,
+                            value,
+                            span,
+                        });
                         let call = self.parse_subroutine_call()?;
                         call.into()
                     }
                     "[" => {
                         let index = self.parse_array_index()?.unwrap();
                         ArrayElementNode {
-                            name: value.into(),
+                            name: IdentifierNode(value, span),
                             index,
                         }
                         .into()
                     }
-                    _ => IdentifierNode(value).into(),
+                    _ => IdentifierNode(value, span).into(),
                 })),
-                None => Ok(NodeBox::new(IdentifierNode(value).into())),
+                None => Ok(NodeBox::new(IdentifierNode(value, span).into())),
                 token => unexpected_token!(token.unwrap().clone(), "symbol or nothing"),
             },
         }
@@ -118,10 +135,10 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
     }
 
     pub(super) fn parse_subroutine_call(&mut self) -> Result<SubroutineCallNode> {
-        let first = self.eat_identifier()?.into();
+        let first = self.eat_identifier()?;
         let (this, name) = if self.look_ahead_for_symbol(".")? {
             self.eat()?;
-            (Some(first), self.eat_identifier()?.into())
+            (Some(first), self.eat_identifier()?)
         } else {
             (None, first)
         };
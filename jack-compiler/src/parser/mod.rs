@@ -5,18 +5,28 @@ mod statements;
 mod tests;
 use self::macros::unexpected_token;
 use super::errors::ParserError;
-use super::token::{Token, TokenKind, TokenRef};
-use super::{ast::AST, tokenizer::TokenResult};
+use super::token::{Span, Token, TokenKind, TokenRef};
+use super::{ast::Ast, tokenizer::TokenResult};
 use crate::ast::*;
-use anyhow::{Ok, Result};
+use anyhow::Result;
 
 pub struct Parser<I: Iterator<Item = TokenResult>> {
     token_stream: I,
     token_buffer: Option<Token>,
     token_storage: Option<Token>,
     source_name: String,
+    /// Span of the last token taken, where the stream ended if it did.
+    last_span: Span,
+    /// The syntax errors the parser recovered from.
+    errors: Vec<anyhow::Error>,
+    max_errors: usize,
 }
 
+/// Keywords statements start with.
+const STATEMENT_KEYWORDS: [&str; 5] = ["let", "if", "while", "do", "return"];
+/// Keywords subroutine declarations start with.
+const SUBROUTINE_KEYWORDS: [&str; 3] = ["constructor", "function", "method"];
+
 impl Token {
     pub fn should_eq(self, token: TokenRef) -> Result<Token> {
         if self == token {
@@ -34,11 +44,111 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
             token_buffer: None,
             token_storage: None,
             source_name,
+            last_span: Span::default(),
+            errors: vec![],
+            max_errors: usize::MAX,
         }
     }
 
-    pub fn parse(&mut self) -> Result<AST> {
-        self.parse_class()
+    /// Keeps at most `max` syntax errors, the later ones are dropped.
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.max_errors = max;
+        self
+    }
+
+    /// Parses the class, failing with its first syntax error.
+    #[cfg(test)]
+    pub fn parse(&mut self) -> Result<Ast> {
+        match self.parse_all() {
+            (Some(ast), errors) if errors.is_empty() => Ok(ast),
+            (_, mut errors) => Err(errors.remove(0)),
+        }
+    }
+
+    /// Parses the class, going on after syntax errors to find all of them in one pass.
+    /// Returns what could be parsed of the class, without the declarations and statements
+    /// that have errors, along with the errors.
+    pub fn parse_all(&mut self) -> (Option<Ast>, Vec<anyhow::Error>) {
+        let ast = match self.parse_class() {
+            Ok(ast) => Some(ast),
+            Err(error) => {
+                self.report(error);
+                None
+            }
+        };
+        if ast.is_some() {
+            // nothing may follow the class
+            match self.peek() {
+                Ok(Some(token)) => {
+                    let token = token.clone();
+                    self.report(
+                        ParserError::UnexpectedToken(token, "end of file".to_string()).into(),
+                    );
+                }
+                Ok(None) => {}
+                Err(error) => self.report(error),
+            }
+        }
+        (ast, std::mem::take(&mut self.errors))
+    }
+
+    /// Records a syntax error. The end of the stream is only reported once,
+    /// as every unfinished node runs into it.
+    fn report(&mut self, error: anyhow::Error) {
+        let is_end = |error: &anyhow::Error| {
+            matches!(
+                error.downcast_ref(),
+                Some(ParserError::UnexpectedEndOfStream(_))
+            )
+        };
+        if is_end(&error) && self.errors.iter().any(is_end) {
+            return;
+        }
+        if self.errors.len() < self.max_errors {
+            self.errors.push(error);
+        }
+    }
+
+    /// Records a syntax error, then skips tokens up to where parsing can go on:
+    /// past the next `;`, or up to the next `}` or one of `keywords`.
+    /// Blocks in braces are skipped as a whole.
+    fn recover(&mut self, error: anyhow::Error, keywords: &[&str]) {
+        let is_symbol =
+            |token: &Token, symbol| token.kind == TokenKind::Symbol && token.value == symbol;
+        let synchronizes = |token: &Token| {
+            is_symbol(token, ";")
+                || is_symbol(token, "}")
+                || token.kind == TokenKind::Keyword && keywords.contains(&token.value.as_str())
+        };
+        // give back the unexpected token if it was taken and skipping should start with it
+        if let Some(ParserError::UnexpectedToken(token, _)) = error.downcast_ref() {
+            if token.span == self.last_span && (synchronizes(token) || is_symbol(token, "{")) {
+                self.store_token(token.clone());
+            }
+        }
+        self.report(error);
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                Err(error) => self.report(error),
+                Ok(None) => return,
+                Ok(Some(token)) => {
+                    let semicolon = is_symbol(token, ";");
+                    if depth == 0 && synchronizes(token) && !semicolon {
+                        return;
+                    }
+                    if is_symbol(token, "{") {
+                        depth += 1;
+                    } else if is_symbol(token, "}") {
+                        depth -= 1;
+                    }
+                    self.token_buffer = self.token_storage.take();
+                    if depth == 0 && semicolon {
+                        return;
+                    }
+                }
+            }
+        }
     }
 
     pub fn source_name(&self) -> &str {
@@ -47,15 +157,17 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
 
     /// grab next token with confidence
     fn next_token(&mut self) -> Result<Token> {
-        Ok(if self.token_buffer.is_some() {
+        let token = if self.token_buffer.is_some() {
             let v = self.token_buffer.take().unwrap();
             self.token_buffer = self.token_storage.take();
             v
         } else {
             self.token_stream
                 .next()
-                .ok_or(ParserError::UnexpectedEndOfStream)??
-        })
+                .ok_or(ParserError::UnexpectedEndOfStream(self.last_span))??
+        };
+        self.last_span = token.span;
+        Ok(token)
     }
 
     /// put back one token into buffer
@@ -84,10 +196,10 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         Ok(())
     }
 
-    fn eat_identifier(&mut self) -> Result<String> {
+    fn eat_identifier(&mut self) -> Result<IdentifierNode> {
         let token = self.next_token()?;
         if token.kind == TokenKind::Identifier {
-            Ok(token.value)
+            Ok(IdentifierNode(token.value, token.span))
         } else {
             unexpected_token!(token, "identifier");
         }
@@ -133,7 +245,10 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
                 _ => unexpected_token!(token, "{}", err_msg),
             }
         } else if token.kind == TokenKind::Identifier {
-            Ok(Some(TypeNode::Class(token.value.into())))
+            Ok(Some(TypeNode::Class(IdentifierNode(
+                token.value,
+                token.span,
+            ))))
         } else {
             unexpected_token!(token, "{}", err_msg);
         }
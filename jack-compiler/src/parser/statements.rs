@@ -1,38 +1,70 @@
-use super::Parser;
+use super::{Parser, STATEMENT_KEYWORDS, SUBROUTINE_KEYWORDS};
 use crate::ast::*;
+use crate::errors::ParserError;
 use crate::token::*;
 use crate::tokenizer::TokenResult;
 use anyhow::Result;
 
 impl<I: Iterator<Item = TokenResult>> Parser<I> {
-    pub(super) fn parse_statements(&mut self) -> Result<NodeCollection<StatementNode>> {
+    /// Parses statements up to the `}` closing them, leaving out the ones with syntax errors.
+    /// A subroutine keyword ends them too, as the `}` is likely missing.
+    pub(super) fn parse_statements(&mut self) -> NodeCollection<StatementNode> {
+        let keywords: Vec<_> = STATEMENT_KEYWORDS
+            .into_iter()
+            .chain(SUBROUTINE_KEYWORDS)
+            .collect();
         let mut list: Vec<StatementNode> = NodeCollection::new();
-        while let Some(TokenRef {
-            kind: TokenKind::Keyword,
-            value: val @ ("let" | "if" | "while" | "do" | "return"),
-        }) = self.peek()?.map(|t| t.as_ref())
-        {
-            list.push(match val {
-                "let" => self.parse_let_statement()?.into(),
-                "if" => self.parse_if_statement()?.into(),
-                "while" => self.parse_while_statement()?.into(),
-                "do" => self.parse_do_statement()?.into(),
-                "return" => self.parse_return_statement()?.into(),
-                _ => panic!("Unreachable code"),
-            })
+        loop {
+            let statement = match self.peek().map(|token| token.map(|t| t.as_ref())) {
+                Err(error) => {
+                    self.report(error);
+                    continue;
+                }
+                Ok(Some(TokenRef {
+                    kind: TokenKind::Keyword,
+                    value: val @ ("let" | "if" | "while" | "do" | "return"),
+                })) => match val {
+                    "let" => self.parse_let_statement().map(Into::into),
+                    "if" => self.parse_if_statement().map(Into::into),
+                    "while" => self.parse_while_statement().map(Into::into),
+                    "do" => self.parse_do_statement().map(Into::into),
+                    "return" => self.parse_return_statement().map(Into::into),
+                    _ => panic!("Unreachable code"),
+                },
+                Ok(None) => return list,
+                Ok(Some(TokenRef {
+                    kind: TokenKind::Symbol,
+                    value: "}",
+                })) => return list,
+                Ok(Some(TokenRef {
+                    kind: TokenKind::Keyword,
+                    value,
+                })) if SUBROUTINE_KEYWORDS.contains(&value) => return list,
+                Ok(Some(_)) => match self.next_token() {
+                    Ok(token) => Err(ParserError::UnexpectedToken(
+                        token,
+                        r#""let", "if", "while", "do" or "return""#.to_string(),
+                    )
+                    .into()),
+                    Err(error) => Err(error),
+                },
+            };
+            match statement {
+                Ok(statement) => list.push(statement),
+                Err(error) => self.recover(error, &keywords),
+            }
         }
-        Ok(list)
     }
 
     pub(super) fn parse_let_statement(&mut self) -> Result<LetNode> {
         // The parse_statements method guarantees this token is "let"
         self.eat()?;
-        let name = self.eat_identifier()?.into();
+        let name = self.eat_identifier()?;
         let index = self.parse_array_index()?;
         self.eat_symbol("=")?;
         let value = self.parse_expression()?;
         self.eat_symbol(";")?;
-        return Ok(LetNode { name, index, value });
+        Ok(LetNode { name, index, value })
     }
 
     pub(super) fn parse_if_statement(&mut self) -> Result<IfElseNode> {
@@ -42,14 +74,15 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         let condition = self.parse_expression()?;
         self.eat_symbol(")")?;
         self.eat_symbol("{")?;
-        let statements = self.parse_statements()?;
+        let statements = self.parse_statements();
         self.eat_symbol("}")?;
-        let else_node = if self.peek()?.is_some_and(
-            |t| t.kind == TokenKind::Keyword && t.value == "else"
-        ) {
+        let else_node = if self
+            .peek()?
+            .is_some_and(|t| t.kind == TokenKind::Keyword && t.value == "else")
+        {
             self.eat()?;
             self.eat_symbol("{")?;
-            let statements = self.parse_statements()?;
+            let statements = self.parse_statements();
             self.eat_symbol("}")?;
             Some(statements)
         } else {
@@ -69,7 +102,7 @@ impl<I: Iterator<Item = TokenResult>> Parser<I> {
         let condition = self.parse_expression()?;
         self.eat_symbol(")")?;
         self.eat_symbol("{")?;
-        let statements = self.parse_statements()?;
+        let statements = self.parse_statements();
         self.eat_symbol("}")?;
         Ok(WhileNode {
             condition,
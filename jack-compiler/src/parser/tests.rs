@@ -686,3 +686,137 @@ test_parser!(
         }
     )
 );
+
+fn parse_all(content: &str) -> (Option<Ast>, Vec<String>) {
+    let source = Source {
+        name: "Main".to_string(),
+        content: content.to_string(),
+    };
+    let (ast, errors) = Parser::new(Tokenizer::stream(&source), "Main".to_string()).parse_all();
+    (ast, errors.iter().map(ToString::to_string).collect())
+}
+
+#[test]
+fn recovers_from_syntax_errors() {
+    let (ast, errors) = parse_all(
+        r#"class Main {
+    static int ;
+    static boolean ready;
+    function void main() {
+        var int x, ;
+        var int y;
+        let x = ;
+        do Output.printInt(x);
+        if (x { let y = 1; }
+        let y = 2 3;
+        return;
+    }
+    method int (int a) { return a; }
+    function void other() {
+        let # = 1;
+        return;
+    }
+}
+"#,
+    );
+    assert_eq!(
+        errors,
+        [
+            r#"2:16: unexpected token symbol ";", expected identifier"#,
+            r#"5:20: unexpected token symbol ";", expected identifier"#,
+            r#"7:17: unexpected token symbol ";", expected an expression"#,
+            r#"9:15: unexpected token symbol "{", expected symbol ")""#,
+            r#"10:19: unexpected token integer constant "3", expected symbol ";""#,
+            r#"13:16: unexpected token symbol "(", expected identifier"#,
+            "15:13: unexpected character '#', which should be a valid character",
+        ]
+    );
+    assert_eq!(
+        ast.unwrap(),
+        n_class!(
+            "Main",
+            vec![
+                n_subroutine! {
+                    Function void main() {
+                        variables: {
+                            int y;
+                        },
+                        statements: [
+                            {do Output.printInt(n_e!(x))},
+                            {return}
+                        ]
+                    }
+                },
+                n_subroutine! {
+                    Function void other() {
+                        {return}
+                    }
+                }
+            ],
+            n_class_vars! {
+                Static boolean ready;
+            }
+        )
+    );
+}
+
+#[test]
+fn reports_the_end_of_the_file_once() {
+    let (ast, errors) = parse_all("class Main {\n  function void main() {\n    if (true) {\n");
+    assert_eq!(errors, ["3:15: unexpected end of token stream"]);
+    assert_eq!(ast.unwrap().subroutines.len(), 1);
+}
+
+#[test]
+fn caps_the_errors() {
+    let source = Source {
+        name: "Main".to_string(),
+        content: "class Main { function void main() { let = 1; let = 2; let = 3; return; } }"
+            .to_string(),
+    };
+    let (ast, errors) = Parser::new(Tokenizer::stream(&source), "Main".to_string())
+        .max_errors(2)
+        .parse_all();
+    assert_eq!(errors.len(), 2);
+    assert!(ast.is_some());
+}
+
+#[test]
+fn recovers_from_statements_without_a_keyword() {
+    let (ast, errors) = parse_all(
+        r#"class Main {
+    function void main() {
+        var int x;
+        x = 5;
+        do Output.printInt(x);
+        return;
+    }
+    function void other() {
+        var int y;
+        let y = ;
+        return;
+    }
+}
+"#,
+    );
+    assert_eq!(
+        errors,
+        [
+            r#"4:9: unexpected token identifier "x", expected "let", "if", "while", "do" or "return""#,
+            r#"10:17: unexpected token symbol ";", expected an expression"#,
+        ]
+    );
+    let ast = ast.unwrap();
+    assert_eq!(ast.subroutines.len(), 2);
+    assert_eq!(ast.subroutines[0].body.statements.len(), 2);
+}
+
+#[test]
+fn reports_tokens_after_the_class() {
+    let (ast, errors) = parse_all("class Main {\n}\n}\nclass Other {}\n");
+    assert_eq!(
+        errors,
+        [r#"3:1: unexpected token symbol "}", expected end of file"#]
+    );
+    assert!(ast.is_some());
+}
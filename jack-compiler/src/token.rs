@@ -1,9 +1,26 @@
 use std::fmt::Display;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Where a token is in its source: the byte offsets of its start and end,
+/// and the line and column it starts at, counting from 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) line: u32,
+    pub(crate) column: u32,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
 pub struct Token {
     pub(crate) kind: TokenKind,
     pub(crate) value: String,
+    pub(crate) span: Span,
 }
 
 pub struct TokenRef<'a> {
@@ -20,6 +37,13 @@ impl Token {
     }
 }
 
+/// Tokens are equal when their kind and value are, wherever they are.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.value == other.value
+    }
+}
+
 impl<'a> PartialEq<TokenRef<'a>> for Token {
     fn eq(&self, other: &TokenRef<'a>) -> bool {
         &self.kind == other.kind && self.value == other.value
@@ -66,6 +90,7 @@ pub(super) mod builder {
             Token {
                 kind: TokenKind::$kind,
                 value: $value.to_owned(),
+                span: Span::default(),
             }
         };
     }
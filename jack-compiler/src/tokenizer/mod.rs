@@ -1,11 +1,8 @@
 mod test;
 mod token_stream;
 
-use lazy_static::lazy_static;
-use regex::Regex;
-
-use super::errors::TokenizerError;
-use super::token::{Token, TokenKind};
+use super::errors::TokenizerErrorKind;
+use super::token::{Span, Token, TokenKind};
 pub(crate) use token_stream::{TokenResult, TokenStream};
 
 pub struct Source {
@@ -15,49 +12,54 @@ pub struct Source {
 
 pub struct Tokenizer;
 
-macro_rules! tokenize_keyword_arm {
-    ($data:ident, $len: expr, $($l:literal),+) => {
-        if match $data.chars().skip($len).next() {
-            Some(c) => !c.is_ascii_alphabetic(),
-            None => true
-        } && ($($data.starts_with($l))||+) {
-            return Ok(
-                (Token {
-                    kind: TokenKind::Keyword,
-                    value: $data[..$len].to_string()
-                }, $len)
-            )
-        }
-    }
-}
+/// The 21 keywords of Jack.
+const KEYWORDS: [&str; 21] = [
+    "class",
+    "constructor",
+    "function",
+    "method",
+    "field",
+    "static",
+    "var",
+    "int",
+    "char",
+    "boolean",
+    "void",
+    "true",
+    "false",
+    "null",
+    "this",
+    "let",
+    "do",
+    "if",
+    "else",
+    "while",
+    "return",
+];
 
-type TokenizationResult = Result<(Token, usize), TokenizerError>;
+/// A token, without its span, and its length in bytes.
+type TokenizationResult = Result<(Token, usize), TokenizerErrorKind>;
 
 impl Tokenizer {
-    pub fn stream<'a>(source: &'a Source) -> TokenStream<'a> {
-        lazy_static! {
-            // BUG: This is just a simple tokenizer. We assume that the comment syntax won't show up in string literals.
-            static ref REMOVE_COMMENTS_AND_LONG_SPACES: Regex =
-                Regex::new(r#"/\*(.|\n)*?\*/|//.*|\s+"#).unwrap();
-        }
-        let Source {
-            content: source,
-            name: _,
-        } = source;
-        TokenStream::new(REMOVE_COMMENTS_AND_LONG_SPACES.replace_all(&source, " "))
+    pub fn stream(source: &Source) -> TokenStream<'_> {
+        TokenStream::new(&source.content)
     }
 
-    fn tokenize_keyword(data: &str) -> TokenizationResult {
-        // 21 keywords
-        tokenize_keyword_arm!(data, 2, "do", "if");
-        tokenize_keyword_arm!(data, 3, "var", "int", "let");
-        tokenize_keyword_arm!(data, 4, "char", "void", "true", "null", "this", "else");
-        tokenize_keyword_arm!(data, 5, "class", "field", "false", "while");
-        tokenize_keyword_arm!(data, 6, "method", "static", "return");
-        tokenize_keyword_arm!(data, 7, "boolean");
-        tokenize_keyword_arm!(data, 8, "function");
-        tokenize_keyword_arm!(data, 11, "constructor");
-        Err(TokenizerError::InvalidKeyword)
+    /// A keyword or an identifier, depending on the whole word,
+    /// so that identifiers like `do_it` and `if1` aren't split after the keyword they start with.
+    fn tokenize_word(data: &str) -> TokenizationResult {
+        let (token, len) = Self::tokenize_identifier(data)?;
+        if KEYWORDS.contains(&token.value.as_str()) {
+            Ok((
+                Token {
+                    kind: TokenKind::Keyword,
+                    ..token
+                },
+                len,
+            ))
+        } else {
+            Ok((token, len))
+        }
     }
 
     fn tokenize_integer(data: &str) -> TokenizationResult {
@@ -69,36 +71,38 @@ impl Tokenizer {
                 Token {
                     kind: TokenKind::IntegerConstant,
                     value: num,
+                    span: Span::default(),
                 },
                 len,
             ))
         } else {
-            Err(TokenizerError::IntegerOutOfRange(num))
+            Err(TokenizerErrorKind::IntegerOutOfRange(num))
         }
     }
 
     fn tokenize_string(data: &str) -> TokenizationResult {
         // Jack string literal: there is no \n or " inside literal.
-        let mut iter = data.chars();
-        let begin = iter.next().ok_or(TokenizerError::UnexpectedEOF)?;
-
-        if begin != '"' {
-            return Err(TokenizerError::UnexpectedCharacter(begin, "\"".to_string()));
-        }
-        let literal: String = iter.take_while(|c| *c != '"').collect();
-        let len = literal.len() + 2;
-        let end = data
+        let begin = data
             .chars()
-            .skip(len - 1)
             .next()
-            .ok_or(TokenizerError::UnexpectedEOF)?;
-        if end != '"' {
-            return Err(TokenizerError::UnexpectedCharacter(end, "\"".to_string()));
+            .ok_or(TokenizerErrorKind::UnexpectedEOF)?;
+        if begin != '"' {
+            return Err(TokenizerErrorKind::UnexpectedCharacter(
+                begin,
+                "\"".to_string(),
+            ));
         }
+        // the literal is kept as is, comments and spaces included
+        let literal = match data[1..].find(['"', '\n']) {
+            Some(end) if data[1 + end..].starts_with('"') => data[1..1 + end].to_string(),
+            _ => return Err(TokenizerErrorKind::UnterminatedString),
+        };
+        let len = literal.len() + 2;
         Ok((
             Token {
                 kind: TokenKind::StringConstant,
                 value: literal,
+                span: Span::default(),
             },
             len,
         ))
@@ -107,9 +111,12 @@ impl Tokenizer {
     fn tokenize_identifier(data: &str) -> TokenizationResult {
         // Jack identifier: A seq of letters, digits, and underscore, not starting with a digit
 
-        let begin = data.chars().next().ok_or(TokenizerError::UnexpectedEOF)?;
+        let begin = data
+            .chars()
+            .next()
+            .ok_or(TokenizerErrorKind::UnexpectedEOF)?;
         if begin.is_ascii_digit() {
-            return Err(TokenizerError::IdentifierStartsWithDigit);
+            return Err(TokenizerErrorKind::IdentifierStartsWithDigit);
         }
 
         let identifier: String = data
@@ -121,28 +128,31 @@ impl Tokenizer {
             Token {
                 kind: TokenKind::Identifier,
                 value: identifier,
+                span: Span::default(),
             },
             len,
         ))
     }
 
     fn tokenize_one_token(data: &str) -> TokenizationResult {
-        let begin = data.chars().next().ok_or(TokenizerError::UnexpectedEOF)?;
+        let begin = data
+            .chars()
+            .next()
+            .ok_or(TokenizerErrorKind::UnexpectedEOF)?;
         match begin {
             symbol @ ('{' | '}' | '(' | ')' | '[' | ']' | '.' | ',' | ';' | '+' | '-' | '*'
             | '/' | '&' | '|' | '<' | '>' | '=' | '~') => Ok((
                 Token {
                     kind: TokenKind::Symbol,
                     value: symbol.to_string(),
+                    span: Span::default(),
                 },
                 1,
             )),
             digit if digit.is_ascii_digit() => Self::tokenize_integer(data),
             '"' => Self::tokenize_string(data),
-            char if char.is_ascii_alphanumeric() || char == '_' => {
-                Self::tokenize_keyword(data).or_else(|_| Self::tokenize_identifier(data))
-            }
-            c => Err(TokenizerError::UnexpectedCharacter(
+            char if char.is_ascii_alphanumeric() || char == '_' => Self::tokenize_word(data),
+            c => Err(TokenizerErrorKind::UnexpectedCharacter(
                 c,
                 "a valid character".to_string(),
             )),
@@ -2,6 +2,7 @@
 
 use super::*;
 use crate::token::builder::token;
+use crate::token::Span;
 
 macro_rules! test_tokenizer {
     ($name:ident, $source:expr, $output:expr) => {
@@ -19,6 +20,56 @@ macro_rules! test_tokenizer {
     };
 }
 
+#[test]
+fn spans() {
+    let source = Source {
+        name: "Test".to_string(),
+        content: "class Main {\n  /* a\n comment */ field int x; // x\n  \"é\" 12\n}".to_string(),
+    };
+    let spans: Vec<_> = Tokenizer::stream(&source)
+        .map(|token| {
+            let token = token.unwrap();
+            let Span {
+                start,
+                end,
+                line,
+                column,
+            } = token.span;
+            assert_eq!(source.content[start..end].trim_matches('"'), token.value);
+            (token.value, line, column)
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("class", 1, 1),
+            ("Main", 1, 7),
+            ("{", 1, 12),
+            ("field", 3, 13),
+            ("int", 3, 19),
+            ("x", 3, 23),
+            (";", 3, 24),
+            ("é", 4, 3),
+            ("12", 4, 7),
+            ("}", 5, 1),
+        ]
+        .map(|(value, line, column)| (value.to_string(), line, column))
+    );
+}
+
+#[test]
+fn errors_have_positions() {
+    let source = Source {
+        name: "Test".to_string(),
+        content: "let x = 1;\n  let y = 99999;".to_string(),
+    };
+    let error = Tokenizer::stream(&source).find_map(Result::err).unwrap();
+    assert_eq!(
+        error.to_string(),
+        "2:11: integer 99999 is out of range [0, 32767]"
+    );
+}
+
 #[test]
 fn remove_comments() {
     let source = Source {
@@ -106,11 +157,17 @@ class ParticleSystem {
 }"##
         .to_string(),
     };
-    let stream = Tokenizer::stream(&source);
-    assert_eq!(
-        stream.source.to_string(),
-        r##"   class ParticleSystem { field int count; field Array particles; constructor ParticleSystem new() { let count = 0; return this; }   method void update() { var int i; var Particle cur; let i = 0; while (i < count) { let cur = particles[i]; do cur.update(); let i = i + 1; } return; }   method void generate() { var int i, xsign, ysign; var Particle p; let i = 0; let xsign = 1; let ysign = 1; while (i < count) { if (Random.rand() > 16383) { let xsign = -xsign; } if (Random.rand() > 16383) { let ysign = -ysign; } let p = Particle.new(Random.randRange(508-3)+3, Random.randRange(252-3)+3, xsign * Random.randRange(3), ysign * Random.randRange(3)); let particles[i] = p; let i = i + 1; } return; }   method void run() { var char key;   var boolean exit; do Random.setSeed(2333); do Output.printString("Welcome to particle system!"); do Output.println(); let count = Keyboard.readInt("How many particles do you want to render?> "); while ((count < 0) | (count = 0)) { let count = Keyboard.readInt("Please enter a positive number!> "); } do Output.printString("Generating particles, please wait..."); do Output.println(); let particles = Array.new(count); do generate(); do Output.printString("Done generation! Press any key to start simulation."); do Output.println(); do Keyboard.readChar(); do Screen.clearScreen(); let exit = false; while (~exit) { let key = Keyboard.keyPressed(); do update(); if (key = 81) { let exit = true; }   } return; } method void dispose() { do particles.dispose(); do Memory.deAlloc(this); return; } }"##
-    );
+    let expected = Source {
+        name: "Test".to_string(),
+        content: r##"   class ParticleSystem { field int count; field Array particles; constructor ParticleSystem new() { let count = 0; return this; }   method void update() { var int i; var Particle cur; let i = 0; while (i < count) { let cur = particles[i]; do cur.update(); let i = i + 1; } return; }   method void generate() { var int i, xsign, ysign; var Particle p; let i = 0; let xsign = 1; let ysign = 1; while (i < count) { if (Random.rand() > 16383) { let xsign = -xsign; } if (Random.rand() > 16383) { let ysign = -ysign; } let p = Particle.new(Random.randRange(508-3)+3, Random.randRange(252-3)+3, xsign * Random.randRange(3), ysign * Random.randRange(3)); let particles[i] = p; let i = i + 1; } return; }   method void run() { var char key;   var boolean exit; do Random.setSeed(2333); do Output.printString("Welcome to particle system!"); do Output.println(); let count = Keyboard.readInt("How many particles do you want to render?> "); while ((count < 0) | (count = 0)) { let count = Keyboard.readInt("Please enter a positive number!> "); } do Output.printString("Generating particles, please wait..."); do Output.println(); let particles = Array.new(count); do generate(); do Output.printString("Done generation! Press any key to start simulation."); do Output.println(); do Keyboard.readChar(); do Screen.clearScreen(); let exit = false; while (~exit) { let key = Keyboard.keyPressed(); do update(); if (key = 81) { let exit = true; }   } return; } method void dispose() { do particles.dispose(); do Memory.deAlloc(this); return; } }"##
+        .to_string(),
+    };
+    let tokens = |source| {
+        Tokenizer::stream(source)
+            .collect::<Result<Vec<Token>, _>>()
+            .unwrap()
+    };
+    assert_eq!(tokens(&source), tokens(&expected));
 }
 
 test_tokenizer!(
@@ -1541,3 +1598,110 @@ test_tokenizer!(
         token!(Symbol, "}"),
     ]
 );
+
+test_tokenizer!(
+    strings_keep_comments_and_spaces,
+    r#"do Output.printString("a  b // c /* d */"); // "e"
+/* "f" */ let s = "";"#,
+    vec![
+        token!(Keyword, "do"),
+        token!(Identifier, "Output"),
+        token!(Symbol, "."),
+        token!(Identifier, "printString"),
+        token!(Symbol, "("),
+        token!(StringConstant, "a  b // c /* d */"),
+        token!(Symbol, ")"),
+        token!(Symbol, ";"),
+        token!(Keyword, "let"),
+        token!(Identifier, "s"),
+        token!(Symbol, "="),
+        token!(StringConstant, ""),
+        token!(Symbol, ";"),
+    ]
+);
+
+fn errors(content: &str) -> Vec<String> {
+    let source = Source {
+        name: "Test".to_string(),
+        content: content.to_string(),
+    };
+    Tokenizer::stream(&source)
+        .filter_map(Result::err)
+        .map(|err| err.to_string())
+        .collect()
+}
+
+#[test]
+fn unterminated_strings_and_comments() {
+    assert_eq!(
+        errors("let s = \"abc;\nlet t = \"x\";"),
+        ["1:9: unterminated string constant, strings end on the line they start on"]
+    );
+    assert_eq!(
+        errors("let s = 1;\n  let t = \"abc"),
+        ["2:11: unterminated string constant, strings end on the line they start on"]
+    );
+    assert_eq!(
+        errors("let s = \"*/\";\n  /* \"a\" */ /* never\n closed"),
+        ["2:13: unterminated block comment"]
+    );
+}
+
+fn tokens(content: &str) -> Vec<Token> {
+    let source = Source {
+        name: "Test".to_string(),
+        content: content.to_string(),
+    };
+    Tokenizer::stream(&source)
+        .collect::<Result<Vec<Token>, _>>()
+        .unwrap()
+}
+
+#[test]
+fn keywords_are_whole_words() {
+    for keyword in KEYWORDS {
+        assert_eq!(tokens(keyword), [token!(Keyword, keyword)]);
+        assert_eq!(
+            tokens(&format!("{keyword}(")),
+            [token!(Keyword, keyword), token!(Symbol, "(")]
+        );
+        for identifier in [
+            format!("{keyword}_"),
+            format!("{keyword}1"),
+            format!("{keyword}x"),
+            format!("{keyword}_it"),
+            format!("{keyword}X2"),
+            format!("_{keyword}"),
+            format!("a{keyword}"),
+            format!("{keyword}{keyword}"),
+        ] {
+            assert_eq!(
+                tokens(&identifier),
+                [token!(Identifier, identifier)],
+                "{identifier}"
+            );
+        }
+    }
+}
+
+test_tokenizer!(
+    identifiers_starting_with_keywords,
+    "do do_it(if1, this_x, var2); let letter = classic;",
+    vec![
+        token!(Keyword, "do"),
+        token!(Identifier, "do_it"),
+        token!(Symbol, "("),
+        token!(Identifier, "if1"),
+        token!(Symbol, ","),
+        token!(Identifier, "this_x"),
+        token!(Symbol, ","),
+        token!(Identifier, "var2"),
+        token!(Symbol, ")"),
+        token!(Symbol, ";"),
+        token!(Keyword, "let"),
+        token!(Identifier, "letter"),
+        token!(Symbol, "="),
+        token!(Identifier, "classic"),
+        token!(Symbol, ";"),
+    ]
+);
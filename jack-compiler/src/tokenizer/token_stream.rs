@@ -1,53 +1,114 @@
 use super::*;
-use std::borrow::Cow;
+use crate::errors::TokenizerError;
 
+/// Lexes a source in a single pass, skipping whitespace and comments,
+/// and keeping track of where each token is.
 pub struct TokenStream<'a> {
-    pub(super) source: Cow<'a, str>,
+    pub(super) source: &'a str,
     pub(super) offset: usize,
+    line: u32,
+    column: u32,
 }
 
 pub type TokenResult = Result<Token, TokenizerError>;
 
 impl<'a> TokenStream<'a> {
-    pub fn new(source: Cow<'a, str>) -> Self {
+    pub fn new(source: &'a str) -> Self {
         Self {
             source,
             offset: 0,
+            line: 1,
+            column: 1,
         }
     }
 
-    fn eat_whitespace(&mut self) {
-        let count = self
-            .source()
-            .chars()
-            .take_while(|s| s.is_whitespace())
-            .count();
-        self.eat(count);
+    /// Skips whitespace, `// line` comments and `/* block */` comments.
+    /// A block comment that doesn't end is skipped with the rest of the source, and reported.
+    fn eat_whitespace_and_comments(&mut self) -> Result<(), TokenizerError> {
+        loop {
+            let source = self.source();
+            let len = if source.starts_with("//") {
+                source.find('\n').unwrap_or(source.len())
+            } else if let Some(comment) = source.strip_prefix("/*") {
+                match comment.find("*/") {
+                    Some(end) => end + 4,
+                    None => {
+                        let span = self.span(2);
+                        self.eat(source.len());
+                        return Err(TokenizerError {
+                            kind: TokenizerErrorKind::UnterminatedComment,
+                            span,
+                        });
+                    }
+                }
+            } else {
+                source
+                    .find(|c: char| !c.is_whitespace())
+                    .unwrap_or(source.len())
+            };
+            if len == 0 {
+                return Ok(());
+            }
+            self.eat(len);
+        }
     }
 
-    fn eat(&mut self, offset: usize) {
-        self.offset += offset;
+    /// Moves `len` bytes forward.
+    fn eat(&mut self, len: usize) {
+        for c in self.source()[..len].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset += len;
     }
 
-    fn source(&self) -> &str {
+    fn source(&self) -> &'a str {
         &self.source[self.offset..]
     }
+
+    /// The span of the next `len` bytes.
+    fn span(&self, len: usize) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset + len,
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 impl<'a> Iterator for TokenStream<'a> {
     type Item = TokenResult;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.eat_whitespace();
-        if self.source().is_empty() {
-            None
-        } else {
-            Some(
-                Tokenizer::tokenize_one_token(self.source()).map(|(token, offset)| {
-                    self.eat(offset);
-                    token
-                }),
-            )
+        if let Err(err) = self.eat_whitespace_and_comments() {
+            return Some(Err(err));
         }
+        let source = self.source();
+        let first = source.chars().next()?;
+        Some(match Tokenizer::tokenize_one_token(source) {
+            Ok((token, len)) => {
+                let span = self.span(len);
+                self.eat(len);
+                Ok(Token { span, ..token })
+            }
+            Err(kind) => {
+                // skip what the error is about, so that the stream goes on
+                let len = match &kind {
+                    TokenizerErrorKind::UnterminatedString => {
+                        source.find('\n').unwrap_or(source.len())
+                    }
+                    TokenizerErrorKind::IntegerOutOfRange(integer) => integer.len(),
+                    _ => first.len_utf8(),
+                };
+                let span = self.span(len);
+                self.eat(len);
+                Err(TokenizerError { kind, span })
+            }
+        })
     }
 }
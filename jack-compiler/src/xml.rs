@@ -0,0 +1,373 @@
+//! The XML files of project 10 of the course: the tokens of a class (`MainT.xml`)
+//! and its parse tree (`Main.xml`).
+
+use crate::ast::*;
+use crate::token::{Token, TokenKind};
+
+/// The reference files of the course end their lines like Windows.
+const NEWLINE: &str = "\r\n";
+
+pub struct XmlWriter {
+    xml: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        Self {
+            xml: String::new(),
+            depth: 0,
+        }
+    }
+
+    /// The tokens in a `<tokens>` element, one per line.
+    pub fn tokens(tokens: &[Token]) -> String {
+        let mut xml = format!("<tokens>{NEWLINE}");
+        for token in tokens {
+            xml += &leaf(token.kind, &token.value);
+            xml += NEWLINE;
+        }
+        xml + "</tokens>" + NEWLINE
+    }
+
+    /// The parse tree of the class, with every token of it, indented by two spaces a level.
+    pub fn parse_tree(ast: &Ast) -> String {
+        let mut writer = Self::new();
+        writer.write_class(ast);
+        writer.xml
+    }
+
+    fn line(&mut self, line: &str) {
+        self.xml += &"  ".repeat(self.depth);
+        self.xml += line;
+        self.xml += NEWLINE;
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.line(&format!("<{tag}>"));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{tag}>"));
+    }
+
+    fn keyword(&mut self, keyword: &str) {
+        self.line(&leaf(TokenKind::Keyword, keyword));
+    }
+
+    fn symbol(&mut self, symbol: &str) {
+        self.line(&leaf(TokenKind::Symbol, symbol));
+    }
+
+    fn identifier(&mut self, identifier: &IdentifierNode) {
+        self.line(&leaf(TokenKind::Identifier, &identifier.0));
+    }
+
+    fn write_type(&mut self, r#type: &TypeNode) {
+        match r#type {
+            TypeNode::Class(name) => self.identifier(name),
+            r#type => self.keyword(&r#type.to_string()),
+        }
+    }
+
+    /// The type and the names of variables, separated by commas.
+    fn write_names(&mut self, variables: &VariableDeclarationNode) {
+        self.write_type(&variables.r#type);
+        for (i, name) in variables.names.iter().enumerate() {
+            if i > 0 {
+                self.symbol(",");
+            }
+            self.identifier(name);
+        }
+        self.symbol(";");
+    }
+
+    fn write_class(&mut self, class: &ClassNode) {
+        self.open("class");
+        self.keyword("class");
+        self.identifier(&class.name);
+        self.symbol("{");
+        for variables in &class.variables {
+            self.open("classVarDec");
+            self.keyword(match variables.kind {
+                ClassVariableKind::Static => "static",
+                ClassVariableKind::Field => "field",
+            });
+            self.write_names(&variables.variables);
+            self.close("classVarDec");
+        }
+        for subroutine in &class.subroutines {
+            self.write_subroutine(subroutine);
+        }
+        self.symbol("}");
+        self.close("class");
+    }
+
+    fn write_subroutine(&mut self, subroutine: &SubroutineDeclarationNode) {
+        self.open("subroutineDec");
+        self.keyword(match subroutine.kind {
+            SubroutineKind::Constructor => "constructor",
+            SubroutineKind::Function => "function",
+            SubroutineKind::Method => "method",
+        });
+        match &subroutine.return_type {
+            Some(r#type) => self.write_type(r#type),
+            None => self.keyword("void"),
+        }
+        self.identifier(&subroutine.name);
+        self.symbol("(");
+        self.open("parameterList");
+        for (i, parameter) in subroutine.parameters.iter().enumerate() {
+            if i > 0 {
+                self.symbol(",");
+            }
+            self.write_type(&parameter.r#type);
+            self.identifier(&parameter.name);
+        }
+        self.close("parameterList");
+        self.symbol(")");
+        self.open("subroutineBody");
+        self.symbol("{");
+        for variables in &subroutine.body.variables {
+            self.open("varDec");
+            self.keyword("var");
+            self.write_names(variables);
+            self.close("varDec");
+        }
+        self.write_statements(&subroutine.body.statements);
+        self.symbol("}");
+        self.close("subroutineBody");
+        self.close("subroutineDec");
+    }
+
+    /// Statements in braces.
+    fn write_block(&mut self, statements: &[StatementNode]) {
+        self.symbol("{");
+        self.write_statements(statements);
+        self.symbol("}");
+    }
+
+    fn write_statements(&mut self, statements: &[StatementNode]) {
+        self.open("statements");
+        for statement in statements {
+            self.write_statement(statement);
+        }
+        self.close("statements");
+    }
+
+    fn write_statement(&mut self, statement: &StatementNode) {
+        match statement {
+            StatementNode::Let(node) => {
+                self.open("letStatement");
+                self.keyword("let");
+                self.identifier(&node.name);
+                if let Some(index) = &node.index {
+                    self.symbol("[");
+                    self.write_expression(index);
+                    self.symbol("]");
+                }
+                self.symbol("=");
+                self.write_expression(&node.value);
+                self.symbol(";");
+                self.close("letStatement");
+            }
+            StatementNode::IfElse(node) => {
+                self.open("ifStatement");
+                self.keyword("if");
+                self.write_parenthesized(&node.condition);
+                self.write_block(&node.statements);
+                if let Some(statements) = &node.else_node {
+                    self.keyword("else");
+                    self.write_block(statements);
+                }
+                self.close("ifStatement");
+            }
+            StatementNode::While(node) => {
+                self.open("whileStatement");
+                self.keyword("while");
+                self.write_parenthesized(&node.condition);
+                self.write_block(&node.statements);
+                self.close("whileStatement");
+            }
+            StatementNode::Do(node) => {
+                self.open("doStatement");
+                self.keyword("do");
+                self.write_call(&node.call);
+                self.symbol(";");
+                self.close("doStatement");
+            }
+            StatementNode::Return(node) => {
+                self.open("returnStatement");
+                self.keyword("return");
+                if let Some(value) = &node.value {
+                    self.write_expression(value);
+                }
+                self.symbol(";");
+                self.close("returnStatement");
+            }
+        }
+    }
+
+    /// An expression in parentheses.
+    fn write_parenthesized(&mut self, condition: &ExpressionNode) {
+        self.symbol("(");
+        self.write_expression(condition);
+        self.symbol(")");
+    }
+
+    fn write_expression(&mut self, expression: &ExpressionNode) {
+        self.open("expression");
+        self.write_term(&expression.term);
+        for part in &expression.parts {
+            self.symbol(match part.operator {
+                BinaryOperator::Plus => "+",
+                BinaryOperator::Minus => "-",
+                BinaryOperator::Multiply => "*",
+                BinaryOperator::Divide => "/",
+                BinaryOperator::And => "&",
+                BinaryOperator::Or => "|",
+                BinaryOperator::LessThan => "<",
+                BinaryOperator::GreaterThan => ">",
+                BinaryOperator::Equal => "=",
+            });
+            self.write_term(&part.term);
+        }
+        self.close("expression");
+    }
+
+    fn write_term(&mut self, term: &TermNode) {
+        self.open("term");
+        match term {
+            TermNode::IntegerConstant(value) => {
+                self.line(&leaf(TokenKind::IntegerConstant, &value.to_string()))
+            }
+            TermNode::StringConstant(value) => self.line(&leaf(TokenKind::StringConstant, value)),
+            TermNode::KeywordConstant(constant) => self.keyword(match constant {
+                KeywordConstant::True => "true",
+                KeywordConstant::False => "false",
+                KeywordConstant::Null => "null",
+                KeywordConstant::This => "this",
+            }),
+            TermNode::Variable(name) => self.identifier(name),
+            TermNode::ArrayElement(node) => {
+                self.identifier(&node.name);
+                self.symbol("[");
+                self.write_expression(&node.index);
+                self.symbol("]");
+            }
+            TermNode::SubroutineCall(call) => self.write_call(call),
+            TermNode::UnaryOperation(node) => {
+                self.symbol(match node.operator {
+                    UnaryOperator::ArthemiticNegation => "-",
+                    UnaryOperator::LogicalNegation => "~",
+                });
+                self.write_term(&node.subject);
+            }
+            TermNode::Parentheses(expression) => self.write_parenthesized(expression),
+        }
+        self.close("term");
+    }
+
+    fn write_call(&mut self, call: &SubroutineCallNode) {
+        if let Some(this) = &call.this {
+            self.identifier(this);
+            self.symbol(".");
+        }
+        self.identifier(&call.name);
+        self.symbol("(");
+        self.open("expressionList");
+        for (i, argument) in call.arguments.iter().enumerate() {
+            if i > 0 {
+                self.symbol(",");
+            }
+            self.write_expression(argument);
+        }
+        self.close("expressionList");
+        self.symbol(")");
+    }
+}
+
+/// An element of a token, like `<symbol> &lt; </symbol>`.
+fn leaf(kind: TokenKind, value: &str) -> String {
+    let tag = match kind {
+        TokenKind::Keyword => "keyword",
+        TokenKind::Symbol => "symbol",
+        TokenKind::Identifier => "identifier",
+        TokenKind::IntegerConstant => "integerConstant",
+        TokenKind::StringConstant => "stringConstant",
+    };
+    let value = value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!("<{tag}> {value} </{tag}>")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{
+        parser::Parser,
+        tokenizer::{Source, Tokenizer},
+    };
+    use pretty_assertions::assert_eq;
+
+    /// The Jack files of project 10, along with their reference token and parse tree files.
+    fn project_10() -> Vec<(Source, String, String)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/10");
+        let mut files = vec![];
+        for dir in fs::read_dir(root).unwrap() {
+            for file in fs::read_dir(dir.unwrap().path()).unwrap() {
+                let path = file.unwrap().path();
+                if path.extension().is_some_and(|ext| ext == "jack") {
+                    let name = path.file_stem().unwrap().to_string_lossy().to_string();
+                    let tokens = path.with_file_name(format!("{name}T.xml"));
+                    let source = Source {
+                        content: fs::read_to_string(&path).unwrap(),
+                        name,
+                    };
+                    files.push((
+                        source,
+                        fs::read_to_string(tokens).unwrap(),
+                        fs::read_to_string(path.with_extension("xml")).unwrap(),
+                    ));
+                }
+            }
+        }
+        assert_eq!(files.len(), 7);
+        files
+    }
+
+    #[test]
+    fn tokens_match_the_reference() {
+        for (source, expected, _) in project_10() {
+            let tokens: Vec<_> = Tokenizer::stream(&source).map(Result::unwrap).collect();
+            assert_eq!(XmlWriter::tokens(&tokens), expected, "{}", source.name);
+        }
+    }
+
+    #[test]
+    fn parse_trees_match_the_reference() {
+        for (source, _, expected) in project_10() {
+            let ast = Parser::new(Tokenizer::stream(&source), source.name.clone())
+                .parse()
+                .unwrap();
+            assert_eq!(XmlWriter::parse_tree(&ast), expected, "{}", source.name);
+        }
+    }
+
+    #[test]
+    fn escapes_symbols() {
+        assert_eq!(leaf(TokenKind::Symbol, "<"), "<symbol> &lt; </symbol>");
+        assert_eq!(leaf(TokenKind::Symbol, ">"), "<symbol> &gt; </symbol>");
+        assert_eq!(leaf(TokenKind::Symbol, "&"), "<symbol> &amp; </symbol>");
+        assert_eq!(
+            leaf(TokenKind::StringConstant, "a<b & c>d"),
+            "<stringConstant> a&lt;b &amp; c&gt;d </stringConstant>"
+        );
+    }
+}
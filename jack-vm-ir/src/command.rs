@@ -0,0 +1,295 @@
+use std::{fmt, str::FromStr};
+
+use crate::errors::ParseCommandError;
+use crate::name::{FunctionName, Label};
+use crate::segment::Segment;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Command {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+    /// Extension commands, which only the translator's `--extensions` mode accepts.
+    Mul,
+    Div,
+    Shl,
+    Shr,
+    Pop {
+        segment: Segment,
+        i: u16,
+    },
+    Push {
+        segment: Segment,
+        i: u16,
+    },
+    Label(Label),
+    GoTo(Label),
+    IfGoTo(Label),
+    Function {
+        name: FunctionName,
+        n_vars: u16,
+    },
+    Call {
+        name: FunctionName,
+        n_args: u16,
+    },
+    Return,
+}
+
+impl FromStr for Command {
+    type Err = ParseCommandError;
+
+    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
+        let comment_pos = s.find("//");
+        if let Some(comment_pos) = comment_pos {
+            s = &s[..comment_pos]
+        }
+        s = s.trim();
+        let mut components = s.split_whitespace();
+        let command_name = components.next().ok_or(ParseCommandError::NoCommand)?;
+        if let Some(no_arg_cmd) = match command_name {
+            "add" => Some(Self::Add),
+            "sub" => Some(Self::Sub),
+            "neg" => Some(Self::Neg),
+            "eq" => Some(Self::Eq),
+            "lt" => Some(Self::Lt),
+            "gt" => Some(Self::Gt),
+            "and" => Some(Self::And),
+            "or" => Some(Self::Or),
+            "not" => Some(Self::Not),
+            "mul" => Some(Self::Mul),
+            "div" => Some(Self::Div),
+            "shl" => Some(Self::Shl),
+            "shr" => Some(Self::Shr),
+            "return" => Some(Self::Return),
+            _ => None,
+        } {
+            return if components.next().is_some() {
+                Err(ParseCommandError::TooManyArguments)
+            } else {
+                Ok(no_arg_cmd)
+            };
+        }
+        match command_name {
+            "pop" => Self::parse_push_pop_args(components)
+                .map(|(segment, index)| Self::Pop { segment, i: index }),
+            "push" => Self::parse_push_pop_args(components)
+                .map(|(segment, index)| Self::Push { segment, i: index }),
+            "label" => Self::parse_label_related_args(components).map(Self::Label),
+            "goto" => Self::parse_label_related_args(components).map(Self::GoTo),
+            "if-goto" => Self::parse_label_related_args(components).map(Self::IfGoTo),
+            "function" => Self::parse_func_related_args(components)
+                .map(|(name, n_vars)| Self::Function { name, n_vars }),
+            "call" => Self::parse_func_related_args(components)
+                .map(|(name, n_args)| Self::Call { name, n_args }),
+            _ => Err(ParseCommandError::InvalidCommandName(
+                command_name.to_string(),
+            )),
+        }
+    }
+}
+
+impl Command {
+    /// Whether the command is one of the extensions to the VM language:
+    /// `mul`, `div`, `shl` and `shr`, which take `x` and `y` and push `x * y`, `x / y`,
+    /// `x << y` and `x >> y` (an arithmetic shift).
+    pub fn is_extension(&self) -> bool {
+        matches!(self, Self::Mul | Self::Div | Self::Shl | Self::Shr)
+    }
+
+    fn parse_push_pop_args<'a>(
+        mut it: impl Iterator<Item = &'a str>,
+    ) -> Result<(Segment, u16), ParseCommandError> {
+        let segment = it
+            .next()
+            .ok_or(ParseCommandError::NotEnoughArguments)
+            .and_then(Segment::from_str)?;
+        let index = it
+            .next()
+            .ok_or(ParseCommandError::NotEnoughArguments)
+            .and_then(|i| {
+                i.parse::<u16>()
+                    .map_err(|err| ParseCommandError::InvalidArgument(err.to_string()))
+            })?;
+        let too_many_args = it.next().is_some();
+        if too_many_args {
+            Err(ParseCommandError::TooManyArguments)
+        } else {
+            Ok((segment, index))
+        }
+    }
+
+    fn parse_label_related_args<'a>(
+        mut it: impl Iterator<Item = &'a str>,
+    ) -> Result<Label, ParseCommandError> {
+        let label = it.next().ok_or(ParseCommandError::NotEnoughArguments)?;
+        let too_many_args = it.next().is_some();
+        if too_many_args {
+            Err(ParseCommandError::TooManyArguments)
+        } else {
+            label.parse()
+        }
+    }
+
+    fn parse_func_related_args<'a>(
+        mut it: impl Iterator<Item = &'a str>,
+    ) -> Result<(FunctionName, u16), ParseCommandError> {
+        let name = it.next().ok_or(ParseCommandError::NotEnoughArguments)?;
+        let count = it
+            .next()
+            .ok_or(ParseCommandError::NotEnoughArguments)
+            .and_then(|i| {
+                i.parse::<u16>()
+                    .map_err(|err| ParseCommandError::InvalidArgument(err.to_string()))
+            })?;
+        let too_many_args = it.next().is_some();
+        if too_many_args {
+            Err(ParseCommandError::TooManyArguments)
+        } else {
+            Ok((name.parse()?, count))
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => write!(f, "add"),
+            Self::Sub => write!(f, "sub"),
+            Self::Neg => write!(f, "neg"),
+            Self::Eq => write!(f, "eq"),
+            Self::Gt => write!(f, "gt"),
+            Self::Lt => write!(f, "lt"),
+            Self::And => write!(f, "and"),
+            Self::Or => write!(f, "or"),
+            Self::Not => write!(f, "not"),
+            Self::Mul => write!(f, "mul"),
+            Self::Div => write!(f, "div"),
+            Self::Shl => write!(f, "shl"),
+            Self::Shr => write!(f, "shr"),
+            Self::Pop { segment, i } => write!(f, "pop {segment} {i}"),
+            Self::Push { segment, i } => write!(f, "push {segment} {i}"),
+            Self::Label(label) => write!(f, "label {label}"),
+            Self::GoTo(label) => write!(f, "goto {label}"),
+            Self::IfGoTo(label) => write!(f, "if-goto {label}"),
+            Self::Function { name, n_vars } => write!(f, "function {name} {n_vars}"),
+            Self::Call { name, n_args } => write!(f, "call {name} {n_args}"),
+            Self::Return => write!(f, "return"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let code = "function Main.main 2
+push constant 7
+pop local 1
+label LOOP$1
+push argument 0
+if-goto LOOP$1
+goto END_1
+call Math.multiply 2
+add
+sub
+neg
+eq
+gt
+lt
+and
+or
+not
+return
+";
+        let commands = crate::parse(code).unwrap();
+        assert_eq!(commands.len(), 18);
+        assert_eq!(
+            commands[0],
+            Command::Function {
+                name: "Main.main".into(),
+                n_vars: 2
+            }
+        );
+        assert_eq!(crate::to_vm_code(&commands), code);
+    }
+
+    #[test]
+    fn comments_and_whitespace() {
+        assert_eq!(
+            "  push   that 3 // comment".parse(),
+            Ok(Command::Push {
+                segment: Segment::That,
+                i: 3
+            })
+        );
+        assert_eq!(
+            "// only a comment".parse::<Command>(),
+            Err(ParseCommandError::NoCommand)
+        );
+        assert_eq!(crate::parse("\n\n// x\nreturn\n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            "push local".parse::<Command>(),
+            Err(ParseCommandError::NotEnoughArguments)
+        );
+        assert_eq!(
+            "add 1".parse::<Command>(),
+            Err(ParseCommandError::TooManyArguments)
+        );
+        assert_eq!(
+            "push heap 1".parse::<Command>(),
+            Err(ParseCommandError::ParseSegmentError("heap".to_string()))
+        );
+        assert_eq!(
+            "goto 1ABC".parse::<Command>(),
+            Err(ParseCommandError::InvalidName("1ABC".to_string()))
+        );
+        assert_eq!(
+            "call Main-main 0".parse::<Command>(),
+            Err(ParseCommandError::InvalidName("Main-main".to_string()))
+        );
+        assert!(matches!(
+            "pop temp -1".parse::<Command>(),
+            Err(ParseCommandError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            crate::parse("add\nfly\n"),
+            Err((2, ParseCommandError::InvalidCommandName("fly".to_string())))
+        );
+    }
+
+    #[test]
+    fn extensions() {
+        let code = "mul\ndiv\nshl\nshr\n";
+        let commands = crate::parse(code).unwrap();
+        assert_eq!(
+            commands,
+            [Command::Mul, Command::Div, Command::Shl, Command::Shr]
+        );
+        assert!(commands.iter().all(Command::is_extension));
+        assert!(!Command::Add.is_extension());
+        assert_eq!(crate::to_vm_code(&commands), code);
+        assert_eq!(
+            "shl 2".parse::<Command>(),
+            Err(ParseCommandError::TooManyArguments)
+        );
+    }
+
+    #[test]
+    fn class_of_function() {
+        assert_eq!(FunctionName::from("Main.main").class(), "Main");
+        assert_eq!(FunctionName::from("init").class(), "init");
+    }
+}
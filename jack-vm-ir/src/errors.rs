@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseCommandError {
+    InvalidCommandName(String),
+    NotEnoughArguments,
+    TooManyArguments,
+    InvalidArgument(String),
+    InvalidName(String),
+    NoCommand,
+    ParseSegmentError(String),
+}
+
+impl Error for ParseCommandError {}
+impl fmt::Display for ParseCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidArgument(arg) => {
+                write!(f, "Invalid argument \"{arg}\"")
+            }
+            Self::InvalidName(name) => write!(f, "Invalid name \"{name}\""),
+            Self::NotEnoughArguments => write!(f, "Not enough arguments"),
+            Self::TooManyArguments => write!(f, "Too many arguments"),
+            Self::NoCommand => write!(f, "No command"),
+            Self::ParseSegmentError(segment) => {
+                write!(f, "Failed to parse segment \"{segment}\"")
+            }
+            Self::InvalidCommandName(command) => {
+                write!(f, "Invalid command \"{command}\"")
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Typed representation of the VM language, shared by the Jack compiler and the VM translator.
+
+mod command;
+mod errors;
+mod name;
+mod segment;
+
+pub use command::Command;
+pub use errors::ParseCommandError;
+pub use name::{FunctionName, Label};
+pub use segment::Segment;
+
+/// Parses VM code, one command per line. Blank lines and comments are skipped.
+/// On error, returns the line number along with the error.
+pub fn parse(code: &str) -> Result<Vec<Command>, (u32, ParseCommandError)> {
+    code.lines()
+        .enumerate()
+        .filter_map(|(id, line)| match line.parse() {
+            Err(ParseCommandError::NoCommand) => None,
+            result => Some(result.map_err(|err| (id as u32 + 1, err))),
+        })
+        .collect()
+}
+
+/// Formats commands as VM code, one command per line.
+pub fn to_vm_code(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|command| format!("{command}\n"))
+        .collect()
+}
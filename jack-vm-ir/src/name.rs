@@ -0,0 +1,99 @@
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use crate::errors::ParseCommandError;
+
+/// Whether `s` is a valid symbol of the VM language:
+/// letters, digits, `_`, `.`, `$` and `:`, not starting with a digit.
+fn is_symbol(s: &str) -> bool {
+    !s.starts_with(|c: char| c.is_ascii_digit())
+        && !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c))
+}
+
+macro_rules! symbol {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(s: String) -> Self {
+                Self(s)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(s: &str) -> Self {
+                Self(s.to_string())
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseCommandError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                if is_symbol(s) {
+                    Ok(Self(s.to_string()))
+                } else {
+                    Err(ParseCommandError::InvalidName(s.to_string()))
+                }
+            }
+        }
+    };
+}
+
+symbol!(
+    /// Name of a VM function, like `Main.main`.
+    FunctionName
+);
+
+symbol!(
+    /// Name of a label, local to the function it appears in.
+    Label
+);
+
+impl FunctionName {
+    /// The class part of the name, `Main` for `Main.main`.
+    pub fn class(&self) -> &str {
+        self.0.split_once('.').map_or(&self.0, |(class, _)| class)
+    }
+}
@@ -1,7 +1,8 @@
 use phf::phf_map;
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use crate::errors::ParseCommandError;
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Segment {
     Static,
@@ -14,6 +15,21 @@ pub enum Segment {
     Pointer,
 }
 
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Segment::Static => "static",
+            Segment::Local => "local",
+            Segment::Argument => "argument",
+            Segment::This => "this",
+            Segment::That => "that",
+            Segment::Temp => "temp",
+            Segment::Constant => "constant",
+            Segment::Pointer => "pointer",
+        })
+    }
+}
+
 static FROMSTR_MAP: phf::Map<&'static str, Segment> = phf_map! {
     "static" => Segment::Static,
     "local" => Segment::Local,
@@ -31,7 +47,7 @@ impl FromStr for Segment {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         FROMSTR_MAP
             .get(s)
-            .map(|x| *x)
+            .copied()
             .ok_or(ParseCommandError::ParseSegmentError(s.to_owned()))
     }
 }
@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, fmt};
+
+use jack_vm_ir::Command;
+use serde::Serialize;
+
+use crate::call_graph::CallGraph;
+use crate::command::ToAsm;
+use crate::errors::TranslationError;
+use crate::parser::ParsedSource;
+use crate::stack_depth;
+use crate::translation_state::TranslationState;
+use crate::translator::{Options, Translator};
+
+/// Size and connections of a function.
+#[derive(Debug, Serialize)]
+pub struct FunctionMetrics {
+    pub name: String,
+    pub file: String,
+    pub commands: usize,
+    /// Number of instructions of the translated function.
+    pub asm_words: usize,
+    pub locals: u16,
+    pub max_stack_depth: usize,
+    pub callers: Vec<String>,
+    pub callees: Vec<String>,
+}
+
+/// Metrics of every function of a program, and the recursion in its call graph.
+#[derive(Debug, Serialize)]
+pub struct Analysis {
+    pub functions: Vec<FunctionMetrics>,
+    /// Groups of functions that call each other, directly or not.
+    pub cycles: Vec<Vec<String>>,
+}
+
+impl Analysis {
+    pub fn new(sources: &[ParsedSource], options: Options) -> Result<Self, TranslationError> {
+        let graph = CallGraph::new(sources);
+        let mut callers = BTreeMap::<_, Vec<String>>::new();
+        for (caller, callees) in &graph.callees {
+            for callee in callees {
+                callers.entry(callee).or_default().push(caller.to_string());
+            }
+        }
+        let mut functions = vec![];
+        for source in sources {
+            let max_depths: BTreeMap<_, _> = stack_depth::analyze(source)
+                .into_iter()
+                .map(|stack| (stack.name, stack.max_depth))
+                .collect();
+            let mut state = TranslationState::new(&source.name, options);
+            for command in &source.commands {
+                let asm_words = Translator::instruction_count(&command.to_asm(&mut state)?);
+                if let Command::Function { name, n_vars } = command {
+                    functions.push(FunctionMetrics {
+                        name: name.to_string(),
+                        file: source.name.clone(),
+                        commands: 0,
+                        asm_words: 0,
+                        locals: *n_vars,
+                        max_stack_depth: max_depths[name],
+                        callers: callers.get(name).cloned().unwrap_or_default(),
+                        callees: graph.callees[name].iter().map(|n| n.to_string()).collect(),
+                    });
+                }
+                // commands before the first function don't belong to any
+                if let Some(function) = functions.last_mut().filter(|f| f.file == source.name) {
+                    function.commands += 1;
+                    function.asm_words += asm_words;
+                }
+            }
+        }
+        let cycles = graph
+            .cycles()
+            .iter()
+            .map(|cycle| cycle.iter().map(|name| name.to_string()).collect())
+            .collect();
+        Ok(Self { functions, cycles })
+    }
+
+    /// The call graph in the Graphviz DOT language, with the functions in cycles in red
+    /// and the functions that aren't defined, like the native OS, dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph calls {\n    node [shape=box];\n".to_string();
+        let recursive = |name: &String| self.cycles.iter().any(|cycle| cycle.contains(name));
+        let defined = |name: &String| self.functions.iter().any(|f| &f.name == name);
+        for function in &self.functions {
+            let color = if recursive(&function.name) {
+                ", color=red"
+            } else {
+                ""
+            };
+            dot += &format!(
+                "    \"{}\" [label=\"{}\\n{} words\"{color}];\n",
+                function.name, function.name, function.asm_words
+            );
+        }
+        let mut undefined: Vec<_> = self
+            .functions
+            .iter()
+            .flat_map(|function| &function.callees)
+            .filter(|name| !defined(name))
+            .collect();
+        undefined.sort();
+        undefined.dedup();
+        for name in undefined {
+            dot += &format!("    \"{name}\" [style=dashed];\n");
+        }
+        for function in &self.functions {
+            for callee in &function.callees {
+                dot += &format!("    \"{}\" -> \"{callee}\";\n", function.name);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for Analysis {
+    /// A table of the functions, the biggest first, followed by the cycles.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.asm_words.cmp(&a.asm_words).then(a.name.cmp(&b.name)));
+        let width = functions
+            .iter()
+            .map(|function| function.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+        writeln!(
+            f,
+            "{:width$}  commands  words  locals  stack  callers  callees",
+            "function"
+        )?;
+        for function in &functions {
+            writeln!(
+                f,
+                "{:width$}  {:>8}  {:>5}  {:>6}  {:>5}  {:>7}  {:>7}",
+                function.name,
+                function.commands,
+                function.asm_words,
+                function.locals,
+                function.max_stack_depth,
+                function.callers.len(),
+                function.callees.len(),
+            )?;
+        }
+        writeln!(
+            f,
+            "{} functions, {} commands, {} words",
+            functions.len(),
+            functions
+                .iter()
+                .map(|function| function.commands)
+                .sum::<usize>(),
+            functions
+                .iter()
+                .map(|function| function.asm_words)
+                .sum::<usize>()
+        )?;
+        if self.cycles.is_empty() {
+            write!(f, "No recursion")
+        } else {
+            write!(f, "Recursion:")?;
+            for cycle in &self.cycles {
+                write!(f, "\n    {}", cycle.join(" <-> "))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, source::Source};
+
+    #[test]
+    fn metrics() {
+        let source = Parser::parse(Source {
+            name: "Main".to_string(),
+            content: r"
+function Main.main 1
+push constant 5
+call Main.fact 1
+pop local 0
+push constant 0
+return
+function Main.fact 0
+push argument 0
+push argument 0
+push constant 1
+sub
+call Main.fact 1
+call Math.multiply 2
+return
+"
+            .to_string(),
+        })
+        .unwrap();
+        let analysis = Analysis::new(std::slice::from_ref(&source), Options::default()).unwrap();
+        let metrics: Vec<_> = analysis
+            .functions
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.commands,
+                    f.locals,
+                    f.max_stack_depth,
+                    f.callers.clone(),
+                    f.callees.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            metrics,
+            [
+                ("Main.main", 6, 1, 1, vec![], vec!["Main.fact".to_string()]),
+                (
+                    "Main.fact",
+                    8,
+                    0,
+                    3,
+                    vec!["Main.fact".to_string(), "Main.main".to_string()],
+                    vec!["Main.fact".to_string(), "Math.multiply".to_string()]
+                ),
+            ]
+        );
+        let total: usize = analysis.functions.iter().map(|f| f.asm_words).sum();
+        let asm = Translator::translate(&source, Options::default()).unwrap();
+        assert_eq!(total, Translator::instruction_count(&asm));
+        assert_eq!(analysis.cycles, [vec!["Main.fact".to_string()]]);
+
+        let dot = analysis.to_dot();
+        assert!(dot.contains("\"Main.fact\" [label=\"Main.fact\\n"));
+        assert!(dot.contains(", color=red];"));
+        assert!(dot.contains("\"Math.multiply\" [style=dashed];"));
+        assert!(dot.contains("\"Main.main\" -> \"Main.fact\";"));
+
+        let json: serde_json::Value = serde_json::from_str(&analysis.to_json()).unwrap();
+        assert_eq!(json["functions"][1]["callers"][1], "Main.main");
+        assert_eq!(json["cycles"][0][0], "Main.fact");
+
+        let table = analysis.to_string();
+        assert!(table.contains(&format!("2 functions, 14 commands, {total} words")));
+        assert!(table.ends_with("Recursion:\n    Main.fact"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Code generation keeping the top of the stack in D.
+//!
+//! While the top is cached, `SP` points to where it belongs and the rest of the stack is in memory.
+//! Straight-line code passes values in D instead of through the stack,
+//! and the value is flushed to memory before labels, jumps, calls and returns,
+//! where every path must agree on where the stack is.
+
+use jack_vm_ir::{Command, Segment};
+
+use crate::translation_state::TranslationState;
+
+/// Indexes up to which a segment address is incremented instead of added to.
+const MAX_INCREMENTS: u16 = 6;
+
+/// Translates the commands that can use or leave the top of the stack in D,
+/// or returns `None` for the ones that need the whole stack in memory.
+pub fn to_asm(command: &Command, state: &mut TranslationState) -> Option<String> {
+    let cached = state.is_top_cached();
+    // the top of the stack in D, wherever it was
+    let top = if cached { "" } else { "@SP\nAM=M-1\nD=M\n" };
+    let asm = match command {
+        Command::Push { segment, i } => {
+            let load = load(state, *segment, *i)?;
+            let flush = if cached {
+                flush_code() + "\n"
+            } else {
+                "".into()
+            };
+            format!("// push {segment} {i}\n{flush}{load}")
+        }
+        Command::Pop { segment, i } => {
+            let store = store(state, *segment, *i)?;
+            state.set_top_cached(false);
+            return Some(format!("// pop {segment} {i}\n{top}{store}"));
+        }
+        Command::Add | Command::Sub | Command::And | Command::Or if cached => {
+            let operation = match command {
+                Command::Add => "D=D+M",
+                Command::Sub => "D=M-D",
+                Command::And => "D=D&M",
+                _ => "D=D|M",
+            };
+            format!("// {command}\n@SP\nAM=M-1\n{operation}")
+        }
+        Command::Neg | Command::Not if cached => {
+            let operation = if matches!(command, Command::Neg) {
+                "D=-D"
+            } else {
+                "D=!D"
+            };
+            format!("// {command}\n{operation}")
+        }
+        Command::Eq | Command::Gt | Command::Lt
+            if matches!(command, Command::Eq) || !state.options().safe_comparisons =>
+        {
+            let jump = match command {
+                Command::Eq => "JEQ",
+                Command::Gt => "JGT",
+                _ => "JLT",
+            };
+            let cnt = state.advance_comparison_counter();
+            format!(
+                r"// {command}
+{top}@SP
+AM=M-1 // D = *(--sp) - D
+D=M-D
+@CMPR.{name}.TRUE.{cnt}
+D;{jump}
+D=0
+@CMPR.{name}.END.{cnt}
+0;JMP
+(CMPR.{name}.TRUE.{cnt})
+D=-1
+(CMPR.{name}.END.{cnt})",
+                name = state.name()
+            )
+        }
+        Command::IfGoTo(label) if cached => {
+            state.set_top_cached(false);
+            let label = match state.func() {
+                Some(func) => format!("{func}${label}"),
+                None => label.to_string(),
+            };
+            return Some(format!("// if-goto {label}\n@{label}\nD;JNE"));
+        }
+        _ => return None,
+    };
+    state.set_top_cached(true);
+    Some(asm)
+}
+
+/// Writes the top of the stack from D to memory, if it is there.
+pub fn flush(state: &mut TranslationState) -> String {
+    if state.is_top_cached() {
+        state.set_top_cached(false);
+        format!("// flush the top of the stack\n{}", flush_code())
+    } else {
+        String::new()
+    }
+}
+
+fn flush_code() -> String {
+    "@SP\nAM=M+1\nA=A-1\nM=D".to_string()
+}
+
+/// Code loading a value of a segment into D.
+fn load(state: &TranslationState, segment: Segment, i: u16) -> Option<String> {
+    Some(match segment {
+        Segment::Constant if i <= 1 => format!("D={i}"),
+        Segment::Constant => format!("@{i}\nD=A"),
+        Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+            format!("{}\nD=M", address(segment, i)?)
+        }
+        _ => format!("@{}\nD=M", fixed_address(state, segment, i)?),
+    })
+}
+
+/// Code storing D into a segment.
+fn store(state: &TranslationState, segment: Segment, i: u16) -> Option<String> {
+    Some(match segment {
+        Segment::Constant => return None,
+        Segment::Local | Segment::Argument | Segment::This | Segment::That
+            if i > MAX_INCREMENTS =>
+        {
+            let symbol = base(segment)?;
+            format!(
+                r"@R13
+M=D
+@{symbol}
+D=M
+@{i}
+D=D+A
+@R14
+M=D
+@R13
+D=M
+@R14
+A=M
+M=D"
+            )
+        }
+        Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+            format!("{}\nM=D", address(segment, i)?)
+        }
+        _ => format!("@{}\nM=D", fixed_address(state, segment, i)?),
+    })
+}
+
+fn base(segment: Segment) -> Option<&'static str> {
+    match segment {
+        Segment::Local => Some("LCL"),
+        Segment::Argument => Some("ARG"),
+        Segment::This => Some("THIS"),
+        Segment::That => Some("THAT"),
+        _ => None,
+    }
+}
+
+/// Code pointing A at a variable of a segment with a base address, leaving D alone.
+fn address(segment: Segment, i: u16) -> Option<String> {
+    let symbol = base(segment)?;
+    Some(match i {
+        0 => format!("@{symbol}\nA=M"),
+        1 => format!("@{symbol}\nA=M+1"),
+        i if i <= MAX_INCREMENTS => {
+            format!("@{symbol}\nA=M+1{}", "\nA=A+1".repeat(i as usize - 1))
+        }
+        // only loads get here, which may overwrite D
+        i => format!("@{symbol}\nD=M\n@{i}\nA=D+A"),
+    })
+}
+
+/// Symbol of a variable of the static, temp or pointer segment,
+/// or `None` for indexes out of the segment, which the regular translation reports.
+fn fixed_address(state: &TranslationState, segment: Segment, i: u16) -> Option<String> {
+    match segment {
+        Segment::Static => Some(format!("{}.{i}", state.name())),
+        Segment::Temp if i < 8 => Some((i + 5).to_string()),
+        Segment::Pointer if i < 2 => Some(if i == 0 { "THIS" } else { "THAT" }.to_string()),
+        _ => None,
+    }
+}
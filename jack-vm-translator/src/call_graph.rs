@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use jack_vm_ir::{Command, FunctionName};
+
+use crate::errors::TranslationError;
+use crate::parser::ParsedSource;
+use crate::translator::{Options, Translator};
+
+/// Which functions each function of the program calls.
+pub struct CallGraph {
+    pub callees: BTreeMap<FunctionName, BTreeSet<FunctionName>>,
+}
+
+impl CallGraph {
+    pub fn new(sources: &[ParsedSource]) -> Self {
+        let mut callees = BTreeMap::<_, BTreeSet<_>>::new();
+        for source in sources {
+            let mut current = None;
+            for command in &source.commands {
+                match command {
+                    Command::Function { name, .. } => {
+                        callees.entry(name.clone()).or_default();
+                        current = Some(name);
+                    }
+                    Command::Call { name, .. } => {
+                        if let Some(current) = current {
+                            callees.get_mut(current).unwrap().insert(name.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Self { callees }
+    }
+
+    /// Functions reachable from `roots` through calls, including the roots themselves.
+    pub fn reachable<'a>(
+        &'a self,
+        roots: impl IntoIterator<Item = &'a FunctionName>,
+    ) -> BTreeSet<&'a FunctionName> {
+        let mut reachable = BTreeSet::new();
+        let mut pending: Vec<_> = roots.into_iter().collect();
+        while let Some(name) = pending.pop() {
+            if reachable.insert(name) {
+                pending.extend(self.callees.get(name).into_iter().flatten());
+            }
+        }
+        reachable
+    }
+
+    /// Groups of functions that call each other, directly or not, and functions that call themselves.
+    pub fn cycles(&self) -> Vec<Vec<&FunctionName>> {
+        // Tarjan's strongly connected components
+        struct Search<'a> {
+            graph: &'a CallGraph,
+            index: BTreeMap<&'a FunctionName, usize>,
+            low: BTreeMap<&'a FunctionName, usize>,
+            stack: Vec<&'a FunctionName>,
+            cycles: Vec<Vec<&'a FunctionName>>,
+        }
+
+        impl<'a> Search<'a> {
+            fn visit(&mut self, name: &'a FunctionName) {
+                let index = self.index.len();
+                self.index.insert(name, index);
+                self.low.insert(name, index);
+                self.stack.push(name);
+                for callee in self.graph.callees.get(name).into_iter().flatten() {
+                    if !self.graph.callees.contains_key(callee) {
+                        continue;
+                    }
+                    if !self.index.contains_key(callee) {
+                        self.visit(callee);
+                        self.low.insert(name, self.low[name].min(self.low[callee]));
+                    } else if self.stack.contains(&callee) {
+                        self.low
+                            .insert(name, self.low[name].min(self.index[callee]));
+                    }
+                }
+                if self.low[name] == index {
+                    let start = self.stack.iter().rposition(|&n| n == name).unwrap();
+                    let mut component = self.stack.split_off(start);
+                    if component.len() > 1 || self.graph.callees[name].contains(name) {
+                        component.sort();
+                        self.cycles.push(component);
+                    }
+                }
+            }
+        }
+
+        let mut search = Search {
+            graph: self,
+            index: BTreeMap::new(),
+            low: BTreeMap::new(),
+            stack: vec![],
+            cycles: vec![],
+        };
+        for name in self.callees.keys() {
+            if !search.index.contains_key(name) {
+                search.visit(name);
+            }
+        }
+        search.cycles.sort();
+        search.cycles
+    }
+}
+
+/// What dead function elimination removed.
+#[derive(Debug, Default)]
+pub struct Elimination {
+    pub functions: Vec<FunctionName>,
+    pub rom_words: usize,
+}
+
+/// Removes the functions that can't be reached from `entry` or the functions in `keep`.
+/// Nothing is removed if the program doesn't define `entry`.
+/// The removed ROM words are counted as translated with `options`.
+pub fn eliminate_dead_functions(
+    sources: &mut [ParsedSource],
+    entry: &FunctionName,
+    keep: &[FunctionName],
+    options: Options,
+) -> Result<Elimination, TranslationError> {
+    let graph = CallGraph::new(sources);
+    let mut elimination = Elimination::default();
+    let Some((entry, _)) = graph.callees.get_key_value(entry) else {
+        return Ok(elimination);
+    };
+    let reachable = graph.reachable(keep.iter().chain([entry]));
+    for source in sources {
+        let mut kept = vec![];
+        let mut removed = vec![];
+        // commands before the first function are always kept
+        let mut live = true;
+        let lines = std::mem::take(&mut source.lines);
+        for (i, command) in source.commands.drain(..).enumerate() {
+            if let Command::Function { name, .. } = &command {
+                live = reachable.contains(name);
+                if !live {
+                    elimination.functions.push(name.clone());
+                }
+            }
+            if live {
+                source.lines.extend(lines.get(i));
+                kept.push(command);
+            } else {
+                removed.push(command);
+            }
+        }
+        source.commands = kept;
+        elimination.rom_words += Translator::instruction_count(&Translator::translate(
+            &ParsedSource {
+                commands: removed,
+                lines: vec![],
+                name: source.name.clone(),
+            },
+            options,
+        )?);
+    }
+    Ok(elimination)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::interpreter::{Interpreter, SCREEN};
+    use crate::test_util::parse;
+    use crate::{parser::Parser, source::Source};
+
+    #[test]
+    fn unreachable_functions() {
+        let mut sources = parse(&[
+            (
+                "Sys",
+                r"
+function Sys.init 0
+call Main.main 0
+pop temp 0
+label END
+goto END
+",
+            ),
+            (
+                "Main",
+                r"
+function Main.main 0
+push constant 1
+call Main.recursive 1
+return
+function Main.recursive 0
+push argument 0
+call Main.recursive 1
+return
+function Main.unused 0
+call Main.unusedToo 0
+return
+function Main.unusedToo 0
+push constant 0
+return
+function Main.callback 0
+push constant 0
+return
+",
+            ),
+        ]);
+        let removed = sources[1].commands[8..14].to_vec();
+        let elimination = eliminate_dead_functions(
+            &mut sources,
+            &"Sys.init".into(),
+            &["Main.callback".into()],
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(elimination.functions, ["Main.unused", "Main.unusedToo"]);
+        let asm = Translator::translate(
+            &ParsedSource {
+                commands: removed,
+                lines: vec![],
+                name: "Main".to_string(),
+            },
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(elimination.rom_words, Translator::instruction_count(&asm));
+        assert_eq!(sources[1].commands.len(), 11);
+        assert_eq!(
+            sources[1].commands[8].to_string(),
+            "function Main.callback 0"
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let sources = parse(&[(
+            "Main",
+            r"
+function Main.a 0
+call Main.b 0
+call Main.d 0
+return
+function Main.b 0
+call Main.c 0
+return
+function Main.c 0
+call Main.a 0
+call Math.abs 1
+return
+function Main.d 0
+call Main.d 0
+return
+function Main.e 0
+call Main.a 0
+return
+",
+        )]);
+        let graph = CallGraph::new(&sources);
+        let cycles: Vec<Vec<_>> = graph
+            .cycles()
+            .iter()
+            .map(|cycle| cycle.iter().map(|name| name.as_str()).collect())
+            .collect();
+        assert_eq!(cycles, [vec!["Main.a", "Main.b", "Main.c"], vec!["Main.d"]]);
+    }
+
+    #[test]
+    fn without_entry_nothing_is_removed() {
+        let mut sources = parse(&[("Main", "function Main.main 0\npush constant 0\nreturn")]);
+        let elimination =
+            eliminate_dead_functions(&mut sources, &"Sys.init".into(), &[], Options::default())
+                .unwrap();
+        assert!(elimination.functions.is_empty());
+        assert_eq!(sources[0].commands.len(), 3);
+    }
+
+    #[test]
+    fn functions_are_reached_from_the_entry() {
+        let mut sources = parse(&[
+            ("Sys", "function Sys.init 0\nlabel END\ngoto END"),
+            (
+                "Main",
+                "function Main.start 0\ncall Main.used 0\nreturn\nfunction Main.used 0\npush constant 0\nreturn",
+            ),
+        ]);
+        let elimination =
+            eliminate_dead_functions(&mut sources, &"Main.start".into(), &[], Options::default())
+                .unwrap();
+        assert_eq!(elimination.functions, ["Sys.init"]);
+        assert!(sources[0].commands.is_empty());
+        assert_eq!(sources[1].commands.len(), 6);
+    }
+
+    #[test]
+    fn official_os() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut sources: Vec<_> = ["projects/11/Seven", "tools/OS"]
+            .iter()
+            .flat_map(|dir| Source::read(&root.join(dir)).unwrap())
+            .map(|source| Parser::parse(source).unwrap())
+            .collect();
+        sources.sort_by(|a, b| a.name.cmp(&b.name));
+        let screen = |sources: &[ParsedSource]| -> Vec<i16> {
+            let mut vm = Interpreter::new(sources).unwrap();
+            vm.run(Some(2_000_000)).unwrap();
+            (SCREEN..SCREEN + 8192).map(|addr| vm.peek(addr)).collect()
+        };
+        let expected = screen(&sources);
+        let elimination =
+            eliminate_dead_functions(&mut sources, &"Sys.init".into(), &[], Options::default())
+                .unwrap();
+        assert!(elimination.functions.contains(&"Screen.drawCircle".into()));
+        assert!(elimination.rom_words > 1000);
+        assert_eq!(screen(&sources), expected);
+    }
+}
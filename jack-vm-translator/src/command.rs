@@ -1,138 +1,18 @@
 use lazy_static::lazy_static;
 
-use std::{collections::HashMap, str::FromStr};
+use std::collections::HashMap;
 
-use crate::segment::Segment;
-use crate::{
-    errors::{ParseCommandError, TranslationError},
-    translation_state::TranslationState,
-};
+use jack_vm_ir::{Command, Segment};
 
-#[derive(Debug)]
-pub enum Command {
-    Add,
-    Sub,
-    Neg,
-    Eq,
-    Gt,
-    Lt,
-    And,
-    Or,
-    Not,
-    Pop { segment: Segment, i: u16 },
-    Push { segment: Segment, i: u16 },
-    Label(String),
-    GoTo(String),
-    IfGoTo(String),
-    Function { name: String, n_vars: u16 },
-    Call { name: String, n_vars: u16 },
-    Return,
-}
-
-impl FromStr for Command {
-    type Err = ParseCommandError;
+use crate::{cached, errors::TranslationError, extensions, translation_state::TranslationState};
 
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        let comment_pos = s.find("//");
-        if let Some(comment_pos) = comment_pos {
-            s = &s[..comment_pos]
-        }
-        s = s.trim();
-        let mut components = s.split_whitespace();
-        let command_name = components.next().ok_or(ParseCommandError::NoCommand)?;
-        if let Some(no_arg_cmd) = match command_name {
-            "add" => Some(Self::Add),
-            "sub" => Some(Self::Sub),
-            "neg" => Some(Self::Neg),
-            "eq" => Some(Self::Eq),
-            "lt" => Some(Self::Lt),
-            "gt" => Some(Self::Gt),
-            "and" => Some(Self::And),
-            "or" => Some(Self::Or),
-            "not" => Some(Self::Not),
-            "return" => Some(Self::Return),
-            _ => None,
-        } {
-            return if components.next().is_some() {
-                Err(ParseCommandError::TooManyArguments)
-            } else {
-                Ok(no_arg_cmd)
-            };
-        }
-        match command_name {
-            "pop" => Self::parse_push_pop_args(components)
-                .map(|(segment, index)| Self::Pop { segment, i: index }),
-            "push" => Self::parse_push_pop_args(components)
-                .map(|(segment, index)| Self::Push { segment, i: index }),
-            "label" => Self::parse_label_related_args(components).map(Self::Label),
-            "goto" => Self::parse_label_related_args(components).map(Self::GoTo),
-            "if-goto" => Self::parse_label_related_args(components).map(Self::IfGoTo),
-            "function" => Self::parse_func_related_args(components)
-                .map(|(name, n_vars)| Self::Function { name, n_vars }),
-            "call" => Self::parse_func_related_args(components)
-                .map(|(name, n_vars)| Self::Call { name, n_vars }),
-            _ => Err(ParseCommandError::InvalidCommandName(
-                command_name.to_string(),
-            )),
-        }
-    }
+/// Translation of VM commands to Hack assembly.
+pub trait ToAsm {
+    fn to_asm(&self, state: &mut TranslationState) -> Result<String, TranslationError>;
 }
 
-impl Command {
-    fn parse_push_pop_args<'a>(
-        mut it: impl Iterator<Item = &'a str>,
-    ) -> Result<(Segment, u16), ParseCommandError> {
-        let segment = it
-            .next()
-            .ok_or(ParseCommandError::NotEnoughArguments)
-            .and_then(Segment::from_str)?;
-        let index = it
-            .next()
-            .ok_or(ParseCommandError::NotEnoughArguments)
-            .and_then(|i| {
-                i.parse::<u16>()
-                    .map_err(|err| ParseCommandError::InvalidArgument(err.to_string()))
-            })?;
-        let too_many_args = it.next().is_some();
-        return if too_many_args {
-            Err(ParseCommandError::TooManyArguments)
-        } else {
-            Ok((segment, index))
-        };
-    }
-
-    fn parse_label_related_args<'a>(
-        mut it: impl Iterator<Item = &'a str>,
-    ) -> Result<String, ParseCommandError> {
-        let label = it.next().ok_or(ParseCommandError::NotEnoughArguments)?;
-        let too_many_args = it.next().is_some();
-        return if too_many_args {
-            Err(ParseCommandError::TooManyArguments)
-        } else {
-            Ok(label.to_owned())
-        };
-    }
-
-    fn parse_func_related_args<'a>(
-        mut it: impl Iterator<Item = &'a str>,
-    ) -> Result<(String, u16), ParseCommandError> {
-        let name = it.next().ok_or(ParseCommandError::NotEnoughArguments)?;
-        let n_vars = it
-            .next()
-            .ok_or(ParseCommandError::NotEnoughArguments)
-            .and_then(|i| {
-                i.parse::<u16>()
-                    .map_err(|err| ParseCommandError::InvalidArgument(err.to_string()))
-            })?;
-        let too_many_args = it.next().is_some();
-        return if too_many_args {
-            Err(ParseCommandError::TooManyArguments)
-        } else {
-            Ok((name.to_string(), n_vars))
-        };
-    }
-
-    pub fn to_asm(&self, state: &mut TranslationState) -> Result<String, TranslationError> {
+impl ToAsm for Command {
+    fn to_asm(&self, state: &mut TranslationState) -> Result<String, TranslationError> {
         lazy_static! {
             static ref SEGMENT2SYMBOL: HashMap<Segment, &'static str> = HashMap::from([
                 (Segment::Local, "LCL"),
@@ -141,6 +21,16 @@ impl Command {
                 (Segment::That, "THAT")
             ]);
         }
+        if state.options().cache_top {
+            if let Some(asm) = cached::to_asm(self, state) {
+                return Ok(asm);
+            }
+            if state.is_top_cached() {
+                // the others need the whole stack in memory
+                let flush = cached::flush(state);
+                return Ok(format!("{flush}\n{}", self.to_asm(state)?));
+            }
+        }
         match self {
             Self::Add => Ok(r"// add
 @SP
@@ -190,6 +80,12 @@ M=D
                     name = state.name()
                 ))
             }
+            Self::Lt if state.options().safe_comparisons => {
+                Ok(signed_comparison(state, "lt", "JLT"))
+            }
+            Self::Gt if state.options().safe_comparisons => {
+                Ok(signed_comparison(state, "gt", "JGT"))
+            }
             Self::Lt => {
                 let cnt = state.advance_comparison_counter();
                 Ok(format!(
@@ -267,6 +163,14 @@ M=M|D"
 A=M-1 // *(sp-1) = !*(sp-1)
 M=!M"
                 .to_owned()),
+            Self::Mul | Self::Div | Self::Shl | Self::Shr if !state.options().extensions => {
+                Err(TranslationError {
+                    message: format!(
+                        "{self} is an extension command, enable extensions to translate it"
+                    ),
+                })
+            }
+            Self::Mul | Self::Div | Self::Shl | Self::Shr => Ok(extensions::to_asm(self, state)),
             Self::Pop {
                 segment: Segment::Static,
                 i,
@@ -338,7 +242,7 @@ D=M
 @R13
 A=M
 M=D",
-                symbol = SEGMENT2SYMBOL.get(&segment).unwrap()
+                symbol = SEGMENT2SYMBOL.get(segment).unwrap()
             )),
             Self::Push {
                 segment: Segment::Constant,
@@ -422,7 +326,7 @@ A=M
 M=D
 @SP
 M=M+1",
-                symbol = SEGMENT2SYMBOL.get(&segment).unwrap()
+                symbol = SEGMENT2SYMBOL.get(segment).unwrap()
             )),
             Self::Label(label) => Ok(if let Some(func) = state.func() {
                 format!("({func}${label})")
@@ -450,7 +354,7 @@ D;JNE"
 AM=M-1
 D=M
 @{label}
-D;JGT"
+D;JNE"
                 )
             }),
             Self::Function { name, n_vars } => {
@@ -470,6 +374,10 @@ M=D",
                     init = "\nM=0\nA=A+1".repeat(*n_vars as usize)
                 ))
             }
+            Self::Return if state.options().trampolines => Ok(format!(
+                "// return (from {func})\n@$$RETURN\n0;JMP",
+                func = current_function(state)?
+            )),
             Self::Return => Ok(format!(
                 r"// return (from {func})
 // R14(return addr) = *(LCL - 5)
@@ -517,12 +425,31 @@ M=D
 @R14
 A=M
 0;JMP",
-                func = state.func().ok_or(TranslationError {
-                    message: format!("Not in a function"),
-                })?
+                func = current_function(state)?
+            )),
+            Self::Call { name, n_args } if state.options().trampolines => Ok(format!(
+                r"// call {name} {n_args}
+@{name}
+D=A
+@R13
+M=D
+@{n_args}
+D=A
+@R14
+M=D
+@{scope}$ret.{i}
+D=A
+@R15
+M=D
+@$$CALL
+0;JMP
+({scope}$ret.{i})
+",
+                i = state.advance_ret_counter(),
+                scope = current_function(state)?
             )),
-            Self::Call { name, n_vars } => Ok(format!(
-                r"// call {name} {n_vars}
+            Self::Call { name, n_args } => Ok(format!(
+                r"// call {name} {n_args}
 @{scope}$ret.{i}
 D=A
 @SP
@@ -556,7 +483,7 @@ M=M+1
 D=M
 @5
 D=D-A
-@{n_vars}
+@{n_args}
 D=D-A
 @ARG
 M=D
@@ -572,10 +499,91 @@ M=D
 ({scope}$ret.{i})
 ",
                 i = state.advance_ret_counter(),
-                scope = state.func().ok_or(TranslationError {
-                    message: "Not in a function ".to_string()
-                })?
+                scope = current_function(state)?
             )),
         }
     }
 }
+
+/// The function being translated, which `call` and `return` must be in.
+fn current_function(state: &TranslationState) -> Result<&String, TranslationError> {
+    state.func().ok_or(TranslationError {
+        message: "Not in a function".to_string(),
+    })
+}
+
+/// Compares `x` and `y` by their signs when they differ, and by `x - y` otherwise,
+/// so that the subtraction can't overflow.
+fn signed_comparison(state: &mut TranslationState, command: &str, jump: &str) -> String {
+    let cnt = state.advance_comparison_counter();
+    // the result when x < 0 <= y, and the opposite one when y < 0 <= x
+    let (x_negative, y_negative) = if command == "lt" {
+        ("TRUE", "FALSE")
+    } else {
+        ("FALSE", "TRUE")
+    };
+    format!(
+        r"// {command}
+@SP
+AM=M-1 // D = *(--sp)
+D=M
+@CMPR.{name}.YNEG.{cnt}
+D;JLT
+@SP
+A=M-1 // D = *(sp-1)
+D=M
+@CMPR.{name}.SAME.{cnt}
+D;JGE
+@CMPR.{name}.{x_negative}.{cnt}
+0;JMP
+(CMPR.{name}.YNEG.{cnt})
+@SP
+A=M-1 // D = *(sp-1)
+D=M
+@CMPR.{name}.{y_negative}.{cnt}
+D;JGE
+(CMPR.{name}.SAME.{cnt})
+@SP
+A=M // D = *(sp-1) - *sp
+D=M
+A=A-1
+D=M-D
+@CMPR.{name}.TRUE.{cnt}
+D;{jump}
+(CMPR.{name}.FALSE.{cnt})
+@SP
+A=M-1 // *(sp-1) = 0
+M=0
+@CMPR.{name}.END.{cnt}
+0;JMP
+(CMPR.{name}.TRUE.{cnt})
+@SP
+A=M-1 // *(sp-1) = -1 (true)
+M=-1
+(CMPR.{name}.END.{cnt})
+",
+        name = state.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Options;
+
+    #[test]
+    fn if_goto_jumps_on_any_non_zero_value() {
+        let mut state = TranslationState::new("Main", Options::default());
+        let outside = Command::IfGoTo("LOOP".into()).to_asm(&mut state).unwrap();
+        Command::Function {
+            name: "Main.main".into(),
+            n_vars: 0,
+        }
+        .to_asm(&mut state)
+        .unwrap();
+        let inside = Command::IfGoTo("LOOP".into()).to_asm(&mut state).unwrap();
+        for asm in [outside, inside] {
+            assert_eq!(asm.lines().last(), Some("D;JNE"), "{asm}");
+        }
+    }
+}
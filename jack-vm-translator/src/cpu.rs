@@ -0,0 +1,145 @@
+use crate::interpreter::RAM_SIZE;
+
+pub const ROM_SIZE: usize = 32768;
+
+/// Emulates the Hack CPU, executing machine code produced by the assembler.
+pub struct Cpu {
+    rom: Vec<u16>,
+    ram: Vec<i16>,
+    a: i16,
+    d: i16,
+    pc: u16,
+    /// Addresses written to since the last call to `take_writes`.
+    writes: Vec<usize>,
+}
+
+impl Cpu {
+    pub fn new(program: &[u16]) -> Self {
+        let mut rom = program.to_vec();
+        rom.resize(ROM_SIZE, 0);
+        Self {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            writes: vec![],
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn peek(&self, addr: usize) -> i16 {
+        self.ram[addr]
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i16) {
+        self.ram[addr] = value;
+    }
+
+    pub fn take_writes(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.writes)
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self) {
+        let instruction = self.rom[self.pc as usize];
+        self.pc = (self.pc + 1) % ROM_SIZE as u16;
+        if instruction & 0x8000 == 0 {
+            self.a = instruction as i16;
+            return;
+        }
+        let bit = |n: u16| instruction >> n & 1 == 1;
+        let addr = self.a as u16 as usize % RAM_SIZE;
+        let mut x = self.d;
+        let mut y = if bit(12) { self.ram[addr] } else { self.a };
+        if bit(11) {
+            x = 0;
+        }
+        if bit(10) {
+            x = !x;
+        }
+        if bit(9) {
+            y = 0;
+        }
+        if bit(8) {
+            y = !y;
+        }
+        let mut out = if bit(7) { x.wrapping_add(y) } else { x & y };
+        if bit(6) {
+            out = !out;
+        }
+        if bit(3) {
+            self.ram[addr] = out;
+            self.writes.push(addr);
+        }
+        let target = self.a as u16 % ROM_SIZE as u16;
+        if bit(5) {
+            self.a = out;
+        }
+        if bit(4) {
+            self.d = out;
+        }
+        if (bit(2) && out < 0) || (bit(1) && out == 0) || (bit(0) && out > 0) {
+            self.pc = target;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cpu;
+    use assembler::Assembler;
+
+    fn assemble(asm: &str) -> Vec<u16> {
+        Assembler::new(asm.lines().map(str::to_string).collect())
+            .compile()
+            .iter()
+            .map(|word| u16::from_str_radix(word, 2).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn max() {
+        let program = assemble(
+            r"
+@R0
+D=M
+@R1
+D=D-M
+@FIRST
+D;JGT
+@R1
+D=M
+@R2
+M=D
+@END
+0;JMP
+(FIRST)
+@R0
+D=M
+@R2
+M=D
+(END)
+@END
+0;JMP
+",
+        );
+        for (x, y, max) in [(3, 5, 5), (-2, -7, -2), (9, 9, 9)] {
+            let mut cpu = Cpu::new(&program);
+            cpu.poke(0, x);
+            cpu.poke(1, y);
+            for _ in 0..20 {
+                cpu.step();
+            }
+            assert_eq!(cpu.peek(2), max);
+            assert_eq!(cpu.take_writes(), [2]);
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::{collections::HashSet, error::Error, ops::Range};
+
+use assembler::Assembler;
+
+use crate::command::ToAsm;
+use crate::cpu::Cpu;
+use crate::errors::{Divergence, RuntimeError};
+use crate::interpreter::{Interpreter, RAM_SIZE, THAT};
+use crate::parser::ParsedSource;
+use crate::test_script::Machine;
+use crate::translation_state::TranslationState;
+use crate::translator::{Bootstrap, Options, Translator};
+use jack_vm_ir::Command;
+
+/// Registers the translated code uses as scratch space.
+const SCRATCH: [usize; 3] = [13, 14, 15];
+
+/// Number of CPU instructions a single VM command may take before giving up.
+const TIMEOUT: usize = 100_000;
+
+/// Runs a program on the interpreter and, translated and assembled, on the CPU,
+/// checking after every VM command that both agree on the RAM and on where to continue.
+pub struct Lockstep {
+    interpreter: Interpreter,
+    cpu: Cpu,
+    /// ROM address of the code of each command, followed by the end of the program.
+    starts: Vec<u16>,
+    /// ROM addresses of the shared routines, which belong to the command that jumped there.
+    runtime: Range<u16>,
+    /// Whether the CPU is at the start of the bootstrap code.
+    bootstrapping: bool,
+}
+
+impl Lockstep {
+    /// Loads the program. With a `bootstrap`, both machines start by calling its entry function,
+    /// otherwise they start where the VM emulator would.
+    pub fn new(
+        sources: &[ParsedSource],
+        bootstrap: Option<&Bootstrap>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::with_options(sources, bootstrap, Options::default())
+    }
+
+    /// Like `new`, translating the program with the code generation `options`.
+    pub fn with_options(
+        sources: &[ParsedSource],
+        bootstrap: Option<&Bootstrap>,
+        options: Options,
+    ) -> Result<Self, Box<dyn Error>> {
+        if options.cache_top {
+            // the stack is only in memory between some commands
+            return Err("The code caching the top of the stack can't be run in lockstep".into());
+        }
+        // the native OS classes of the interpreter have no translation
+        let commands = || sources.iter().flat_map(|source| &source.commands);
+        let defined: HashSet<_> = commands()
+            .filter_map(|command| match command {
+                Command::Function { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let entry = match bootstrap {
+            Some(bootstrap) => Some(bootstrap.entry.as_str()),
+            None => defined.contains("Main.main").then_some("Sys.init"),
+        };
+        let called = commands().filter_map(|command| match command {
+            Command::Call { name, .. } => Some(name.as_str()),
+            _ => None,
+        });
+        if let Some(name) = called.chain(entry).find(|name| !defined.contains(name)) {
+            return Err(RuntimeError::UndefinedFunction(name.to_string()).into());
+        }
+
+        let mut asm = String::new();
+        if let Some(bootstrap) = bootstrap {
+            asm.push_str(&bootstrap.to_asm());
+        }
+        let mut starts = vec![];
+        let mut address = Translator::instruction_count(&asm);
+        for ParsedSource { name, commands, .. } in sources {
+            let mut state = TranslationState::new(name, options);
+            for command in commands {
+                let code = command.to_asm(&mut state)?;
+                starts.push(address as u16);
+                address += Translator::instruction_count(&code);
+                asm.push_str(&code);
+                asm.push('\n');
+            }
+        }
+        starts.push(address as u16);
+        let routines = Translator::runtime(options);
+        let runtime = address as u16..(address + Translator::instruction_count(&routines)) as u16;
+        asm.push_str(&routines);
+        let rom = Assembler::new(asm.lines().map(str::to_string).collect())
+            .try_compile()
+            .map_err(|errors| errors[0].clone())?
+            .iter()
+            .map(|word| u16::from_str_radix(word, 2))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut interpreter = Interpreter::new(sources)?;
+        interpreter.set_return_addresses(starts.iter().map(|&start| start as i16).collect());
+        interpreter.record_writes();
+        let mut cpu = Cpu::new(&rom);
+        if let Some(bootstrap) = bootstrap {
+            interpreter.bootstrap(&bootstrap.entry, bootstrap.sp as i16)?;
+        } else {
+            cpu.set_pc(starts[interpreter.pc()]);
+        }
+        for addr in 0..=THAT {
+            cpu.poke(addr, interpreter.peek(addr));
+        }
+        Ok(Self {
+            interpreter,
+            cpu,
+            starts,
+            runtime,
+            bootstrapping: bootstrap.is_some(),
+        })
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// Runs until the program halts, or `max_steps` commands have been executed.
+    pub fn run(&mut self, max_steps: Option<u64>) -> Result<(), Box<dyn Error>> {
+        while !self.interpreter.is_halted()
+            && max_steps.is_none_or(|max| self.interpreter.steps() < max)
+        {
+            self.step()?;
+        }
+        self.compare_all()?;
+        Ok(())
+    }
+
+    /// Index of the command whose code contains ROM address `pc`.
+    fn command_at(&self, pc: u16) -> usize {
+        self.starts.partition_point(|&start| start <= pc)
+    }
+
+    fn is_start(&self, pc: u16) -> bool {
+        self.starts.binary_search(&pc).is_ok()
+    }
+
+    /// Executes the code of the current command on the CPU, until it reaches the code of another one.
+    fn catch_up(&mut self, step: u64, command: &str) -> Result<(), Divergence> {
+        let current = self.command_at(self.cpu.pc());
+        for _ in 0..TIMEOUT {
+            self.cpu.step();
+            let pc = self.cpu.pc();
+            if self.runtime.contains(&pc) {
+                continue;
+            }
+            if self.is_start(pc) || self.command_at(pc) != current {
+                return Ok(());
+            }
+        }
+        Err(Divergence::Timeout {
+            step,
+            command: command.to_string(),
+        })
+    }
+
+    fn compare(
+        &self,
+        addrs: impl Iterator<Item = usize>,
+        step: u64,
+        command: &str,
+    ) -> Result<(), Divergence> {
+        for addr in addrs.filter(|addr| !SCRATCH.contains(addr)) {
+            let (interpreter, cpu) = (self.interpreter.peek(addr), self.cpu.peek(addr));
+            if interpreter != cpu {
+                return Err(Divergence::Memory {
+                    step,
+                    command: command.to_string(),
+                    addr,
+                    interpreter,
+                    cpu,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn compare_all(&self) -> Result<(), Divergence> {
+        self.compare(0..RAM_SIZE, self.interpreter.steps(), "end of program")
+    }
+}
+
+impl Machine for Lockstep {
+    fn load(sources: &[ParsedSource]) -> Result<Self, Box<dyn Error>> {
+        Self::new(sources, None)
+    }
+
+    fn peek(&self, addr: usize) -> i16 {
+        self.interpreter.peek(addr)
+    }
+
+    fn poke(&mut self, addr: usize, value: i16) {
+        self.interpreter.poke(addr, value);
+        self.cpu.poke(addr, value);
+    }
+
+    fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.interpreter.is_halted() {
+            return Ok(());
+        }
+        let command = self.interpreter.next_command().cloned();
+        let description = match &command {
+            Some(command) => format!("{command:?}"),
+            None if self.bootstrapping => "bootstrap".to_string(),
+            None => "call".to_string(),
+        };
+        self.interpreter.step()?;
+        let step = self.interpreter.steps();
+        let halted = self.interpreter.is_halted();
+        // Labels have no code. Commands that halt the interpreter have no effect,
+        // except for returning from the function the execution started in.
+        let has_code = match &command {
+            Some(Command::Label(_)) => false,
+            Some(Command::Return) => true,
+            _ => !halted || self.bootstrapping,
+        };
+        self.bootstrapping = false;
+        if has_code {
+            self.catch_up(step, &description)?;
+        }
+        if !halted {
+            let expected = self.starts[self.interpreter.pc()];
+            if self.cpu.pc() != expected {
+                return Err(Divergence::ControlFlow {
+                    step,
+                    command: description,
+                    expected,
+                    found: self.cpu.pc(),
+                }
+                .into());
+            }
+        }
+        let mut writes = self.interpreter.take_writes();
+        writes.extend(self.cpu.take_writes());
+        self.compare((0..=THAT).chain(writes), step, &description)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.compare_all()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::test_util::parse;
+    use crate::{parser::Parser, source::Source};
+
+    #[test]
+    fn bootstrapped_programs() {
+        let projects = Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/08/FunctionCalls");
+        for (dir, steps) in [
+            ("FibonacciElement", 104),
+            ("NestedCall", 42),
+            ("StaticsTest", 37),
+        ] {
+            let sources = Source::read(&projects.join(dir))
+                .unwrap()
+                .into_iter()
+                .map(|source| Parser::parse(source).unwrap())
+                .collect::<Vec<_>>();
+            let mut lockstep = Lockstep::new(&sources, Some(&Bootstrap::default())).unwrap();
+            lockstep.run(Some(10_000)).unwrap();
+            assert!(lockstep.interpreter().is_halted());
+            assert_eq!(lockstep.interpreter().steps(), steps, "{dir}");
+        }
+    }
+
+    #[test]
+    fn trampolines() {
+        let options = Options {
+            trampolines: true,
+            ..Options::default()
+        };
+        let projects = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        for dir in [
+            "projects/08/FunctionCalls/FibonacciElement",
+            "projects/08/FunctionCalls/NestedCall",
+            "projects/11/Seven",
+        ] {
+            let mut sources = Source::read(&projects.join(dir)).unwrap();
+            if dir.ends_with("Seven") {
+                sources.extend(Source::read(&projects.join("tools/OS")).unwrap());
+            }
+            let sources = sources
+                .into_iter()
+                .map(|source| Parser::parse(source).unwrap())
+                .collect::<Vec<_>>();
+            let mut lockstep =
+                Lockstep::with_options(&sources, Some(&Bootstrap::default()), options).unwrap();
+            lockstep.run(Some(200_000)).unwrap();
+        }
+    }
+
+    #[test]
+    fn comparisons_with_overflow() {
+        let values = [
+            i16::MIN,
+            i16::MIN + 1,
+            -2,
+            -1,
+            0,
+            1,
+            2,
+            i16::MAX - 1,
+            i16::MAX,
+        ];
+        let push = |value: i16| {
+            if value < 0 {
+                format!("push constant {}\nnot\n", !value)
+            } else {
+                format!("push constant {value}\n")
+            }
+        };
+        let mut code = "push constant 3000\npop pointer 1\n".to_string();
+        let mut expected = vec![];
+        for x in values {
+            for y in values {
+                for (command, result) in [("eq", x == y), ("gt", x > y), ("lt", x < y)] {
+                    code += &format!(
+                        "{}{}{command}\npop that {}\n",
+                        push(x),
+                        push(y),
+                        expected.len()
+                    );
+                    expected.push(-(result as i16));
+                }
+            }
+        }
+        let sources = parse(&[("Test", &code)]);
+        assert!(Lockstep::new(&sources, None).unwrap().run(None).is_err());
+        let options = Options {
+            safe_comparisons: true,
+            ..Options::default()
+        };
+        let mut lockstep = Lockstep::with_options(&sources, None, options).unwrap();
+        lockstep.run(None).unwrap();
+        for (i, &result) in expected.iter().enumerate() {
+            assert_eq!(lockstep.interpreter().peek(3000 + i), result, "{i}");
+        }
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let sources = parse(&[(
+            "Test",
+            "push constant 1\npush constant 0\ndiv\npop temp 0\n\
+             push constant 1\nneg\npush constant 0\ndiv\npop temp 1\n",
+        )]);
+        let options = Options {
+            extensions: true,
+            ..Options::default()
+        };
+        let mut lockstep = Lockstep::with_options(&sources, None, options).unwrap();
+        lockstep.run(None).unwrap();
+        assert_eq!(lockstep.interpreter().peek(5), 32767);
+        assert_eq!(lockstep.interpreter().peek(6), -32767);
+    }
+
+    #[test]
+    fn custom_bootstrap() {
+        let sources = parse(&[(
+            "Main",
+            r"
+function Main.main 0
+push constant 3
+call Main.double 1
+pop static 0
+label END
+goto END
+function Main.double 0
+push argument 0
+push argument 0
+add
+return
+",
+        )]);
+        let bootstrap = Bootstrap {
+            entry: "Main.main".into(),
+            sp: 1000,
+        };
+        assert!(bootstrap.is_defined_in(&sources));
+        assert!(!Bootstrap::default().is_defined_in(&sources));
+        let mut lockstep = Lockstep::new(&sources, Some(&bootstrap)).unwrap();
+        lockstep.run(Some(100)).unwrap();
+        assert_eq!(lockstep.interpreter().peek(16), 6);
+        assert_eq!(lockstep.interpreter().peek(1000), 0);
+        assert_eq!(lockstep.interpreter().peek(0), 1005);
+    }
+
+    #[test]
+    fn if_goto_outside_of_functions() {
+        // used to jump only on positive values
+        let sources = parse(&[(
+            "Test",
+            r"
+push constant 1
+neg
+if-goto SKIP
+push constant 5
+pop temp 0
+label SKIP
+push constant 7
+pop temp 1
+",
+        )]);
+        let mut lockstep = Lockstep::new(&sources, None).unwrap();
+        lockstep.run(None).unwrap();
+        assert_eq!(lockstep.interpreter().peek(5), 0);
+        assert_eq!(lockstep.interpreter().peek(6), 7);
+    }
+
+    #[test]
+    fn comparisons_and_returns() {
+        let sources = parse(&[(
+            "Main",
+            r"
+function Sys.init 0
+push constant 300
+push constant 1
+neg
+call Main.compare 2
+pop static 0
+label END
+goto END
+function Main.compare 1
+push argument 0
+push argument 1
+gt
+push argument 0
+push argument 1
+lt
+push argument 0
+push argument 0
+eq
+pop local 0
+or
+push local 0
+and
+return
+",
+        )]);
+        let mut lockstep = Lockstep::new(&sources, Some(&Bootstrap::default())).unwrap();
+        lockstep.run(None).unwrap();
+        assert_eq!(lockstep.interpreter().peek(16), -1);
+    }
+
+    #[test]
+    fn native_os_is_rejected() {
+        let sources = parse(&[(
+            "Main",
+            "function Main.main 0\npush constant 9\ncall Math.sqrt 1\nreturn",
+        )]);
+        assert!(Lockstep::new(&sources, None).is_err());
+    }
+}
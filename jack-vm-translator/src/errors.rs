@@ -1,26 +1,98 @@
 use std::error::Error;
 use std::fmt::{self, Debug};
 
-#[derive(Debug)]
-pub struct ParserError {
-    pub line_number: u32,
-    pub inner_error: ParseCommandError,
+pub use jack_vm_ir::ParseCommandError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
 }
 
-impl ParserError {
-    pub fn new(err: ParseCommandError, line_number: u32) -> Self {
+/// An error or a warning about a line of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the file, without the `.vm` extension.
+    pub file: String,
+    pub line: Option<u32>,
+    /// Column the problem starts at, the start of the command if unknown.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(file: &str, line: Option<u32>, message: String) -> Self {
         Self {
-            line_number,
-            inner_error: err,
+            severity: Severity::Error,
+            file: file.to_string(),
+            line,
+            column: None,
+            message,
+        }
+    }
+
+    /// Formats the diagnostic like `Main.vm:12:5: error: ...`,
+    /// followed by the line of `content` it is about and a caret under the column.
+    pub fn render(&self, content: Option<&str>) -> String {
+        let source_line = self
+            .line
+            .and_then(|line| content?.lines().nth(line as usize - 1));
+        let (Some(line), Some(source_line)) = (self.line, source_line) else {
+            return self.to_string();
+        };
+        let column = self.column.unwrap_or_else(|| {
+            source_line
+                .find(|c: char| !c.is_whitespace())
+                .map_or(1, |start| start as u32 + 1)
+        });
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "{}.vm:{line}:{column}: {}: {}\n{line} | {source_line}\n{gutter} | {}^",
+            self.file,
+            self.severity,
+            self.message,
+            " ".repeat(column as usize - 1)
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.vm", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
         }
+        write!(f, ": {}: {}", self.severity, self.message)
     }
 }
 
-impl Error for ParserError {}
-impl fmt::Display for ParserError {
+/// Every diagnostic about a program, of which at least one is an error.
+#[derive(Debug)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Error for Diagnostics {}
+impl fmt::Display for Diagnostics {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        std::fmt::Display::fmt(&self.inner_error, f)?;
-        write!(f, " on line {}", self.line_number)
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
     }
 }
 
@@ -37,30 +109,143 @@ impl fmt::Display for TranslationError {
 }
 
 #[derive(Debug)]
-pub enum ParseCommandError {
-    InvalidCommandName(String),
-    NotEnoughArguments,
-    TooManyArguments,
-    InvalidArgument(String),
-    NoCommand,
-    ParseSegmentError(String),
+pub enum RuntimeError {
+    UndefinedLabel {
+        function: String,
+        label: String,
+    },
+    DuplicateFunction(String),
+    UndefinedFunction(String),
+    ArgumentCountMismatch {
+        name: String,
+        expected: u16,
+        found: u16,
+    },
+    IllegalCommand(String),
+    AddressOutOfRange(i32),
+    KeyboardInputExhausted,
 }
 
-impl Error for ParseCommandError {}
-impl fmt::Display for ParseCommandError {
+impl Error for RuntimeError {}
+impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::InvalidArgument(arg) => {
-                write!(f, "Invalid argument \"{arg}\"")
+            Self::UndefinedLabel { function, label } => {
+                write!(f, "Label \"{label}\" is not defined in \"{function}\"")
             }
-            Self::NotEnoughArguments => write!(f, "Not enough arguments"),
-            Self::TooManyArguments => write!(f, "Too many arguments"),
-            Self::NoCommand => write!(f, "No command"),
-            Self::ParseSegmentError(segment) => {
-                write!(f, "Failed to parse segment \"{segment}\"")
+            Self::DuplicateFunction(name) => write!(f, "Function \"{name}\" is defined twice"),
+            Self::UndefinedFunction(name) => write!(f, "Function \"{name}\" is not defined"),
+            Self::ArgumentCountMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function \"{name}\" expects {expected} argument(s), but {found} were given"
+            ),
+            Self::IllegalCommand(command) => write!(f, "Illegal operation: {command}"),
+            Self::AddressOutOfRange(addr) => write!(f, "Address {addr} is out of range"),
+            Self::KeyboardInputExhausted => write!(f, "No more keyboard input"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    UnknownCommand(usize, String),
+    Io(String, std::io::Error),
+    Machine(Box<dyn Error>),
+    Comparison {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+}
+
+impl ScriptError {
+    pub fn syntax(line: usize, message: &str) -> Self {
+        Self::Syntax {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Syntax { line, message } => write!(f, "{message} on line {line}"),
+            Self::UnknownCommand(line, command) => {
+                write!(f, "Unknown script command \"{command}\" on line {line}")
             }
-            Self::InvalidCommandName(command) => {
-                write!(f, "Invalid command \"{command}\"")
+            Self::Io(file, err) => write!(f, "Failed to read {file}: {err}"),
+            Self::Machine(err) => fmt::Display::fmt(err, f),
+            Self::Comparison {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Comparison failure at line {line}\n  expected: {expected}\n     found: {found}"
+            ),
+        }
+    }
+}
+
+/// The translated code doesn't behave like the interpreter.
+#[derive(Debug)]
+pub enum Divergence {
+    Memory {
+        step: u64,
+        command: String,
+        addr: usize,
+        interpreter: i16,
+        cpu: i16,
+    },
+    ControlFlow {
+        step: u64,
+        command: String,
+        expected: u16,
+        found: u16,
+    },
+    Timeout {
+        step: u64,
+        command: String,
+    },
+}
+
+impl Error for Divergence {}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Memory {
+                step,
+                command,
+                addr,
+                interpreter,
+                cpu,
+            } => write!(
+                f,
+                "RAM[{addr}] is {cpu} on the CPU but {interpreter} in the interpreter \
+                 after step {step} ({command})"
+            ),
+            Self::ControlFlow {
+                step,
+                command,
+                expected,
+                found,
+            } => write!(
+                f,
+                "The CPU continued at ROM address {found} instead of {expected} \
+                 after step {step} ({command})"
+            ),
+            Self::Timeout { step, command } => {
+                write!(f, "The CPU didn't finish step {step} ({command})")
             }
         }
     }
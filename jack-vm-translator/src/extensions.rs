@@ -0,0 +1,393 @@
+//! The extension commands `mul`, `div`, `shl` and `shr`, translated to loops
+//! in place of the calls to `Math.multiply` and `Math.divide`.
+//!
+//! The code only uses R13 to R15 and the two stack slots of the operands:
+//! the result replaces `x`, and `y` is left above the stack, like the other commands do.
+
+use jack_vm_ir::Command;
+
+use crate::translation_state::TranslationState;
+
+/// Result of an extension command, or `None` for other commands.
+/// Dividing by zero gives 32767, or -32767 when `x` is negative, like the translated code.
+pub fn evaluate(command: &Command, x: i16, y: i16) -> Option<i16> {
+    Some(match command {
+        Command::Mul => x.wrapping_mul(y),
+        Command::Div if y == 0 && x < 0 => -32767,
+        Command::Div if y == 0 => 32767,
+        Command::Div => x.wrapping_div(y),
+        Command::Shl if y >= 16 => 0,
+        Command::Shl => x << y.max(0),
+        Command::Shr => x >> y.clamp(0, 15),
+        _ => return None,
+    })
+}
+
+/// Translates an extension command. Dividing by zero gives 32767 or -32767.
+/// Unlike `Math.divide`, it doesn't call `Sys.error`.
+pub fn to_asm(command: &Command, state: &mut TranslationState) -> String {
+    let cnt = state.advance_comparison_counter();
+    let name = state.name();
+    match command {
+        Command::Mul => mul(name, cnt),
+        Command::Div => div(name, cnt),
+        Command::Shl => shl(name, cnt),
+        Command::Shr => shr(name, cnt),
+        _ => unreachable!("{command} is not an extension command"),
+    }
+}
+
+/// Adds `x` shifted left for each bit of `y`, until no bit is left.
+fn mul(name: &str, cnt: u16) -> String {
+    format!(
+        r"// mul
+@SP
+AM=M-1 // R13 = the bits of y left
+D=M
+@R13
+M=D
+@SP
+A=M-1 // R14 = x, *(sp-1) = 0
+D=M
+M=0
+@R14
+M=D
+@R15 // R15 = the bit of y
+M=1
+(MUL.{name}.LOOP.{cnt})
+@R13
+D=M
+@MUL.{name}.END.{cnt}
+D;JEQ
+@R15
+D=D&M
+@MUL.{name}.NEXT.{cnt}
+D;JEQ
+@R15 // the bit is set: R13 -= R15, *(sp-1) += R14
+D=M
+@R13
+M=M-D
+@R14
+D=M
+@SP
+A=M-1
+M=M+D
+(MUL.{name}.NEXT.{cnt})
+@R14
+D=M
+M=D+M
+@R15
+D=M
+M=D+M
+@MUL.{name}.LOOP.{cnt}
+0;JMP
+(MUL.{name}.END.{cnt})"
+    )
+}
+
+/// Long division of `|x|` by `|y|`, one bit of `|x|` at a time.
+/// `|x|` has 15 bits, as -32768 is divided as 32767 and corrected afterwards.
+fn div(name: &str, cnt: u16) -> String {
+    // D = |y|, from the slot of y
+    let abs_y = |label: &str| {
+        format!("@SP\nA=M\nD=M\n@DIV.{name}.{label}.{cnt}\nD;JGE\nD=-D\n(DIV.{name}.{label}.{cnt})")
+    };
+    let abs_y_loop = abs_y("YPOS");
+    let abs_y_end = abs_y("YABS");
+    format!(
+        r"// div
+@SP
+AM=M-1 // y == -32768?
+D=M
+@32767
+D=D+A
+D=D+1
+@DIV.{name}.YMIN.{cnt}
+D;JEQ
+@SP // R13 = |x| shifted left once, 32767 for -32768
+A=M-1
+D=M
+@DIV.{name}.XPOS.{cnt}
+D;JGE
+D=-D
+@DIV.{name}.XPOS.{cnt}
+D;JGE
+D=!D
+(DIV.{name}.XPOS.{cnt})
+@R13
+M=D
+M=D+M
+@R14 // R14 = the remainder
+M=0
+@R15 // R15 = the quotient, with a bit marking the iterations done
+M=1
+(DIV.{name}.LOOP.{cnt})
+@R14 // R14 = R14 << 1 | the top bit of R13, R13 <<= 1
+D=M
+M=D+M
+@R13
+D=M
+M=D+M
+@DIV.{name}.SHIFTED.{cnt}
+D;JGE
+@R14
+M=M+1
+(DIV.{name}.SHIFTED.{cnt})
+@R15
+D=M
+M=D+M
+{abs_y_loop}
+@R14 // D = R14 - |y|, right even when R14 overflowed
+D=M-D
+@DIV.{name}.NEXT.{cnt}
+D;JLT
+@R14
+M=D
+@R15
+M=M+1
+(DIV.{name}.NEXT.{cnt})
+@R15
+D=M
+@DIV.{name}.LOOP.{cnt}
+D;JGE
+@32767 // R15 = the quotient
+D=D&A
+@R15
+M=D
+@SP // 32768 / |y| is one more than 32767 / |y| when the remainder reaches |y|
+A=M-1
+D=M
+@32767
+D=D+A
+D=D+1
+@DIV.{name}.SIGN.{cnt}
+D;JNE
+{abs_y_end}
+@R14
+D=D-M
+D=D-1
+@DIV.{name}.SIGN.{cnt}
+D;JNE
+@R15
+M=M+1
+(DIV.{name}.SIGN.{cnt}) // negate when x and y have different signs
+@SP
+A=M
+D=M
+@DIV.{name}.YNEG.{cnt}
+D;JLT
+@SP
+A=M-1
+D=M
+@DIV.{name}.NEGATE.{cnt}
+D;JLT
+@DIV.{name}.STORE.{cnt}
+0;JMP
+(DIV.{name}.YNEG.{cnt})
+@SP
+A=M-1
+D=M
+@DIV.{name}.STORE.{cnt}
+D;JLT
+(DIV.{name}.NEGATE.{cnt})
+@R15
+M=-M
+(DIV.{name}.STORE.{cnt})
+@R15
+D=M
+@SP
+A=M-1
+M=D
+@DIV.{name}.END.{cnt}
+0;JMP
+(DIV.{name}.YMIN.{cnt}) // x / -32768 is 1 for -32768 and 0 otherwise
+@SP
+A=M-1
+D=M
+M=0
+@32767
+D=D+A
+D=D+1
+@DIV.{name}.END.{cnt}
+D;JNE
+@SP
+A=M-1
+M=1
+(DIV.{name}.END.{cnt})"
+    )
+}
+
+/// Doubles `x` `y` times, stopping early once it is 0.
+fn shl(name: &str, cnt: u16) -> String {
+    format!(
+        r"// shl
+@SP
+AM=M-1 // R13 = y
+D=M
+@R13
+M=D
+@SHL.{name}.END.{cnt}
+D;JLE
+(SHL.{name}.LOOP.{cnt})
+@SP
+A=M-1 // *(sp-1) += *(sp-1)
+D=M
+MD=D+M
+@SHL.{name}.END.{cnt}
+D;JEQ
+@R13
+MD=M-1
+@SHL.{name}.LOOP.{cnt}
+D;JGT
+(SHL.{name}.END.{cnt})"
+    )
+}
+
+/// Copies the bits of `x` from bit `y` up to the bottom of the result,
+/// and fills the bits above them with the sign of `x`.
+fn shr(name: &str, cnt: u16) -> String {
+    format!(
+        r"// shr
+@SP
+AM=M-1
+D=M
+@SHR.{name}.END.{cnt}
+D;JLE
+@16
+D=D-A
+@SHR.{name}.SIGN.{cnt}
+D;JGE
+@16
+D=D+A
+@R13 // R14 = 1 << y
+M=D
+@R14
+M=1
+(SHR.{name}.MASK.{cnt})
+@R14
+D=M
+M=D+M
+@R13
+MD=M-1
+@SHR.{name}.MASK.{cnt}
+D;JGT
+@SP // R15 = x, *(sp-1) = 0
+A=M-1
+D=M
+M=0
+@R15
+M=D
+@R13 // R13 = the bit of the result
+M=1
+(SHR.{name}.LOOP.{cnt})
+@R15
+D=M
+@R14
+D=D&M
+@SHR.{name}.NEXT.{cnt}
+D;JEQ
+@R13
+D=M
+@SP
+A=M-1
+M=D|M
+(SHR.{name}.NEXT.{cnt})
+@R13
+D=M
+M=D+M
+@R14
+D=M
+MD=D+M
+@SHR.{name}.LOOP.{cnt}
+D;JNE
+@R15 // the bits from R13 up are the sign of x
+D=M
+@SHR.{name}.END.{cnt}
+D;JGE
+@R13
+D=-M
+@SP
+A=M-1
+M=D|M
+@SHR.{name}.END.{cnt}
+0;JMP
+(SHR.{name}.SIGN.{cnt}) // shifting by 16 or more leaves the sign
+@SP
+A=M-1
+D=M
+M=0
+@SHR.{name}.END.{cnt}
+D;JGE
+@SP
+A=M-1
+M=-1
+(SHR.{name}.END.{cnt})"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::ToAsm;
+    use crate::cpu::Cpu;
+    use crate::translator::{Options, Translator};
+
+    const VALUES: [i16; 17] = [
+        -32768, -32767, -16384, -1000, -17, -2, -1, 0, 1, 2, 3, 7, 15, 16, 255, 16384, 32767,
+    ];
+
+    /// Runs the code of `command` with `x` and `y` on the stack.
+    fn run(rom: &[u16], x: i16, y: i16) -> Cpu {
+        let mut cpu = Cpu::new(rom);
+        cpu.poke(0, 258);
+        cpu.poke(256, x);
+        cpu.poke(257, y);
+        let mut steps = 0;
+        while (cpu.pc() as usize) < rom.len() {
+            cpu.step();
+            steps += 1;
+            assert!(steps < 2000, "too many steps for {x} and {y}");
+        }
+        cpu
+    }
+
+    #[test]
+    fn extension_commands_match_their_evaluation() {
+        let options = Options {
+            extensions: true,
+            ..Options::default()
+        };
+        for command in [Command::Mul, Command::Div, Command::Shl, Command::Shr] {
+            let mut state = TranslationState::new("Main", options);
+            let asm = command.to_asm(&mut state).unwrap();
+            let rom: Vec<u16> = Translator::assemble(&asm, &[])
+                .unwrap()
+                .lines()
+                .map(|word| u16::from_str_radix(word, 2).unwrap())
+                .collect();
+            let shifts = [-1, 0, 1, 4, 14, 15, 16, 100, -32768];
+            let ys = if matches!(command, Command::Shl | Command::Shr) {
+                &shifts[..]
+            } else {
+                &VALUES[..]
+            };
+            for x in VALUES {
+                for &y in ys {
+                    let expected = evaluate(&command, x, y).unwrap();
+                    let cpu = run(&rom, x, y);
+                    assert_eq!(cpu.peek(256), expected, "{x} {command} {y}");
+                    assert_eq!((cpu.peek(0), cpu.peek(257)), (257, y), "{x} {command} {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extension_commands_must_be_enabled() {
+        let mut state = TranslationState::new("Main", Options::default());
+        assert_eq!(
+            Command::Mul.to_asm(&mut state).unwrap_err().message,
+            "mul is an extension command, enable extensions to translate it"
+        );
+    }
+}
@@ -0,0 +1,460 @@
+mod os;
+mod program;
+mod tests;
+
+use std::{io::Write, rc::Rc};
+
+use crate::errors::RuntimeError;
+use crate::extensions;
+use crate::parser::ParsedSource;
+use jack_vm_ir::Command;
+use jack_vm_ir::Segment;
+
+use self::os::OsState;
+use self::program::{Callee, Program};
+
+pub const RAM_SIZE: usize = 32768;
+pub const SP: usize = 0;
+pub const LCL: usize = 1;
+pub const ARG: usize = 2;
+pub const THIS: usize = 3;
+pub const THAT: usize = 4;
+pub const TEMP: usize = 5;
+pub const STACK_BASE: i16 = 256;
+pub const SCREEN: usize = 16384;
+pub const KBD: usize = 24576;
+
+/// Reasons for the execution to stop abruptly.
+pub(crate) enum Trap {
+    Halt,
+    StepLimit,
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Trap {
+    fn from(err: RuntimeError) -> Self {
+        Self::Error(err)
+    }
+}
+
+struct Frame {
+    /// Where to continue after returning. `None` if the function was invoked by native code.
+    return_to: Option<usize>,
+}
+
+/// Executes VM code directly, using the native OS classes
+/// for the classes the program doesn't provide.
+pub struct Interpreter {
+    program: Rc<Program>,
+    ram: Vec<i16>,
+    pc: usize,
+    frames: Vec<Frame>,
+    /// Function to call before executing the next instruction.
+    pending_call: Option<usize>,
+    halted: bool,
+    steps: u64,
+    step_limit: Option<u64>,
+    error_code: Option<i16>,
+    os: OsState,
+    console: String,
+    echo: Option<Box<dyn Write>>,
+    keyboard: Box<dyn Iterator<Item = i16>>,
+    /// Values pushed as return addresses, by instruction index. Defaults to the index itself.
+    return_addresses: Option<Vec<i16>>,
+    /// Addresses written to by commands since the last call to `take_writes`.
+    writes: Option<Vec<usize>>,
+}
+
+impl Interpreter {
+    /// Loads the program the way the VM emulator does:
+    /// execution starts at `Sys.init` if the program defines it,
+    /// otherwise at the native `Sys.init` if there is a `Main.main` to run,
+    /// otherwise at the first command.
+    pub fn new(sources: &[ParsedSource]) -> Result<Self, RuntimeError> {
+        let program = Program::load(sources)?;
+        let mut interpreter = Self {
+            program: Rc::new(program),
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+            frames: vec![],
+            pending_call: None,
+            halted: false,
+            steps: 0,
+            step_limit: None,
+            error_code: None,
+            os: OsState::default(),
+            console: String::new(),
+            echo: None,
+            keyboard: Box::new(std::iter::empty()),
+            return_addresses: None,
+            writes: None,
+        };
+        interpreter.ram[SP] = STACK_BASE;
+        if let Some(Callee::Function { pc }) = interpreter.callee("Sys.init") {
+            interpreter.pc = *pc;
+        } else if interpreter.program.defines("Main.main") {
+            interpreter.pending_call = interpreter.program.callee_index("Sys.init");
+        }
+        Ok(interpreter)
+    }
+
+    /// Starts the program the way the bootstrap code of the translator does:
+    /// by calling `entry` with an empty stack at `sp`.
+    pub fn bootstrap(&mut self, entry: &str, sp: i16) -> Result<(), RuntimeError> {
+        self.pending_call = Some(
+            self.program
+                .callee_index(entry)
+                .ok_or_else(|| RuntimeError::UndefinedFunction(entry.to_string()))?,
+        );
+        self.ram[SP] = sp;
+        Ok(())
+    }
+
+    /// Pushes `addresses[i]` instead of `i` as the return address of a call returning to command `i`,
+    /// so the stack frames look like the ones of the translated code.
+    pub fn set_return_addresses(&mut self, addresses: Vec<i16>) {
+        self.return_addresses = Some(addresses);
+    }
+
+    /// Starts recording the addresses written to by commands.
+    pub fn record_writes(&mut self) {
+        self.writes = Some(vec![]);
+    }
+
+    pub fn take_writes(&mut self) -> Vec<usize> {
+        self.writes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Feeds the keyboard with key codes, as read by `Keyboard.readChar`.
+    pub fn set_keyboard(&mut self, keys: impl Iterator<Item = i16> + 'static) {
+        self.keyboard = Box::new(keys);
+    }
+
+    /// Copies everything printed with `Output` to `writer`.
+    pub fn set_echo(&mut self, writer: impl Write + 'static) {
+        self.echo = Some(Box::new(writer));
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// The error code passed to the native `Sys.error`, if the program failed.
+    pub fn error_code(&self) -> Option<i16> {
+        self.error_code
+    }
+
+    /// Text printed with the native `Output` class.
+    #[cfg(test)]
+    pub fn console(&self) -> &str {
+        &self.console
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Index of the next command to execute.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The next command to execute, `None` if a call is pending or the program ended.
+    pub fn next_command(&self) -> Option<&Command> {
+        match self.pending_call {
+            Some(_) => None,
+            None => self.program.instructions[self.pc.min(self.program.instructions.len())..]
+                .iter()
+                .map(|instruction| &instruction.command)
+                .find(|command| !matches!(command, Command::Label(_))),
+        }
+    }
+
+    pub fn peek(&self, addr: usize) -> i16 {
+        self.ram[addr]
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i16) {
+        self.ram[addr] = value;
+    }
+
+    /// Executes one command.
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        if self.halted {
+            return Ok(());
+        }
+        match self.exec() {
+            Ok(()) => Ok(()),
+            Err(Trap::Halt | Trap::StepLimit) => {
+                // The step limit can interrupt native code, which can't be resumed.
+                self.halted = true;
+                Ok(())
+            }
+            Err(Trap::Error(err)) => {
+                self.halted = true;
+                Err(err)
+            }
+        }
+    }
+
+    /// Runs until the program halts, or `max_steps` commands have been executed in total.
+    pub fn run(&mut self, max_steps: Option<u64>) -> Result<(), RuntimeError> {
+        self.step_limit = max_steps;
+        while !self.halted && max_steps.is_none_or(|max| self.steps < max) {
+            self.step()?;
+        }
+        self.step_limit = None;
+        Ok(())
+    }
+
+    fn callee(&self, name: &str) -> Option<&Callee> {
+        self.program
+            .callee_index(name)
+            .map(|index| self.program.callee(index))
+    }
+
+    fn read(&self, addr: i32) -> Result<i16, RuntimeError> {
+        usize::try_from(addr)
+            .ok()
+            .and_then(|addr| self.ram.get(addr))
+            .copied()
+            .ok_or(RuntimeError::AddressOutOfRange(addr))
+    }
+
+    fn write(&mut self, addr: i32, value: i16) -> Result<(), RuntimeError> {
+        *usize::try_from(addr)
+            .ok()
+            .and_then(|addr| self.ram.get_mut(addr))
+            .ok_or(RuntimeError::AddressOutOfRange(addr))? = value;
+        if let Some(writes) = self.writes.as_mut() {
+            writes.push(addr as usize);
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, value: i16) -> Result<(), RuntimeError> {
+        let sp = self.ram[SP];
+        self.write(sp as i32, value)?;
+        self.ram[SP] = sp.wrapping_add(1);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i16, RuntimeError> {
+        let sp = self.ram[SP].wrapping_sub(1);
+        self.ram[SP] = sp;
+        self.read(sp as i32)
+    }
+
+    fn echo(&mut self, c: char) {
+        if c == '\u{8}' {
+            self.console.pop();
+        } else {
+            self.console.push(c);
+        }
+        if let Some(echo) = self.echo.as_mut() {
+            let _ = match c {
+                '\u{8}' => write!(echo, "\u{8} \u{8}"),
+                c => write!(echo, "{c}"),
+            };
+            let _ = echo.flush();
+        }
+    }
+
+    /// RAM address of `segment i`. `operand` is the resolved address of static variables.
+    fn address(&self, segment: Segment, i: u16, operand: usize) -> Result<i32, RuntimeError> {
+        let base = |pointer: usize| self.ram[pointer] as i32;
+        Ok(match segment {
+            Segment::Local => base(LCL) + i as i32,
+            Segment::Argument => base(ARG) + i as i32,
+            Segment::This => base(THIS) + i as i32,
+            Segment::That => base(THAT) + i as i32,
+            Segment::Pointer => (THIS + i as usize) as i32,
+            Segment::Temp => (TEMP + i as usize) as i32,
+            Segment::Static => operand as i32,
+            Segment::Constant => {
+                return Err(RuntimeError::IllegalCommand(format!("pop constant {i}")));
+            }
+        })
+    }
+
+    fn binary(&mut self, op: impl Fn(i16, i16) -> i16) -> Result<(), RuntimeError> {
+        let y = self.pop()?;
+        let x = self.pop()?;
+        self.push(op(x, y))
+    }
+
+    fn unary(&mut self, op: impl Fn(i16) -> i16) -> Result<(), RuntimeError> {
+        let x = self.pop()?;
+        self.push(op(x))
+    }
+
+    fn exec(&mut self) -> Result<(), Trap> {
+        self.steps += 1;
+        if self.step_limit.is_some_and(|limit| self.steps > limit) {
+            return Err(Trap::StepLimit);
+        }
+        if let Some(callee) = self.pending_call.take() {
+            // returning from the entry function ends the program
+            let end = self.program.instructions.len();
+            return self.call(callee, 0, Some(end), 0);
+        }
+        let program = Rc::clone(&self.program);
+        // like in the VM emulator, labels don't take a step
+        while let Some(Command::Label(_)) = program.instructions.get(self.pc).map(|i| &i.command) {
+            self.pc += 1;
+        }
+        let Some(instruction) = program.instructions.get(self.pc) else {
+            return Err(Trap::Halt);
+        };
+        let operand = instruction.operand;
+        let truth = |b: bool| -(b as i16);
+        match &instruction.command {
+            Command::Add => self.binary(i16::wrapping_add)?,
+            Command::Sub => self.binary(i16::wrapping_sub)?,
+            Command::Neg => self.unary(i16::wrapping_neg)?,
+            Command::Eq => self.binary(|x, y| truth(x == y))?,
+            Command::Gt => self.binary(|x, y| truth(x > y))?,
+            Command::Lt => self.binary(|x, y| truth(x < y))?,
+            Command::And => self.binary(|x, y| x & y)?,
+            Command::Or => self.binary(|x, y| x | y)?,
+            Command::Not => self.unary(|x| !x)?,
+            command @ (Command::Mul | Command::Div | Command::Shl | Command::Shr) => {
+                self.binary(|x, y| extensions::evaluate(command, x, y).unwrap())?
+            }
+            Command::Push {
+                segment: Segment::Constant,
+                i,
+            } => self.push(*i as i16)?,
+            Command::Push { segment, i } => {
+                let value = self.read(self.address(*segment, *i, operand)?)?;
+                self.push(value)?;
+            }
+            Command::Pop { segment, i } => {
+                let addr = self.address(*segment, *i, operand)?;
+                let value = self.pop()?;
+                self.write(addr, value)?;
+            }
+            Command::Label(_) => {}
+            Command::GoTo(_) => {
+                // A jump back over nothing but labels loops forever, the usual way to end a program.
+                if operand <= self.pc
+                    && program.instructions[operand..self.pc]
+                        .iter()
+                        .all(|instruction| matches!(instruction.command, Command::Label(_)))
+                {
+                    return Err(Trap::Halt);
+                }
+                self.pc = operand;
+                return Ok(());
+            }
+            Command::IfGoTo(_) => {
+                if self.pop()? != 0 {
+                    self.pc = operand;
+                    return Ok(());
+                }
+            }
+            Command::Function { n_vars, .. } => {
+                for _ in 0..*n_vars {
+                    self.push(0)?;
+                }
+            }
+            Command::Call { n_args, .. } => {
+                let return_to = self.pc + 1;
+                let return_address = match self.return_addresses.as_ref() {
+                    Some(addresses) => addresses[return_to],
+                    None => return_to as i16,
+                };
+                return self.call(operand, *n_args, Some(return_to), return_address);
+            }
+            Command::Return => return self.ret(),
+        }
+        self.pc += 1;
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        callee: usize,
+        n_args: u16,
+        return_to: Option<usize>,
+        return_address: i16,
+    ) -> Result<(), Trap> {
+        let program = Rc::clone(&self.program);
+        match program.callee(callee) {
+            Callee::Function { pc } => {
+                self.push(return_address)?;
+                for pointer in [LCL, ARG, THIS, THAT] {
+                    self.push(self.ram[pointer])?;
+                }
+                self.ram[ARG] = self.ram[SP].wrapping_sub(5 + n_args as i16);
+                self.ram[LCL] = self.ram[SP];
+                self.frames.push(Frame { return_to });
+                self.pc = *pc;
+            }
+            Callee::Native(native) => {
+                let mut args = vec![0; n_args as usize];
+                for arg in args.iter_mut().rev() {
+                    *arg = self.pop()?;
+                }
+                let value = (native.function)(self, &args)?;
+                self.push(value)?;
+                if let Some(pc) = return_to {
+                    self.pc = pc;
+                }
+            }
+            Callee::Undefined(name) => {
+                return Err(RuntimeError::UndefinedFunction(name.to_string()).into())
+            }
+        }
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<(), Trap> {
+        let frame = self.ram[LCL] as i32;
+        let value = self.pop()?;
+        self.write(self.ram[ARG] as i32, value)?;
+        self.ram[SP] = self.ram[ARG].wrapping_add(1);
+        for (offset, pointer) in [THAT, THIS, ARG, LCL].into_iter().enumerate() {
+            self.ram[pointer] = self.read(frame - 1 - offset as i32)?;
+        }
+        match self.frames.pop() {
+            Some(Frame {
+                return_to: Some(pc),
+            }) => self.pc = pc,
+            Some(Frame { return_to: None }) => {}
+            // returning from the function the execution started in
+            None => return Err(Trap::Halt),
+        }
+        Ok(())
+    }
+
+    /// Calls a function from native code and returns its result.
+    pub(crate) fn invoke(&mut self, name: &str, args: &[i16]) -> Result<i16, Trap> {
+        let index = self
+            .program
+            .callee_index(name)
+            .ok_or_else(|| RuntimeError::UndefinedFunction(name.to_string()))?;
+        if let Callee::Native(native) = self.program.callee(index) {
+            if native.arity as usize != args.len() {
+                return Err(RuntimeError::ArgumentCountMismatch {
+                    name: name.to_string(),
+                    expected: native.arity,
+                    found: args.len() as u16,
+                }
+                .into());
+            }
+            return (native.function)(self, args);
+        }
+        for arg in args {
+            self.push(*arg)?;
+        }
+        let pc = self.pc;
+        self.call(index, args.len() as u16, None, 0)?;
+        let depth = self.frames.len();
+        while self.frames.len() >= depth {
+            self.exec()?;
+        }
+        self.pc = pc;
+        Ok(self.pop()?)
+    }
+}
@@ -0,0 +1,17 @@
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[("new", 1, new), ("dispose", 1, dispose)];
+
+fn new(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    if args[0] <= 0 {
+        return vm.sys_error(ARRAY_NEW_NONPOSITIVE_SIZE);
+    }
+    vm.invoke("Memory.alloc", args)
+}
+
+fn dispose(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.invoke("Memory.deAlloc", args)?;
+    Ok(0)
+}
@@ -0,0 +1,58 @@
+use super::NativeFn;
+use crate::errors::RuntimeError;
+use crate::interpreter::{Interpreter, Trap, KBD};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("keyPressed", 0, key_pressed),
+    ("readChar", 0, read_char),
+    ("readLine", 1, read_line),
+    ("readInt", 1, read_int),
+];
+
+const NEW_LINE: i16 = 128;
+const BACK_SPACE: i16 = 129;
+/// Capacity of the strings returned by `readLine`.
+const LINE_CAPACITY: i16 = 80;
+
+fn init(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(0)
+}
+
+fn key_pressed(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(vm.read(KBD as i32)?)
+}
+
+fn read_char(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    let c = vm
+        .keyboard
+        .next()
+        .ok_or(RuntimeError::KeyboardInputExhausted)?;
+    vm.invoke("Output.printChar", &[c])?;
+    Ok(c)
+}
+
+fn read_line(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.invoke("Output.printString", args)?;
+    let line = vm.invoke("String.new", &[LINE_CAPACITY])?;
+    loop {
+        match read_char(vm, &[])? {
+            NEW_LINE => return Ok(line),
+            BACK_SPACE => {
+                if vm.invoke("String.length", &[line])? > 0 {
+                    vm.invoke("String.eraseLastChar", &[line])?;
+                }
+            }
+            c => {
+                vm.invoke("String.appendChar", &[line, c])?;
+            }
+        }
+    }
+}
+
+fn read_int(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let line = read_line(vm, args)?;
+    let value = vm.invoke("String.intValue", &[line])?;
+    vm.invoke("String.dispose", &[line])?;
+    Ok(value)
+}
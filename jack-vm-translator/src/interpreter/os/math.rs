@@ -0,0 +1,47 @@
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("abs", 1, abs),
+    ("multiply", 2, multiply),
+    ("divide", 2, divide),
+    ("min", 2, min),
+    ("max", 2, max),
+    ("sqrt", 1, sqrt),
+];
+
+fn init(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(0)
+}
+
+fn abs(_: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(args[0].wrapping_abs())
+}
+
+fn multiply(_: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(args[0].wrapping_mul(args[1]))
+}
+
+fn divide(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    if args[1] == 0 {
+        return vm.sys_error(MATH_DIVIDE_BY_ZERO);
+    }
+    Ok(args[0].wrapping_div(args[1]))
+}
+
+fn min(_: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(args[0].min(args[1]))
+}
+
+fn max(_: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(args[0].max(args[1]))
+}
+
+fn sqrt(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    if args[0] < 0 {
+        return vm.sys_error(MATH_SQRT_NEGATIVE);
+    }
+    Ok((args[0] as f64).sqrt() as i16)
+}
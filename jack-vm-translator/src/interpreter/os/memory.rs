@@ -0,0 +1,98 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("peek", 1, peek),
+    ("poke", 2, poke),
+    ("alloc", 1, alloc),
+    ("deAlloc", 1, de_alloc),
+];
+
+const HEAP_BASE: i16 = 2048;
+const HEAP_END: i16 = 16384;
+
+/// Bookkeeping of the heap lives outside of the RAM,
+/// so programs can't corrupt it by writing past their objects.
+#[derive(Default)]
+pub(super) struct Heap {
+    initialized: bool,
+    /// Free segments, by base address.
+    free: BTreeMap<i16, i16>,
+    /// Sizes of allocated blocks, by base address.
+    blocks: HashMap<i16, i16>,
+}
+
+impl Heap {
+    fn init(&mut self) {
+        self.initialized = true;
+        self.free = BTreeMap::from([(HEAP_BASE, HEAP_END - HEAP_BASE)]);
+        self.blocks.clear();
+    }
+
+    /// First-fit allocation.
+    fn alloc(&mut self, size: i16) -> Option<i16> {
+        if !self.initialized {
+            self.init();
+        }
+        let (&base, &len) = self.free.iter().find(|(_, len)| **len >= size)?;
+        self.free.remove(&base);
+        if len > size {
+            self.free.insert(base + size, len - size);
+        }
+        self.blocks.insert(base, size);
+        Some(base)
+    }
+
+    fn de_alloc(&mut self, base: i16) {
+        let Some(mut len) = self.blocks.remove(&base) else {
+            return;
+        };
+        let mut base = base;
+        // merge with the adjacent free segments
+        if let Some(next_len) = self.free.remove(&(base + len)) {
+            len += next_len;
+        }
+        if let Some((&prev, &prev_len)) = self.free.range(..base).next_back() {
+            if prev + prev_len == base {
+                self.free.remove(&prev);
+                base = prev;
+                len += prev_len;
+            }
+        }
+        self.free.insert(base, len);
+    }
+}
+
+fn init(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    vm.os.heap.init();
+    Ok(0)
+}
+
+fn peek(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(vm.read(args[0] as i32)?)
+}
+
+fn poke(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.write(args[0] as i32, args[1])?;
+    Ok(0)
+}
+
+fn alloc(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let size = args[0];
+    if size < 0 {
+        return vm.sys_error(MEMORY_ALLOC_NEGATIVE_SIZE);
+    }
+    match vm.os.heap.alloc(size.max(1)) {
+        Some(base) => Ok(base),
+        None => vm.sys_error(MEMORY_ALLOC_HEAP_OVERFLOW),
+    }
+}
+
+fn de_alloc(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.os.heap.de_alloc(args[0]);
+    Ok(0)
+}
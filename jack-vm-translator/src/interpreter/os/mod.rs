@@ -0,0 +1,92 @@
+//! Native implementations of the Jack OS classes (see `tools/OS`).
+//!
+//! They follow the official OS API, including the error codes reported
+//! through `Sys.error`. Calls to other OS classes always go through
+//! [`Interpreter::invoke`], so a native class keeps working when the program
+//! brings its own implementation of a class it depends on.
+
+mod array;
+mod font;
+mod keyboard;
+mod math;
+mod memory;
+mod output;
+mod screen;
+mod string;
+mod sys;
+
+use super::{Interpreter, Trap};
+
+pub(super) type NativeFn = fn(&mut Interpreter, &[i16]) -> Result<i16, Trap>;
+
+#[derive(Clone, Copy)]
+pub(super) struct Native {
+    pub arity: u16,
+    pub function: NativeFn,
+}
+
+type Class = (&'static str, &'static [(&'static str, u16, NativeFn)]);
+
+static CLASSES: &[Class] = &[
+    ("Array", array::FUNCTIONS),
+    ("Keyboard", keyboard::FUNCTIONS),
+    ("Math", math::FUNCTIONS),
+    ("Memory", memory::FUNCTIONS),
+    ("Output", output::FUNCTIONS),
+    ("Screen", screen::FUNCTIONS),
+    ("String", string::FUNCTIONS),
+    ("Sys", sys::FUNCTIONS),
+];
+
+/// All native functions, with their fully qualified names.
+pub(super) fn natives() -> impl Iterator<Item = (String, Native)> {
+    CLASSES.iter().flat_map(|(class, functions)| {
+        functions.iter().map(move |(name, arity, function)| {
+            (
+                format!("{class}.{name}"),
+                Native {
+                    arity: *arity,
+                    function: *function,
+                },
+            )
+        })
+    })
+}
+
+/// Error codes of the official OS, reported through `Sys.error`.
+mod error_code {
+    pub const SYS_WAIT_DURATION_NEGATIVE: i16 = 1;
+    pub const ARRAY_NEW_NONPOSITIVE_SIZE: i16 = 2;
+    pub const MATH_DIVIDE_BY_ZERO: i16 = 3;
+    pub const MATH_SQRT_NEGATIVE: i16 = 4;
+    pub const MEMORY_ALLOC_NEGATIVE_SIZE: i16 = 5;
+    pub const MEMORY_ALLOC_HEAP_OVERFLOW: i16 = 6;
+    pub const SCREEN_DRAW_PIXEL_ILLEGAL_COORDS: i16 = 7;
+    pub const SCREEN_DRAW_LINE_ILLEGAL_COORDS: i16 = 8;
+    pub const SCREEN_DRAW_RECTANGLE_ILLEGAL_COORDS: i16 = 9;
+    pub const SCREEN_DRAW_CIRCLE_ILLEGAL_CENTER: i16 = 12;
+    pub const SCREEN_DRAW_CIRCLE_ILLEGAL_RADIUS: i16 = 13;
+    pub const STRING_NEW_NEGATIVE_LENGTH: i16 = 14;
+    pub const STRING_CHAR_AT_OUT_OF_BOUNDS: i16 = 15;
+    pub const STRING_SET_CHAR_AT_OUT_OF_BOUNDS: i16 = 16;
+    pub const STRING_APPEND_CHAR_FULL: i16 = 17;
+    pub const STRING_ERASE_LAST_CHAR_EMPTY: i16 = 18;
+    pub const STRING_SET_INT_INSUFFICIENT_CAPACITY: i16 = 19;
+    pub const OUTPUT_MOVE_CURSOR_ILLEGAL_POSITION: i16 = 20;
+}
+
+/// State kept by the native classes between calls.
+#[derive(Default)]
+pub(super) struct OsState {
+    heap: memory::Heap,
+    cursor: output::Cursor,
+    color: screen::Color,
+}
+
+impl Interpreter {
+    /// Reports `code` through `Sys.error`, which never returns.
+    fn sys_error(&mut self, code: i16) -> Result<i16, Trap> {
+        self.invoke("Sys.error", &[code])?;
+        Err(Trap::Halt)
+    }
+}
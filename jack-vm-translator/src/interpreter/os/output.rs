@@ -0,0 +1,119 @@
+use super::error_code::*;
+use super::font::{GLYPHS, UNKNOWN};
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap, SCREEN};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("moveCursor", 2, move_cursor),
+    ("printChar", 1, print_char),
+    ("printString", 1, print_string),
+    ("printInt", 1, print_int),
+    ("println", 0, println),
+    ("backSpace", 0, back_space),
+];
+
+const ROWS: i16 = 23;
+const COLUMNS: i16 = 64;
+const GLYPH_HEIGHT: i32 = 11;
+const NEW_LINE: i16 = 128;
+const BACK_SPACE: i16 = 129;
+
+#[derive(Default)]
+pub(super) struct Cursor {
+    row: i16,
+    column: i16,
+}
+
+impl Interpreter {
+    /// Draws `c` at the cursor, without moving it.
+    fn draw_char(&mut self, c: i16) -> Result<(), Trap> {
+        let glyph = match c {
+            32..=126 => &GLYPHS[c as usize - 32],
+            _ => &UNKNOWN,
+        };
+        let Cursor { row, column } = self.os.cursor;
+        // two characters share one 16-pixel word
+        let (mask, shift) = if column % 2 == 0 {
+            (0xFF00u16 as i16, 0)
+        } else {
+            (0x00FF, 8)
+        };
+        let base = SCREEN as i32 + row as i32 * GLYPH_HEIGHT * 32 + column as i32 / 2;
+        for (i, line) in glyph.iter().enumerate() {
+            let addr = base + i as i32 * 32;
+            let word = self.read(addr)? & mask | ((*line as i16) << shift);
+            self.write(addr, word)?;
+        }
+        Ok(())
+    }
+}
+
+fn init(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    vm.os.cursor = Cursor::default();
+    Ok(0)
+}
+
+fn move_cursor(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (row, column) = (args[0], args[1]);
+    if !(0..ROWS).contains(&row) || !(0..COLUMNS).contains(&column) {
+        return vm.sys_error(OUTPUT_MOVE_CURSOR_ILLEGAL_POSITION);
+    }
+    vm.os.cursor = Cursor { row, column };
+    vm.draw_char(' ' as i16)?;
+    Ok(0)
+}
+
+fn print_char(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    match args[0] {
+        NEW_LINE => println(vm, &[]),
+        BACK_SPACE => back_space(vm, &[]),
+        c => {
+            vm.draw_char(c)?;
+            vm.echo(char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+            vm.os.cursor.column += 1;
+            if vm.os.cursor.column == COLUMNS {
+                println(vm, &[])
+            } else {
+                Ok(0)
+            }
+        }
+    }
+}
+
+fn print_string(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let length = vm.invoke("String.length", args)?;
+    for i in 0..length {
+        let c = vm.invoke("String.charAt", &[args[0], i])?;
+        print_char(vm, &[c])?;
+    }
+    Ok(0)
+}
+
+fn print_int(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    for c in args[0].to_string().bytes() {
+        print_char(vm, &[c as i16])?;
+    }
+    Ok(0)
+}
+
+fn println(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    vm.echo('\n');
+    let cursor = &mut vm.os.cursor;
+    cursor.column = 0;
+    cursor.row = (cursor.row + 1) % ROWS;
+    Ok(0)
+}
+
+fn back_space(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    vm.echo('\u{8}');
+    let cursor = &mut vm.os.cursor;
+    if cursor.column > 0 {
+        cursor.column -= 1;
+    } else if cursor.row > 0 {
+        cursor.row -= 1;
+        cursor.column = COLUMNS - 1;
+    }
+    vm.draw_char(' ' as i16)?;
+    Ok(0)
+}
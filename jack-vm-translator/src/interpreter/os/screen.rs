@@ -0,0 +1,131 @@
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap, KBD, SCREEN};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("clearScreen", 0, clear_screen),
+    ("setColor", 1, set_color),
+    ("drawPixel", 2, draw_pixel),
+    ("drawLine", 4, draw_line),
+    ("drawRectangle", 4, draw_rectangle),
+    ("drawCircle", 3, draw_circle),
+];
+
+const WIDTH: i16 = 512;
+const HEIGHT: i16 = 256;
+
+pub(super) struct Color {
+    black: bool,
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self { black: true }
+    }
+}
+
+fn on_screen(x: i16, y: i16) -> bool {
+    (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y)
+}
+
+impl Interpreter {
+    fn set_pixel(&mut self, x: i16, y: i16) -> Result<(), Trap> {
+        let addr = SCREEN as i32 + y as i32 * 32 + x as i32 / 16;
+        let bit = 1i16 << (x % 16);
+        let word = self.read(addr)?;
+        let word = if self.os.color.black {
+            word | bit
+        } else {
+            word & !bit
+        };
+        self.write(addr, word)?;
+        Ok(())
+    }
+
+    fn set_row(&mut self, x1: i16, x2: i16, y: i16) -> Result<(), Trap> {
+        for x in x1..=x2 {
+            self.set_pixel(x, y)?;
+        }
+        Ok(())
+    }
+}
+
+fn init(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    vm.os.color = Color::default();
+    Ok(0)
+}
+
+fn clear_screen(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    for addr in SCREEN..KBD {
+        vm.write(addr as i32, 0)?;
+    }
+    Ok(0)
+}
+
+fn set_color(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.os.color.black = args[0] != 0;
+    Ok(0)
+}
+
+fn draw_pixel(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (x, y) = (args[0], args[1]);
+    if !on_screen(x, y) {
+        return vm.sys_error(SCREEN_DRAW_PIXEL_ILLEGAL_COORDS);
+    }
+    vm.set_pixel(x, y)?;
+    Ok(0)
+}
+
+fn draw_line(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (x1, y1, x2, y2) = (args[0], args[1], args[2], args[3]);
+    if !on_screen(x1, y1) || !on_screen(x2, y2) {
+        return vm.sys_error(SCREEN_DRAW_LINE_ILLEGAL_COORDS);
+    }
+    // Bresenham's line algorithm
+    let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+    let (sx, sy) = ((x2 - x1).signum(), (y2 - y1).signum());
+    let (mut x, mut y, mut err) = (x1, y1, dx + dy);
+    loop {
+        vm.set_pixel(x, y)?;
+        if x == x2 && y == y2 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+    Ok(0)
+}
+
+fn draw_rectangle(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (x1, y1, x2, y2) = (args[0], args[1], args[2], args[3]);
+    if x1 > x2 || y1 > y2 || !on_screen(x1, y1) || !on_screen(x2, y2) {
+        return vm.sys_error(SCREEN_DRAW_RECTANGLE_ILLEGAL_COORDS);
+    }
+    for y in y1..=y2 {
+        vm.set_row(x1, x2, y)?;
+    }
+    Ok(0)
+}
+
+fn draw_circle(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (x, y, r) = (args[0], args[1], args[2]);
+    if !on_screen(x, y) {
+        return vm.sys_error(SCREEN_DRAW_CIRCLE_ILLEGAL_CENTER);
+    }
+    if r < 0 || !on_screen(x - r, y - r) || !on_screen(x + r, y + r) {
+        return vm.sys_error(SCREEN_DRAW_CIRCLE_ILLEGAL_RADIUS);
+    }
+    for dy in -r..=r {
+        let dx = ((r as i32 * r as i32 - dy as i32 * dy as i32) as f64).sqrt() as i16;
+        vm.set_row(x - dx, x + dx, y + dy)?;
+    }
+    Ok(0)
+}
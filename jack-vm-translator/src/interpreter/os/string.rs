@@ -0,0 +1,128 @@
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("new", 1, new),
+    ("dispose", 1, dispose),
+    ("length", 1, length),
+    ("charAt", 2, char_at),
+    ("setCharAt", 3, set_char_at),
+    ("appendChar", 2, append_char),
+    ("eraseLastChar", 1, erase_last_char),
+    ("intValue", 1, int_value),
+    ("setInt", 2, set_int),
+    ("backSpace", 0, back_space),
+    ("doubleQuote", 0, double_quote),
+    ("newLine", 0, new_line),
+];
+
+// Layout of a string object: [max length, length, chars...]
+const MAX_LENGTH: i32 = 0;
+const LENGTH: i32 = 1;
+const CHARS: i32 = 2;
+
+fn new(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let max_length = args[0];
+    if max_length < 0 {
+        return vm.sys_error(STRING_NEW_NEGATIVE_LENGTH);
+    }
+    let this = vm.invoke("Memory.alloc", &[max_length.saturating_add(CHARS as i16)])?;
+    vm.write(this as i32 + MAX_LENGTH, max_length)?;
+    vm.write(this as i32 + LENGTH, 0)?;
+    Ok(this)
+}
+
+fn dispose(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.invoke("Memory.deAlloc", args)?;
+    Ok(0)
+}
+
+fn length(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    Ok(vm.read(args[0] as i32 + LENGTH)?)
+}
+
+fn char_at(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (this, j) = (args[0] as i32, args[1]);
+    if j < 0 || j >= vm.read(this + LENGTH)? {
+        return vm.sys_error(STRING_CHAR_AT_OUT_OF_BOUNDS);
+    }
+    Ok(vm.read(this + CHARS + j as i32)?)
+}
+
+fn set_char_at(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (this, j, c) = (args[0] as i32, args[1], args[2]);
+    if j < 0 || j >= vm.read(this + LENGTH)? {
+        return vm.sys_error(STRING_SET_CHAR_AT_OUT_OF_BOUNDS);
+    }
+    vm.write(this + CHARS + j as i32, c)?;
+    Ok(0)
+}
+
+fn append_char(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (this, c) = (args[0], args[1]);
+    let length = vm.read(this as i32 + LENGTH)?;
+    if length >= vm.read(this as i32 + MAX_LENGTH)? {
+        return vm.sys_error(STRING_APPEND_CHAR_FULL);
+    }
+    vm.write(this as i32 + CHARS + length as i32, c)?;
+    vm.write(this as i32 + LENGTH, length + 1)?;
+    Ok(this)
+}
+
+fn erase_last_char(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let this = args[0] as i32;
+    let length = vm.read(this + LENGTH)?;
+    if length == 0 {
+        return vm.sys_error(STRING_ERASE_LAST_CHAR_EMPTY);
+    }
+    vm.write(this + LENGTH, length - 1)?;
+    Ok(0)
+}
+
+fn int_value(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let this = args[0] as i32;
+    let length = vm.read(this + LENGTH)? as i32;
+    let mut value: i16 = 0;
+    let mut negative = false;
+    for i in 0..length {
+        let c = vm.read(this + CHARS + i)?;
+        if i == 0 && c == '-' as i16 {
+            negative = true;
+        } else if (0x30..=0x39).contains(&c) {
+            value = value.wrapping_mul(10).wrapping_add(c - 0x30);
+        } else {
+            break;
+        }
+    }
+    Ok(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+fn set_int(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    let (this, value) = (args[0] as i32, args[1]);
+    let digits = value.to_string();
+    if digits.len() as i16 > vm.read(this + MAX_LENGTH)? {
+        return vm.sys_error(STRING_SET_INT_INSUFFICIENT_CAPACITY);
+    }
+    for (i, c) in digits.bytes().enumerate() {
+        vm.write(this + CHARS + i as i32, c as i16)?;
+    }
+    vm.write(this + LENGTH, digits.len() as i16)?;
+    Ok(0)
+}
+
+fn back_space(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(129)
+}
+
+fn double_quote(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(34)
+}
+
+fn new_line(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Ok(128)
+}
@@ -0,0 +1,39 @@
+use super::error_code::*;
+use super::NativeFn;
+use crate::interpreter::{Interpreter, Trap};
+
+pub(super) static FUNCTIONS: &[(&str, u16, NativeFn)] = &[
+    ("init", 0, init),
+    ("halt", 0, halt),
+    ("error", 1, error),
+    ("wait", 1, wait),
+];
+
+fn init(vm: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    for class in ["Memory", "Math", "Screen", "Output", "Keyboard"] {
+        vm.invoke(&format!("{class}.init"), &[])?;
+    }
+    vm.invoke("Main.main", &[])?;
+    Err(Trap::Halt)
+}
+
+fn halt(_: &mut Interpreter, _: &[i16]) -> Result<i16, Trap> {
+    Err(Trap::Halt)
+}
+
+fn error(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    vm.error_code = Some(args[0]);
+    for c in "ERR".bytes() {
+        vm.invoke("Output.printChar", &[c as i16])?;
+    }
+    vm.invoke("Output.printInt", args)?;
+    Err(Trap::Halt)
+}
+
+/// The interpreter doesn't model time, so waiting returns immediately.
+fn wait(vm: &mut Interpreter, args: &[i16]) -> Result<i16, Trap> {
+    if args[0] < 0 {
+        return vm.sys_error(SYS_WAIT_DURATION_NEGATIVE);
+    }
+    Ok(0)
+}
@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+
+use super::os::{self, Native};
+use crate::errors::RuntimeError;
+use crate::parser::ParsedSource;
+use jack_vm_ir::Command;
+use jack_vm_ir::Segment;
+
+/// The first RAM address handed out to static variables.
+const STATIC_BASE: usize = 16;
+
+pub(super) struct Instruction {
+    pub command: Command,
+    /// Resolved operand of the command:
+    /// the jump target of `goto`/`if-goto`, the callee of `call`
+    /// and the RAM address of `push/pop static`.
+    pub operand: usize,
+}
+
+pub(super) enum Callee {
+    Function { pc: usize },
+    Native(Native),
+    Undefined(String),
+}
+
+/// All the loaded VM files, flattened into a single instruction list
+/// with labels, functions and statics resolved ahead of time.
+pub(super) struct Program {
+    pub instructions: Vec<Instruction>,
+    callees: Vec<Callee>,
+    callee_indices: HashMap<String, usize>,
+}
+
+impl Program {
+    pub fn load(sources: &[ParsedSource]) -> Result<Self, RuntimeError> {
+        let mut functions = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut pc = 0;
+        for source in sources {
+            let mut scope: Option<&str> = None;
+            for command in &source.commands {
+                match command {
+                    Command::Function { name, .. } => {
+                        if functions.insert(name.as_str(), pc).is_some() {
+                            return Err(RuntimeError::DuplicateFunction(name.to_string()));
+                        }
+                        scope = Some(name);
+                    }
+                    Command::Label(label) => {
+                        labels.insert((scope, label.as_str()), pc);
+                    }
+                    _ => {}
+                }
+                pc += 1;
+            }
+        }
+
+        // Native functions are only provided for classes the program doesn't define itself.
+        let user_classes: HashSet<&str> = functions.keys().map(|name| class_of(name)).collect();
+        let mut program = Self {
+            instructions: Vec::with_capacity(pc),
+            callees: vec![],
+            callee_indices: HashMap::new(),
+        };
+        for (name, pc) in &functions {
+            program.add_callee(name, Callee::Function { pc: *pc });
+        }
+        for (name, native) in os::natives() {
+            if !user_classes.contains(class_of(&name)) {
+                program.add_callee(&name, Callee::Native(native));
+            }
+        }
+
+        let mut statics = HashMap::new();
+        for (file, source) in sources.iter().enumerate() {
+            let mut scope: Option<&str> = None;
+            for command in &source.commands {
+                let operand = match command {
+                    Command::Function { name, .. } => {
+                        scope = Some(name);
+                        0
+                    }
+                    Command::GoTo(label) | Command::IfGoTo(label) => *labels
+                        .get(&(scope, label.as_str()))
+                        .ok_or_else(|| RuntimeError::UndefinedLabel {
+                            function: scope.unwrap_or(&source.name).to_string(),
+                            label: label.to_string(),
+                        })?,
+                    Command::Call { name, n_args } => {
+                        let index = program.callee_index(name).unwrap_or_else(|| {
+                            program.add_callee(name, Callee::Undefined(name.to_string()))
+                        });
+                        if let Callee::Native(native) = program.callee(index) {
+                            if native.arity != *n_args {
+                                return Err(RuntimeError::ArgumentCountMismatch {
+                                    name: name.to_string(),
+                                    expected: native.arity,
+                                    found: *n_args,
+                                });
+                            }
+                        }
+                        index
+                    }
+                    Command::Push {
+                        segment: Segment::Static,
+                        i,
+                    }
+                    | Command::Pop {
+                        segment: Segment::Static,
+                        i,
+                    } => {
+                        // Statics are numbered by first appearance, just like the
+                        // assembler allocates variables for the translated code.
+                        let next = STATIC_BASE + statics.len();
+                        *statics.entry((file, *i)).or_insert(next)
+                    }
+                    Command::Push {
+                        segment: Segment::Temp,
+                        i: 8..,
+                    }
+                    | Command::Pop {
+                        segment: Segment::Temp,
+                        i: 8..,
+                    } => {
+                        return Err(RuntimeError::IllegalCommand(
+                            "Temp segment overflow!".to_string(),
+                        ))
+                    }
+                    Command::Push {
+                        segment: Segment::Pointer,
+                        i: 2..,
+                    }
+                    | Command::Pop {
+                        segment: Segment::Pointer,
+                        i: 2..,
+                    } => {
+                        return Err(RuntimeError::IllegalCommand(
+                            "Pointer segment overflow!".to_string(),
+                        ))
+                    }
+                    Command::Pop {
+                        segment: Segment::Constant,
+                        i,
+                    } => return Err(RuntimeError::IllegalCommand(format!("pop constant {i}"))),
+                    _ => 0,
+                };
+                program.instructions.push(Instruction {
+                    command: command.clone(),
+                    operand,
+                });
+            }
+        }
+        Ok(program)
+    }
+
+    fn add_callee(&mut self, name: &str, callee: Callee) -> usize {
+        let index = self.callees.len();
+        self.callees.push(callee);
+        self.callee_indices.insert(name.to_string(), index);
+        index
+    }
+
+    pub fn callee_index(&self, name: &str) -> Option<usize> {
+        self.callee_indices.get(name).copied()
+    }
+
+    pub fn callee(&self, index: usize) -> &Callee {
+        &self.callees[index]
+    }
+
+    /// Whether `name` is defined by the program itself, rather than natively.
+    pub fn defines(&self, name: &str) -> bool {
+        self.callee_index(name)
+            .is_some_and(|index| matches!(self.callee(index), Callee::Function { .. }))
+    }
+}
+
+fn class_of(function: &str) -> &str {
+    function
+        .split_once('.')
+        .map_or(function, |(class, _)| class)
+}
@@ -0,0 +1,376 @@
+#![cfg(test)]
+
+use std::fs;
+
+use super::*;
+use crate::test_util::parse;
+use crate::{parser::Parser, source::Source};
+
+fn run(files: &[(&str, &str)]) -> Interpreter {
+    let mut interpreter = Interpreter::new(&parse(files)).unwrap();
+    interpreter.run(Some(1_000_000)).unwrap();
+    assert!(interpreter.is_halted());
+    interpreter
+}
+
+fn project(dir: &str) -> Vec<ParsedSource> {
+    let dir = format!("{}/../projects/{dir}", env!("CARGO_MANIFEST_DIR"));
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "vm"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            Parser::parse(Source {
+                name: path.file_stem().unwrap().to_string_lossy().to_string(),
+                content: fs::read_to_string(path).unwrap(),
+            })
+            .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn arithmetic_and_segments() {
+    let vm = run(&[(
+        "Test",
+        r"
+push constant 7
+push constant 8
+add
+pop temp 2
+push constant 32767
+push constant 1
+add
+push constant 3
+push constant 5
+lt
+push constant 5
+push constant 3
+gt
+push constant 4
+push constant 4
+eq
+and
+and
+push constant 1
+neg
+pop static 0
+push static 0
+not
+",
+    )]);
+    assert_eq!(vm.peek(TEMP + 2), 15);
+    assert_eq!(vm.peek(SP), 259);
+    assert_eq!(vm.peek(256), i16::MIN);
+    assert_eq!(vm.peek(257), -1);
+    assert_eq!(vm.peek(258), 0);
+    assert_eq!(vm.peek(16), -1);
+}
+
+#[test]
+fn recursive_calls() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Sys.init 0
+push constant 10
+call Main.fibonacci 1
+pop static 0
+label END
+goto END
+function Main.fibonacci 0
+push argument 0
+push constant 2
+lt
+if-goto BASE
+push argument 0
+push constant 1
+sub
+call Main.fibonacci 1
+push argument 0
+push constant 2
+sub
+call Main.fibonacci 1
+add
+return
+label BASE
+push argument 0
+return
+",
+    )]);
+    assert_eq!(vm.peek(16), 55);
+    assert_eq!(vm.peek(SP), STACK_BASE);
+    assert_eq!(vm.error_code(), None);
+}
+
+#[test]
+fn native_math() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Main.main 0
+push constant 300
+push constant 200
+neg
+call Math.multiply 2
+pop static 0
+push constant 1000
+neg
+push constant 7
+call Math.divide 2
+pop static 1
+push constant 1000
+call Math.sqrt 1
+pop static 2
+push constant 3
+push constant 9
+call Math.max 2
+push constant 20
+neg
+call Math.abs 1
+call Math.min 2
+pop static 3
+push constant 0
+return
+",
+    )]);
+    assert_eq!(vm.peek(16), 300i16.wrapping_mul(-200));
+    assert_eq!(vm.peek(17), -142);
+    assert_eq!(vm.peek(18), 31);
+    assert_eq!(vm.peek(19), 9);
+    assert_eq!(vm.error_code(), None);
+}
+
+#[test]
+fn native_output() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Main.main 0
+push constant 3
+call String.new 1
+push constant 72
+call String.appendChar 2
+push constant 105
+call String.appendChar 2
+call Output.printString 1
+pop temp 0
+call Output.println 0
+pop temp 0
+push constant 1234
+neg
+call Output.printInt 1
+pop temp 0
+push constant 0
+return
+",
+    )]);
+    assert_eq!(vm.console(), "Hi\n-1234");
+    // the top rows of 'H' and 'i' share the first screen word
+    assert_eq!(vm.peek(SCREEN), 51 | 12 << 8);
+}
+
+#[test]
+fn error_code_through_sys_error() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Main.main 0
+push constant 1
+push constant 0
+call Math.divide 2
+pop static 0
+push constant 1
+pop static 0
+push constant 0
+return
+",
+    )]);
+    assert_eq!(vm.error_code(), Some(3));
+    assert_eq!(vm.console(), "ERR3");
+    assert_eq!(vm.peek(16), 0);
+}
+
+#[test]
+fn string_errors() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Main.main 0
+push constant 1
+call String.new 1
+push constant 65
+call String.appendChar 2
+push constant 66
+call String.appendChar 2
+pop temp 0
+push constant 0
+return
+",
+    )]);
+    assert_eq!(vm.error_code(), Some(17));
+}
+
+#[test]
+fn keyboard_input() {
+    let mut vm = Interpreter::new(&parse(&[(
+        "Main",
+        r"
+function Main.main 0
+push constant 1
+call String.new 1
+push constant 63
+call String.appendChar 2
+call Keyboard.readInt 1
+pop static 0
+push constant 0
+return
+",
+    )]))
+    .unwrap();
+    vm.set_keyboard("-1x".bytes().map(|b| b as i16).chain([129, 50, 128]));
+    vm.run(None).unwrap();
+    assert_eq!(vm.peek(16), -12);
+    assert_eq!(vm.console(), "?-12\n");
+}
+
+#[test]
+fn heap_is_reused() {
+    let vm = run(&[(
+        "Main",
+        r"
+function Main.main 1
+push constant 10
+call Array.new 1
+pop local 0
+push local 0
+call Array.dispose 1
+pop temp 0
+push constant 4
+call Memory.alloc 1
+pop static 0
+push constant 4
+call Memory.alloc 1
+pop static 1
+push local 0
+pop static 2
+push constant 0
+return
+",
+    )]);
+    assert_eq!(vm.peek(16), 2048);
+    assert_eq!(vm.peek(17), 2052);
+    assert_eq!(vm.peek(18), 2048);
+}
+
+#[test]
+fn user_classes_take_precedence() {
+    let sources = [
+        (
+            "Main",
+            r"
+function Main.main 0
+push constant 6
+push constant 7
+call Math.multiply 2
+pop static 0
+push constant 0
+return
+",
+        ),
+        (
+            "Math",
+            r"
+function Math.init 0
+push constant 0
+return
+function Math.multiply 0
+push constant 1
+return
+",
+        ),
+    ];
+    let vm = run(&sources);
+    assert_eq!(vm.peek(16), 1);
+
+    // natives are replaced class by class
+    let mut vm = Interpreter::new(&parse(&[
+        sources[0],
+        ("Math", "function Math.init 0\npush constant 0\nreturn"),
+    ]))
+    .unwrap();
+    assert!(matches!(
+        vm.run(None),
+        Err(RuntimeError::UndefinedFunction(name)) if name == "Math.multiply"
+    ));
+}
+
+#[test]
+fn natives_call_user_classes() {
+    // a bump allocator used by the native String class
+    let vm = run(&[
+        (
+            "Main",
+            r"
+function Main.main 0
+push constant 5
+call String.new 1
+pop static 0
+push constant 0
+return
+",
+        ),
+        (
+            "Memory",
+            r"
+function Memory.init 0
+push constant 5000
+pop static 0
+push constant 0
+return
+function Memory.alloc 0
+push static 0
+push static 0
+push argument 0
+add
+pop static 0
+return
+",
+        ),
+    ]);
+    assert_eq!(vm.peek(16), 5000);
+    assert_eq!(vm.peek(5000), 5);
+}
+
+#[test]
+fn argument_count_of_natives_is_checked() {
+    let result = Interpreter::new(&parse(&[(
+        "Main",
+        "function Main.main 0\ncall Math.abs 2\nreturn",
+    )]));
+    assert!(matches!(
+        result,
+        Err(RuntimeError::ArgumentCountMismatch {
+            expected: 1,
+            found: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn memory_test() {
+    // projects/12/MemoryTest, with the supplied Memory.vm and with the native one
+    let sources = project("12/MemoryTest");
+    for sources in [&sources[..], &sources[..1]] {
+        let mut vm = Interpreter::new(sources).unwrap();
+        vm.run(Some(1_000_000)).unwrap();
+        assert!(vm.is_halted());
+        let results: Vec<_> = (8000..8006).map(|addr| vm.peek(addr)).collect();
+        assert_eq!(results, [333, 334, 222, 122, 100, 10]);
+    }
+}
@@ -1,105 +1,557 @@
-#![feature(is_some_with)]
+mod analysis;
+mod cached;
+mod call_graph;
 mod command;
+mod cpu;
+mod differential;
 mod errors;
+mod extensions;
+mod interpreter;
+mod optimizer;
+mod os;
 mod parser;
-mod segment;
 mod source;
+mod stack_depth;
+mod test_script;
+mod test_util;
 mod translation_state;
 mod translator;
+mod validator;
 
-use clap::Parser as CmdlineParser;
+use clap::{Parser as CmdlineParser, Subcommand};
 use colored::*;
 use std::{
+    collections::HashMap,
     error::Error,
     fmt::Debug,
     fs,
+    io::{self, Read},
     path::{Path, PathBuf},
+    process,
 };
 
-use parser::Parser;
+use analysis::Analysis;
+use differential::Lockstep;
+use errors::{Diagnostic, Diagnostics, Severity};
+use interpreter::Interpreter;
+use jack_vm_ir::FunctionName;
+use optimizer::Pass;
+use parser::{ParsedSource, Parser};
 use source::Source;
-use translator::Translator;
+use test_script::TestScript;
+use translator::{Bootstrap, Translator};
 
 #[derive(CmdlineParser, Debug)]
 #[clap(author="kxxt", version, about="Jack VM code translator for nand2tetris course", long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// input file
-    #[clap(value_parser)]
-    input: String,
+    #[clap(subcommand)]
+    command: Option<SubCommand>,
+
+    /// input files or folders, or glob patterns like `dir/*.vm`
+    #[clap(value_parser, required = true)]
+    input: Vec<String>,
 
     /// output file
     #[clap(short, long, value_parser)]
     output: Option<String>,
+
+    #[clap(flatten)]
+    optimization: OptimizationArgs,
+
+    #[clap(flatten)]
+    codegen: CodegenArgs,
+
+    #[clap(flatten)]
+    bootstrap: BootstrapArgs,
+
+    #[clap(flatten)]
+    os: OsArgs,
+
+    /// remove the functions that can't be reached from the entry function
+    #[clap(long, value_parser)]
+    remove_unused_functions: bool,
+
+    /// functions to keep even if they aren't called, like ones called indirectly
+    #[clap(
+        long,
+        value_parser,
+        value_delimiter = ',',
+        requires = "remove-unused-functions"
+    )]
+    keep: Vec<String>,
+
+    /// precede the code of each VM command with a comment naming it and where it is
+    #[clap(short, long, value_parser)]
+    debug: bool,
+
+    /// write where the code of each VM command is in the output, in JSON, to this file
+    #[clap(long, value_parser)]
+    source_map: Option<PathBuf>,
+
+    /// assemble the translated program, writing machine code instead of assembly
+    #[clap(long, value_parser)]
+    hack: bool,
+
+    /// also write the assembly next to the machine code
+    #[clap(long, value_parser, requires = "hack")]
+    keep_asm: bool,
 }
 
-fn handle_error(err: &Box<dyn Error>) {
-    println!("{}: {}", "Error".bright_red(), err);
-}
-
-fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let input = Path::new(&args.input);
-    let files = if input.is_dir() {
-        fs::read_dir(input)?
-            .filter_map(|s| {
-                s.ok().and_then(|entry| {
-                    let path = entry.path();
-                    if path.is_file() && path.extension().is_some_and(|ext| *ext == "vm") {
-                        Some(path)
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect()
-    } else {
-        vec![PathBuf::from(input)]
+#[derive(clap::Args, Debug)]
+struct OptimizationArgs {
+    /// optimize the VM code first
+    #[clap(short = 'O', long, value_parser)]
+    optimize: bool,
+
+    /// optimization passes to run, all of them by default
+    #[clap(long, value_enum, value_delimiter = ',', requires = "optimize")]
+    passes: Vec<Pass>,
+
+    /// inline the calls to non-recursive functions of at most this many commands
+    #[clap(long, value_parser, value_name = "MAX_COMMANDS")]
+    inline: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CodegenArgs {
+    /// share one copy of the code for calls and returns, making the program smaller but slower
+    #[clap(long, value_parser)]
+    trampolines: bool,
+
+    /// compare correctly when `x - y` overflows, at the cost of bigger code
+    #[clap(long, value_parser)]
+    safe_comparisons: bool,
+
+    /// keep the top of the stack in the D register, making straight-line code smaller and faster
+    #[clap(long, value_parser)]
+    cache_top: bool,
+
+    /// accept the extension commands mul, div, shl and shr, translated without calls
+    /// (div by zero gives 32767, or -32767 for a negative dividend)
+    #[clap(long, value_parser)]
+    extensions: bool,
+}
+
+impl CodegenArgs {
+    fn options(&self) -> translator::Options {
+        translator::Options {
+            trampolines: self.trampolines,
+            safe_comparisons: self.safe_comparisons,
+            cache_top: self.cache_top,
+            extensions: self.extensions,
+            ..translator::Options::default()
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct BootstrapArgs {
+    /// start by calling the entry function, by default only if the program defines it
+    #[clap(long, value_parser, conflicts_with = "no-bootstrap")]
+    bootstrap: bool,
+
+    /// start from the first command instead, like the tests of project 7
+    #[clap(long, value_parser)]
+    no_bootstrap: bool,
+
+    /// function the bootstrap code calls, Sys.init by default
+    #[clap(long, value_parser)]
+    entry: Option<FunctionName>,
+
+    /// initial stack pointer of the bootstrap code
+    #[clap(long, value_parser, default_value_t = 256)]
+    sp: u16,
+}
+
+#[derive(clap::Args, Debug)]
+struct OsArgs {
+    /// directory of the OS classes to link, the standard Jack OS by default
+    #[clap(long, value_parser)]
+    os: Option<PathBuf>,
+
+    /// don't link OS classes
+    #[clap(long, value_parser, conflicts_with = "os")]
+    no_os: bool,
+}
+
+impl OsArgs {
+    /// Links the OS classes the program needs but doesn't define.
+    fn link(
+        &self,
+        sources: &mut Vec<ParsedSource>,
+        entry: &FunctionName,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.no_os {
+            os::link(sources, os::load(self.os.as_deref())?, entry);
+        }
+        Ok(())
+    }
+}
+
+impl BootstrapArgs {
+    fn entry(&self) -> FunctionName {
+        self.entry
+            .clone()
+            .unwrap_or_else(|| Bootstrap::default().entry)
+    }
+
+    /// Whether the input is meant to be a whole program, rather than some of its files
+    /// like the tests of project 8 are translated one at a time.
+    fn whole_program(&self) -> bool {
+        self.bootstrap || self.entry.is_some()
+    }
+
+    /// The bootstrap of the linked program, failing if it was asked for
+    /// with `--bootstrap` or `--entry` but the program doesn't define the entry function.
+    fn resolve(&self, sources: &[ParsedSource]) -> Result<Option<Bootstrap>, Box<dyn Error>> {
+        let bootstrap = Bootstrap {
+            entry: self.entry(),
+            sp: self.sp,
+        };
+        if self.no_bootstrap {
+            return Ok(None);
+        }
+        if !bootstrap.is_defined_in(sources) {
+            if self.bootstrap || self.entry.is_some() {
+                return Err(format!("Entry function {} is not defined", bootstrap.entry).into());
+            }
+            return Ok(None);
+        }
+        Ok(Some(bootstrap))
+    }
+}
+
+impl OptimizationArgs {
+    fn apply(&self, sources: &mut [ParsedSource]) {
+        if let Some(threshold) = self.inline {
+            optimizer::inline(sources, threshold);
+        }
+        if !self.optimize {
+            return;
+        }
+        let passes = if self.passes.is_empty() {
+            &Pass::ALL[..]
+        } else {
+            &self.passes
+        };
+        optimizer::optimize(sources, passes);
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum SubCommand {
+    /// Run VM code with the interpreter.
+    /// OS classes missing from the program are provided natively.
+    Run(RunArgs),
+    /// Run test scripts for the VM emulator, like `StackTestVME.tst`,
+    /// and compare their output with the compare files.
+    Test(TestArgs),
+    /// Run VM code on the interpreter and, translated and assembled, on the CPU emulator,
+    /// and check that both agree on the RAM after every command.
+    Diff(DiffArgs),
+    /// Print the size, stack depth and callers of every function and the recursion between them,
+    /// and export the call graph.
+    Analyze(AnalyzeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct AnalyzeArgs {
+    /// input files or folders, or glob patterns like `dir/*.vm`
+    #[clap(value_parser, required = true)]
+    input: Vec<String>,
+
+    /// write the call graph to this file in the Graphviz DOT language
+    #[clap(long, value_parser)]
+    dot: Option<PathBuf>,
+
+    /// write the call graph and the metrics to this file as JSON
+    #[clap(long, value_parser)]
+    json: Option<PathBuf>,
+
+    #[clap(flatten)]
+    optimization: OptimizationArgs,
+
+    #[clap(flatten)]
+    codegen: CodegenArgs,
+
+    #[clap(flatten)]
+    os: OsArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct TestArgs {
+    /// test scripts
+    #[clap(value_parser, required = true)]
+    scripts: Vec<String>,
+
+    /// run the scripts on both the interpreter and the CPU emulator, like `diff` does
+    #[clap(long, value_parser)]
+    differential: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    #[clap(flatten)]
+    run: RunArgs,
+
+    #[clap(flatten)]
+    codegen: CodegenArgs,
+
+    #[clap(flatten)]
+    bootstrap: BootstrapArgs,
+
+    #[clap(flatten)]
+    os: OsArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// input files or folders, or glob patterns like `dir/*.vm`
+    #[clap(value_parser, required = true)]
+    input: Vec<String>,
+
+    /// stop after executing this many commands
+    #[clap(long, value_parser)]
+    max_steps: Option<u64>,
+
+    #[clap(flatten)]
+    optimization: OptimizationArgs,
+}
+
+fn handle_error(err: &dyn Error) {
+    eprintln!("{}: {}", "Error".bright_red(), err);
+}
+
+/// The contents of the files of a program, to show the lines diagnostics are about.
+struct Files(HashMap<String, String>);
+
+impl Files {
+    /// Prints `diagnostics`, failing if any of them is an error.
+    fn report(&self, diagnostics: &[Diagnostic]) -> Result<(), Box<dyn Error>> {
+        for diagnostic in diagnostics {
+            let content = self.0.get(&diagnostic.file).map(String::as_str);
+            eprintln!("{}", diagnostic.render(content));
+        }
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            return Err(format!("Aborting due to {errors} error(s)").into());
+        }
+        Ok(())
+    }
+
+    /// Prints the diagnostics of `result`, failing if any of them is an error,
+    /// or if warnings came without a value.
+    fn check<T>(&self, result: Result<T, Diagnostics>) -> Result<T, Box<dyn Error>> {
+        let (value, diagnostics) = match result {
+            Ok(value) => (Some(value), vec![]),
+            Err(Diagnostics(diagnostics)) => (None, diagnostics),
+        };
+        self.report(&diagnostics)?;
+        value.ok_or_else(|| "Stopped with only warnings".into())
+    }
+}
+
+/// Reports the problems of the program that can't be found line by line.
+fn validate(
+    sources: &[ParsedSource],
+    whole_program: bool,
+    files: &Files,
+) -> Result<(), Box<dyn Error>> {
+    let mut diagnostics = validator::validate(sources, whole_program);
+    diagnostics.extend(stack_depth::check(sources));
+    files.report(&diagnostics)
+}
+
+/// Reads and parses the program, reporting the errors of all files.
+fn load(inputs: &[String]) -> Result<(Vec<ParsedSource>, Files), Box<dyn Error>> {
+    let sources = Source::read_all(inputs)?;
+    let files = Files(
+        sources
+            .iter()
+            .map(|source| (source.name.clone(), source.content.clone()))
+            .collect(),
+    );
+    let mut parsed_sources = vec![];
+    let mut errors = vec![];
+    for source in sources {
+        match Parser::parse(source) {
+            Ok(parsed) => parsed_sources.push(parsed),
+            Err(diagnostics) => errors.extend(diagnostics.0),
+        }
+    }
+    files.report(&errors)?;
+    Ok((parsed_sources, files))
+}
+
+fn translate(args: Args) -> Result<(), Box<dyn Error>> {
+    let (mut parsed_sources, files) = load(&args.input)?;
+    args.os.link(&mut parsed_sources, &args.bootstrap.entry())?;
+    validate(&parsed_sources, args.bootstrap.whole_program(), &files)?;
+    args.optimization.apply(&mut parsed_sources);
+    let bootstrap = args.bootstrap.resolve(&parsed_sources)?;
+    if args.remove_unused_functions {
+        let keep: Vec<_> = args.keep.iter().map(|name| name.as_str().into()).collect();
+        let elimination = call_graph::eliminate_dead_functions(
+            &mut parsed_sources,
+            &args.bootstrap.entry(),
+            &keep,
+            args.codegen.options(),
+        )?;
+        println!(
+            "Removed {} unused function(s), {} ROM words",
+            elimination.functions.len(),
+            elimination.rom_words
+        );
+    }
+    let options = translator::Options {
+        debug: args.debug,
+        ..args.codegen.options()
     };
-    if files.is_empty() {
-        return Err(format!(
-            "No source code found in directory {}!",
-            input.to_string_lossy()
-        )
-        .into());
-    }
-    let sources = files.iter().map(|file| Source {
-        content: fs::read_to_string(&file)
-            .expect(&format!("Error reading {}!", file.to_string_lossy())),
-        name: Path::new(file)
-            .file_stem()
-            .unwrap()
-            .to_string_lossy()
-            .to_string(),
-    });
-    let parsed_sources = sources
-        .map(|source| Parser::parse(source))
-        .collect::<Result<Vec<_>, _>>()?;
-    let asms = parsed_sources
-        .iter()
-        .map(|parsed| Translator::translate(parsed))
-        .collect::<Result<Vec<_>, _>>()?;
+    let (asm, source_map) = files.check(Translator::translate_program(
+        &parsed_sources,
+        bootstrap.as_ref(),
+        options,
+    ))?;
+    if let Some(path) = args.source_map {
+        fs::write(path, serde_json::to_string_pretty(&source_map)?)?;
+    }
+    let extension = if args.hack { "hack" } else { "asm" };
     let output_path: PathBuf = if let Some(output_path) = args.output {
         output_path.into()
     } else {
+        let [input] = &args.input[..] else {
+            return Err(
+                "Name the output file with --output when translating several inputs".into(),
+            );
+        };
+        let input = Path::new(input);
         if input.is_file() {
             let mut p = PathBuf::from(input);
-            p.set_extension("asm");
+            p.set_extension(extension);
             p
         } else {
-            input.join(Path::new(input.file_name().unwrap()).with_extension("asm"))
+            input.join(Path::new(input.file_name().unwrap()).with_extension(extension))
         }
     };
-    let bootstrap = if input.is_dir() {
-        Translator::BOOTSTRAP
+    if args.hack {
+        let hack = files.check(Translator::assemble(&asm, &source_map))?;
+        fs::write(&output_path, hack)?;
+        if args.keep_asm {
+            fs::write(output_path.with_extension("asm"), asm)?;
+        }
     } else {
-        ""
-    };
-    fs::write(output_path, format!("{bootstrap}{}", asms.join("\n")))?;
+        fs::write(output_path, asm)?;
+    }
+    Ok(())
+}
+
+fn interpret(args: RunArgs) -> Result<(), Box<dyn Error>> {
+    let (mut parsed_sources, _) = load(&args.input)?;
+    args.optimization.apply(&mut parsed_sources);
+    let mut interpreter = Interpreter::new(&parsed_sources)?;
+    interpreter.set_echo(io::stdout());
+    interpreter.set_keyboard(
+        io::BufReader::new(io::stdin())
+            .bytes()
+            .map_while(Result::ok)
+            .map(|key| match key {
+                b'\n' => 128,
+                8 | 127 => 129,
+                key => key as i16,
+            }),
+    );
+    interpreter.run(args.max_steps)?;
+    println!();
+    if let Some(code) = interpreter.error_code() {
+        return Err(format!("Program terminated with error code {code}").into());
+    }
+    if !interpreter.is_halted() {
+        println!("Stopped after {} steps", interpreter.steps());
+    }
+    Ok(())
+}
+
+fn test(args: TestArgs) -> Result<(), Box<dyn Error>> {
+    let mut failures = 0;
+    for script in &args.scripts {
+        let path = Path::new(script);
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let result = TestScript::parse(&fs::read_to_string(path)?).and_then(|test| {
+            if args.differential {
+                test.run::<Lockstep>(dir)
+            } else {
+                test.run::<Interpreter>(dir)
+            }
+        });
+        match result {
+            Ok(_) => println!("{} {script}", "PASS".bright_green()),
+            Err(err) => {
+                failures += 1;
+                println!("{} {script}: {err}", "FAIL".bright_red());
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!("{failures} of {} test(s) failed", args.scripts.len()).into());
+    }
+    Ok(())
+}
+
+fn diff(
+    DiffArgs {
+        run: args,
+        codegen,
+        bootstrap,
+        os,
+    }: DiffArgs,
+) -> Result<(), Box<dyn Error>> {
+    let (mut parsed_sources, files) = load(&args.input)?;
+    os.link(&mut parsed_sources, &bootstrap.entry())?;
+    validate(&parsed_sources, bootstrap.whole_program(), &files)?;
+    args.optimization.apply(&mut parsed_sources);
+    let bootstrap = bootstrap.resolve(&parsed_sources)?;
+    let mut lockstep =
+        Lockstep::with_options(&parsed_sources, bootstrap.as_ref(), codegen.options())?;
+    lockstep.run(args.max_steps)?;
+    println!(
+        "The CPU agrees with the interpreter after {} steps",
+        lockstep.interpreter().steps()
+    );
+    Ok(())
+}
+
+fn analyze(args: AnalyzeArgs) -> Result<(), Box<dyn Error>> {
+    let (mut parsed_sources, files) = load(&args.input)?;
+    args.os
+        .link(&mut parsed_sources, &Bootstrap::default().entry)?;
+    validate(&parsed_sources, false, &files)?;
+    args.optimization.apply(&mut parsed_sources);
+    let analysis = Analysis::new(&parsed_sources, args.codegen.options())?;
+    println!("{analysis}");
+    if let Some(path) = args.dot {
+        fs::write(path, analysis.to_dot())?;
+    }
+    if let Some(path) = args.json {
+        fs::write(path, analysis.to_json())?;
+    }
     Ok(())
 }
 
 fn main() {
     let args = Args::parse();
-    if let Err(error) = run(args) {
-        handle_error(&error);
+    let result = match args.command {
+        Some(SubCommand::Run(args)) => interpret(args),
+        Some(SubCommand::Test(args)) => test(args),
+        Some(SubCommand::Diff(args)) => diff(args),
+        Some(SubCommand::Analyze(args)) => analyze(args),
+        None => translate(args),
+    };
+    if let Err(error) = result {
+        handle_error(error.as_ref());
+        process::exit(1);
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use jack_vm_ir::{Command, FunctionName, Label, Segment};
+
+use crate::call_graph::CallGraph;
+use crate::parser::ParsedSource;
+use crate::stack_depth;
+
+/// A function whose calls can be replaced with its code.
+struct Inlinable {
+    file: String,
+    n_vars: u16,
+    /// The commands after `function`.
+    body: Vec<Command>,
+    uses_statics: bool,
+    /// Number of arguments the function reads.
+    n_args: u16,
+    /// Whether it sets `pointer 0` and `pointer 1`, which a return would restore.
+    sets_pointer: [bool; 2],
+}
+
+/// The function being rewritten.
+struct Caller {
+    /// Where its `function` command is in the new commands.
+    index: usize,
+    n_vars: u16,
+    /// Locals its inlined calls need, besides its own.
+    extra: u16,
+}
+
+/// Replaces the calls to non-recursive functions of at most `threshold` commands with their code.
+/// The arguments and locals of the inlined function become extra locals of the caller.
+/// Returns the number of inlined calls.
+pub fn inline(sources: &mut [ParsedSource], threshold: usize) -> usize {
+    let functions = inlinable(sources, threshold);
+    let mut count = 0;
+    for source in sources.iter_mut() {
+        let mut commands = Vec::with_capacity(source.commands.len());
+        let mut lines = vec![];
+        let mut caller: Option<Caller> = None;
+        for (i, command) in source.commands.iter().enumerate() {
+            let line = source.lines.get(i).copied();
+            let function = match (command, &caller) {
+                (Command::Call { name, n_args }, Some(_)) => {
+                    functions.get(name).filter(|function| {
+                        function.n_args <= *n_args
+                            && (!function.uses_statics || function.file == source.name)
+                    })
+                }
+                _ => None,
+            };
+            match (command, function, caller.as_mut()) {
+                (Command::Call { n_args, .. }, Some(function), Some(caller)) => {
+                    let (code, locals) = expand(function, *n_args, caller.n_vars, count);
+                    caller.extra = caller.extra.max(locals);
+                    lines.extend(line.map(|line| vec![line; code.len()]).unwrap_or_default());
+                    commands.extend(code);
+                    count += 1;
+                }
+                _ => {
+                    if let Command::Function { n_vars, .. } = command {
+                        finish(&mut commands, caller.take());
+                        caller = Some(Caller {
+                            index: commands.len(),
+                            n_vars: *n_vars,
+                            extra: 0,
+                        });
+                    }
+                    lines.extend(line);
+                    commands.push(command.clone());
+                }
+            }
+        }
+        finish(&mut commands, caller);
+        source.commands = commands;
+        source.lines = lines;
+    }
+    count
+}
+
+/// Gives the caller the locals of the calls inlined into it.
+fn finish(commands: &mut [Command], caller: Option<Caller>) {
+    if let Some(Caller { index, extra, .. }) = caller {
+        if let Command::Function { n_vars, .. } = &mut commands[index] {
+            *n_vars += extra;
+        }
+    }
+}
+
+/// The functions of the program that can be inlined: not recursive,
+/// with at most `threshold` commands, and leaving only their result on the stack.
+fn inlinable(sources: &[ParsedSource], threshold: usize) -> HashMap<FunctionName, Inlinable> {
+    let graph = CallGraph::new(sources);
+    let recursive: Vec<_> = graph.cycles().into_iter().flatten().collect();
+    let mut functions = HashMap::new();
+    for source in sources {
+        let starts: HashMap<_, _> = source
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(i, command)| match command {
+                Command::Function { name, n_vars } => Some((name, (i, *n_vars))),
+                _ => None,
+            })
+            .collect();
+        for stack in stack_depth::analyze(source) {
+            if recursive.contains(&&stack.name) || !stack.issues.is_empty() {
+                continue;
+            }
+            let (start, n_vars) = starts[&stack.name];
+            let body = &source.commands[start + 1..start + stack.depths.len()];
+            let returns_result = body.iter().zip(&stack.depths[1..]).all(|(command, depth)| {
+                !matches!(command, Command::Return) || depth.is_none_or(|depth| depth == 1)
+            });
+            // the function doesn't continue into the next one
+            let ends = (!body.is_empty() && stack.depths.last().unwrap().is_none())
+                || matches!(body.last(), Some(Command::Return | Command::GoTo(_)));
+            if body.len() > threshold || !returns_result || !ends {
+                continue;
+            }
+            let segments = || {
+                body.iter().filter_map(|command| match command {
+                    Command::Push { segment, i } => Some((false, *segment, *i)),
+                    Command::Pop { segment, i } => Some((true, *segment, *i)),
+                    _ => None,
+                })
+            };
+            let sets_pointer = |i| segments().any(|access| access == (true, Segment::Pointer, i));
+            functions.insert(
+                stack.name,
+                Inlinable {
+                    file: source.name.clone(),
+                    n_vars,
+                    body: body.to_vec(),
+                    uses_statics: segments().any(|(_, segment, _)| segment == Segment::Static),
+                    n_args: segments()
+                        .filter(|(_, segment, _)| *segment == Segment::Argument)
+                        .map(|(_, _, i)| i + 1)
+                        .max()
+                        .unwrap_or(0),
+                    sets_pointer: [sets_pointer(0), sets_pointer(1)],
+                },
+            );
+        }
+    }
+    functions
+}
+
+/// The code replacing a call with `n_args` arguments to `function`, in a caller with `n_vars` locals,
+/// and the number of locals it adds to the caller.
+/// `site` makes the labels of the code unique.
+fn expand(function: &Inlinable, n_args: u16, n_vars: u16, site: usize) -> (Vec<Command>, u16) {
+    let argument = |i: u16| n_vars + i;
+    let local = |i: u16| n_vars + n_args + i;
+    let saved = |i: u16| n_vars + n_args + function.n_vars + i;
+    let push = |segment, i| Command::Push { segment, i };
+    let pop = |segment, i| Command::Pop { segment, i };
+    let label = |label: &Label| Label::from(format!("INLINE{site}.{label}"));
+    let end = Label::from(format!("INLINE{site}$END"));
+    let pointers: Vec<u16> = (0..2)
+        .filter(|&i| function.sets_pointer[i as usize])
+        .collect();
+
+    let mut code = vec![];
+    code.extend((0..n_args).rev().map(|i| pop(Segment::Local, argument(i))));
+    for i in 0..function.n_vars {
+        code.extend([push(Segment::Constant, 0), pop(Segment::Local, local(i))]);
+    }
+    for (j, &i) in pointers.iter().enumerate() {
+        code.extend([
+            push(Segment::Pointer, i),
+            pop(Segment::Local, saved(j as u16)),
+        ]);
+    }
+    let last = function.body.len() - 1;
+    let mut jumps_to_end = false;
+    for (index, command) in function.body.iter().enumerate() {
+        let remap = |segment: &Segment, i: &u16| match segment {
+            Segment::Argument => (Segment::Local, argument(*i)),
+            Segment::Local => (Segment::Local, local(*i)),
+            _ => (*segment, *i),
+        };
+        code.push(match command {
+            Command::Push { segment, i } => {
+                let (segment, i) = remap(segment, i);
+                push(segment, i)
+            }
+            Command::Pop { segment, i } => {
+                let (segment, i) = remap(segment, i);
+                pop(segment, i)
+            }
+            Command::Label(name) => Command::Label(label(name)),
+            Command::GoTo(name) => Command::GoTo(label(name)),
+            Command::IfGoTo(name) => Command::IfGoTo(label(name)),
+            Command::Return if index == last => continue,
+            Command::Return => {
+                jumps_to_end = true;
+                Command::GoTo(end.clone())
+            }
+            command => command.clone(),
+        });
+    }
+    if jumps_to_end {
+        code.push(Command::Label(end));
+    }
+    for (j, &i) in pointers.iter().enumerate() {
+        code.extend([
+            push(Segment::Local, saved(j as u16)),
+            pop(Segment::Pointer, i),
+        ]);
+    }
+    (code, n_args + function.n_vars + pointers.len() as u16)
+}
@@ -0,0 +1,235 @@
+mod inline;
+mod tests;
+
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+use jack_vm_ir::{Command, Label, Segment};
+
+use crate::extensions;
+use crate::parser::ParsedSource;
+
+pub use inline::inline;
+
+/// Optimization passes over VM commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Pass {
+    /// `push constant 2; push constant 3; add` becomes `push constant 5`.
+    ConstantFolding,
+    /// `push x; pop x` is removed.
+    PushPop,
+    /// `not; not` is removed.
+    DoubleNot,
+    /// Conditional jumps on constants become `goto`s or are removed.
+    ConstantBranches,
+    /// Commands that can't be reached after `goto` and `return`, and jumps to the next command, are removed.
+    DeadCode,
+    /// Labels no jump refers to are removed.
+    UnusedLabels,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 6] = [
+        Pass::ConstantFolding,
+        Pass::PushPop,
+        Pass::DoubleNot,
+        Pass::ConstantBranches,
+        Pass::DeadCode,
+        Pass::UnusedLabels,
+    ];
+
+    /// Runs the pass on the commands and their lines, returning whether anything changed.
+    fn run(&self, commands: &mut Vec<Command>, lines: &mut Vec<u32>) -> bool {
+        let before = commands.len();
+        match self {
+            Pass::ConstantFolding => rewrite(commands, lines, 3, fold_constants),
+            Pass::PushPop => rewrite(commands, lines, 2, |window| match window {
+                [Command::Push { segment, i }, Command::Pop {
+                    segment: pop_segment,
+                    i: pop_i,
+                }] if segment == pop_segment && i == pop_i => Some(vec![]),
+                _ => None,
+            }),
+            Pass::DoubleNot => rewrite(commands, lines, 2, |window| match window {
+                [Command::Not, Command::Not] => Some(vec![]),
+                _ => None,
+            }),
+            Pass::ConstantBranches => {
+                rewrite(commands, lines, 3, |window| match window {
+                    [Command::Push {
+                        segment: Segment::Constant,
+                        i: 0,
+                    }, Command::Not, Command::IfGoTo(label)] => {
+                        Some(vec![Command::GoTo(label.clone())])
+                    }
+                    _ => None,
+                });
+                rewrite(commands, lines, 2, |window| match window {
+                    [Command::Push {
+                        segment: Segment::Constant,
+                        i,
+                    }, Command::IfGoTo(label)] => Some(if *i == 0 {
+                        vec![]
+                    } else {
+                        vec![Command::GoTo(label.clone())]
+                    }),
+                    _ => None,
+                });
+            }
+            Pass::DeadCode => {
+                let keep = reachable(commands);
+                retain(commands, lines, &keep);
+                // jumps to the next command
+                rewrite(commands, lines, 2, |window| match window {
+                    [Command::GoTo(target), label @ Command::Label(name)] if target == name => {
+                        Some(vec![label.clone()])
+                    }
+                    _ => None,
+                });
+            }
+            Pass::UnusedLabels => {
+                let keep = used_labels(commands);
+                retain(commands, lines, &keep);
+            }
+        }
+        commands.len() != before
+    }
+}
+
+/// Runs `passes` on every source until none of them changes anything.
+pub fn optimize(sources: &mut [ParsedSource], passes: &[Pass]) {
+    for source in sources {
+        loop {
+            let mut changed = false;
+            for pass in passes {
+                changed |= pass.run(&mut source.commands, &mut source.lines);
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+/// Replaces windows of `size` commands for which `replace` returns a replacement.
+/// The replacement commands get the line of the first command of the window.
+fn rewrite(
+    commands: &mut Vec<Command>,
+    lines: &mut Vec<u32>,
+    size: usize,
+    replace: impl Fn(&[Command]) -> Option<Vec<Command>>,
+) {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut result_lines = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < commands.len() {
+        let line = lines.get(i).copied();
+        match commands.get(i..i + size).and_then(&replace) {
+            Some(replacement) => {
+                result_lines.extend(
+                    line.map(|line| vec![line; replacement.len()])
+                        .unwrap_or_default(),
+                );
+                result.extend(replacement);
+                i += size;
+            }
+            None => {
+                result_lines.extend(line);
+                result.push(commands[i].clone());
+                i += 1;
+            }
+        }
+    }
+    *commands = result;
+    *lines = result_lines;
+}
+
+/// Keeps the commands, along with their lines, for which `keep` is true.
+fn retain(commands: &mut Vec<Command>, lines: &mut Vec<u32>, keep: &[bool]) {
+    let mut flags = keep.iter();
+    commands.retain(|_| *flags.next().unwrap());
+    let mut flags = keep.iter();
+    lines.retain(|_| *flags.next().unwrap());
+}
+
+fn fold_constants(window: &[Command]) -> Option<Vec<Command>> {
+    let [Command::Push {
+        segment: Segment::Constant,
+        i: x,
+    }, Command::Push {
+        segment: Segment::Constant,
+        i: y,
+    }, op] = window
+    else {
+        return None;
+    };
+    let (x, y) = (*x as i16, *y as i16);
+    let value = match op {
+        Command::Add => x.wrapping_add(y),
+        Command::Sub => x.wrapping_sub(y),
+        Command::And => x & y,
+        Command::Or => x | y,
+        Command::Eq => -((x == y) as i16),
+        Command::Gt => -((x > y) as i16),
+        Command::Lt => -((x < y) as i16),
+        op => extensions::evaluate(op, x, y)?,
+    };
+    let push = |i: i16| Command::Push {
+        segment: Segment::Constant,
+        i: i as u16,
+    };
+    Some(match value {
+        0.. => vec![push(value)],
+        -1 => vec![push(0), Command::Not],
+        i16::MIN => return None,
+        _ => vec![push(-value), Command::Neg],
+    })
+}
+
+/// Whether each command can be reached: commands after `goto` and `return`
+/// can't be, up to the next label or function.
+fn reachable(commands: &[Command]) -> Vec<bool> {
+    let mut reachable = true;
+    commands
+        .iter()
+        .map(|command| {
+            if matches!(command, Command::Label(_) | Command::Function { .. }) {
+                reachable = true;
+            }
+            let keep = reachable;
+            if matches!(command, Command::GoTo(_) | Command::Return) {
+                reachable = false;
+            }
+            keep
+        })
+        .collect()
+}
+
+/// Whether each command is something else than a label that isn't the target
+/// of a jump in the same function.
+fn used_labels(commands: &[Command]) -> Vec<bool> {
+    // labels are scoped by function, or by file before the first function
+    let mut scope = 0;
+    let mut used = HashSet::<(usize, &Label)>::new();
+    for command in commands.iter() {
+        match command {
+            Command::Function { .. } => scope += 1,
+            Command::GoTo(label) | Command::IfGoTo(label) => {
+                used.insert((scope, label));
+            }
+            _ => {}
+        }
+    }
+    let mut scope = 0;
+    commands
+        .iter()
+        .map(|command| match command {
+            Command::Function { .. } => {
+                scope += 1;
+                true
+            }
+            Command::Label(label) => used.contains(&(scope, label)),
+            _ => true,
+        })
+        .collect()
+}
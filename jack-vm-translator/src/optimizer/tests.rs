@@ -0,0 +1,433 @@
+#![cfg(test)]
+
+use std::path::Path;
+
+use super::*;
+use crate::interpreter::{Interpreter, SCREEN};
+use crate::test_util;
+use crate::translator::{Options, Translator};
+use crate::{parser::Parser, source::Source};
+
+fn parse(content: &str) -> Vec<ParsedSource> {
+    vec![Parser::parse(Source {
+        name: "Main".to_string(),
+        content: content.to_string(),
+    })
+    .unwrap()]
+}
+
+fn load(dirs: &[&str]) -> Vec<ParsedSource> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut sources: Vec<_> = dirs
+        .iter()
+        .flat_map(|dir| Source::read(&root.join(dir)).unwrap())
+        .collect();
+    sources.sort_by(|a, b| a.name.cmp(&b.name));
+    sources
+        .into_iter()
+        .map(|source| Parser::parse(source).unwrap())
+        .collect()
+}
+
+/// Runs the program and returns the statics, the screen and the console.
+fn run(sources: &[ParsedSource], setup: &[(usize, i16)]) -> (Vec<i16>, Vec<i16>, String) {
+    let mut vm = Interpreter::new(sources).unwrap();
+    for (addr, value) in setup {
+        vm.poke(*addr, *value);
+    }
+    // `Sys.halt` of the official OS loops in a way that is only detected once optimized
+    vm.run(Some(2_000_000)).unwrap();
+    (
+        (16..256).map(|addr| vm.peek(addr)).collect(),
+        (SCREEN..SCREEN + 8192).map(|addr| vm.peek(addr)).collect(),
+        vm.console().to_string(),
+    )
+}
+
+/// Checks that the program behaves the same after each pass, and after all of them.
+/// Returns the number of commands saved by all passes.
+fn assert_equivalent(sources: &[ParsedSource], setup: &[(usize, i16)]) -> usize {
+    let expected = run(sources, setup);
+    let count = |sources: &[ParsedSource]| -> usize {
+        sources.iter().map(|source| source.commands.len()).sum()
+    };
+    for passes in Pass::ALL
+        .map(|pass| vec![pass])
+        .iter()
+        .chain([&Pass::ALL.to_vec()])
+    {
+        let mut optimized = sources.to_vec();
+        optimize(&mut optimized, passes);
+        assert_eq!(run(&optimized, setup), expected, "{passes:?}");
+        for source in &optimized {
+            assert_eq!(source.lines.len(), source.commands.len(), "{passes:?}");
+        }
+    }
+    let mut optimized = sources.to_vec();
+    optimize(&mut optimized, &Pass::ALL);
+    count(sources) - count(&optimized)
+}
+
+fn optimized(content: &str, passes: &[Pass]) -> Vec<Command> {
+    let mut sources = parse(content);
+    assert_equivalent(&sources, &[]);
+    optimize(&mut sources, passes);
+    sources.remove(0).commands
+}
+
+#[test]
+fn constant_folding() {
+    let code = optimized(
+        r"
+function Sys.init 0
+push constant 2
+push constant 3
+add
+push constant 10
+sub
+pop static 0
+push constant 7
+push constant 7
+eq
+pop static 1
+push constant 12
+push constant 10
+and
+push constant 1
+or
+pop static 2
+label END
+goto END
+",
+        &[Pass::ConstantFolding],
+    );
+    assert_eq!(
+        jack_vm_ir::to_vm_code(&code),
+        "function Sys.init 0
+push constant 5
+neg
+pop static 0
+push constant 0
+not
+pop static 1
+push constant 9
+pop static 2
+label END
+goto END
+"
+    );
+}
+
+#[test]
+fn optimized_commands_keep_their_lines() {
+    let mut sources = parse(
+        "function Main.main 0
+push constant 2
+push constant 3
+add
+pop static 0
+push constant 1
+if-goto END
+push constant 0
+return
+label END
+push constant 0
+return",
+    );
+    optimize(&mut sources, &Pass::ALL);
+    let (_, map) = Translator::translate_program(&sources, None, Options::default()).unwrap();
+    let lines: Vec<_> = map
+        .iter()
+        .map(|entry| (entry.line, entry.command.as_str()))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (Some(1), "function Main.main 0"),
+            (Some(2), "push constant 5"),
+            (Some(5), "pop static 0"),
+            (Some(11), "push constant 0"),
+            (Some(12), "return"),
+        ]
+    );
+}
+
+#[test]
+fn folding_keeps_overflow() {
+    let code = optimized(
+        r"
+function Sys.init 0
+push constant 32767
+push constant 1
+add
+pop static 0
+label END
+goto END
+",
+        &[Pass::ConstantFolding],
+    );
+    assert_eq!(code.len(), 7);
+}
+
+#[test]
+fn push_pop_and_double_not() {
+    let code = optimized(
+        r"
+function Sys.init 1
+push constant 7
+pop local 0
+push local 0
+pop local 0
+push local 0
+not
+not
+pop static 0
+push static 0
+pop static 1
+label END
+goto END
+",
+        &[Pass::PushPop, Pass::DoubleNot],
+    );
+    assert_eq!(
+        jack_vm_ir::to_vm_code(&code),
+        "function Sys.init 1
+push constant 7
+pop local 0
+push local 0
+pop static 0
+push static 0
+pop static 1
+label END
+goto END
+"
+    );
+}
+
+#[test]
+fn constant_branches_and_dead_code() {
+    // `while (true)` and `if (false)` as emitted by the compiler
+    let code = optimized(
+        r"
+function Sys.init 0
+push constant 0
+pop static 0
+label WHILE
+push constant 0
+not
+not
+if-goto WHILE_END
+push static 0
+push constant 1
+add
+pop static 0
+push constant 0
+if-goto SKIP
+push constant 100
+pop static 1
+label SKIP
+push static 0
+push constant 5
+eq
+not
+if-goto WHILE
+push constant 0
+not
+if-goto WHILE_END
+push constant 1
+pop static 2
+label WHILE_END
+label END
+goto END
+",
+        &Pass::ALL,
+    );
+    assert_eq!(
+        jack_vm_ir::to_vm_code(&code),
+        "function Sys.init 0
+push constant 0
+pop static 0
+label WHILE
+push static 0
+push constant 1
+add
+pop static 0
+push constant 100
+pop static 1
+push static 0
+push constant 5
+eq
+not
+if-goto WHILE
+label END
+goto END
+"
+    );
+}
+
+#[test]
+fn labels_are_scoped_by_function() {
+    let code = optimized(
+        r"
+function Sys.init 0
+push constant 3
+call Main.f 1
+pop static 0
+label L
+goto L
+function Main.f 0
+label L
+push argument 0
+return
+",
+        &[Pass::UnusedLabels],
+    );
+    // only the label of `Sys.init` is used
+    assert_eq!(code[4], Command::Label("L".into()));
+    assert_eq!(code[6].to_string(), "function Main.f 0");
+    assert_eq!(code[7].to_string(), "push argument 0");
+}
+
+#[test]
+fn course_programs() {
+    assert_equivalent(&load(&["projects/11/ComplexArrays"]), &[]);
+    assert_equivalent(&load(&["projects/11/ConvertToBin"]), &[(8000, 1234)]);
+    assert_equivalent(&load(&["projects/12/MemoryTest"]), &[]);
+    // the VM code of the official OS
+    let sources = load(&["projects/11/Seven", "tools/OS"]);
+    let (_, screen, _) = run(&sources, &[]);
+    assert!(screen.iter().any(|word| *word != 0));
+    assert!(assert_equivalent(&sources, &[]) > 0);
+}
+
+/// Checks that the program behaves the same with the calls to small functions inlined,
+/// alone and followed by the other passes. Returns the number of inlined calls.
+fn assert_inlining_equivalent(
+    sources: &[ParsedSource],
+    setup: &[(usize, i16)],
+    threshold: usize,
+) -> usize {
+    let expected = run(sources, setup);
+    let mut inlined = sources.to_vec();
+    let count = inline(&mut inlined, threshold);
+    assert_eq!(run(&inlined, setup), expected);
+    optimize(&mut inlined, &Pass::ALL);
+    assert_eq!(run(&inlined, setup), expected);
+    count
+}
+
+#[test]
+fn inlining() {
+    let files = [
+        (
+            "Sys",
+            "function Sys.init 0\ncall Main.main 0\npop temp 0\nlabel END\ngoto END",
+        ),
+        (
+            "Main",
+            r"
+function Main.main 1
+push constant 3000
+pop pointer 0
+push constant 8000
+call Main.getter 1
+pop static 0
+push constant 5
+neg
+call Main.abs 1
+pop static 1
+push constant 7
+call Main.abs 1
+pop static 2
+push constant 6
+call Main.fact 1
+pop static 3
+push constant 2
+push constant 3
+call Main.sum 2
+pop static 4
+push pointer 0
+pop static 5
+call Other.count 0
+pop static 6
+push constant 0
+return
+function Main.getter 0
+push argument 0
+pop pointer 0
+push this 1
+return
+function Main.abs 0
+push argument 0
+push constant 0
+lt
+if-goto NEG
+push argument 0
+return
+label NEG
+push argument 0
+neg
+return
+function Main.fact 0
+push argument 0
+push constant 1
+gt
+if-goto REC
+push constant 1
+return
+label REC
+push argument 0
+push argument 0
+push constant 1
+sub
+call Main.fact 1
+call Math.multiply 2
+return
+function Main.sum 1
+push argument 0
+push argument 1
+add
+pop local 0
+push local 0
+return
+",
+        ),
+        (
+            "Other",
+            "function Other.count 0\npush static 0\npush constant 1\nadd\npop static 0\npush static 0\nreturn",
+        ),
+    ];
+    let sources = test_util::parse(&files);
+    let setup = [(8001, 42)];
+    let (statics, _, _) = run(&sources, &setup);
+    assert_eq!(statics[..7], [42, 5, 7, 720, 5, 3000, 1]);
+    // not `Main.fact`, which is recursive, nor `Other.count`, whose statics belong to its file
+    assert_eq!(assert_inlining_equivalent(&sources, &setup, 10), 4);
+
+    let mut inlined = sources.clone();
+    inline(&mut inlined, 10);
+    let main = &inlined[1].commands;
+    // the arguments, locals and saved pointer of `Main.sum` and `Main.getter` are extra locals
+    assert_eq!(main[0].to_string(), "function Main.main 4");
+    let calls: Vec<_> = main
+        .iter()
+        .take_while(|command| !matches!(command, Command::Return))
+        .filter(|command| matches!(command, Command::Call { .. }))
+        .map(Command::to_string)
+        .collect();
+    assert_eq!(calls, ["call Main.fact 1", "call Other.count 0"]);
+    assert_eq!(inlined[1].lines.len(), main.len());
+
+    // nothing is small enough
+    assert_eq!(assert_inlining_equivalent(&sources, &setup, 3), 0);
+}
+
+#[test]
+fn inlining_course_programs() {
+    assert!(assert_inlining_equivalent(&load(&["projects/11/ComplexArrays"]), &[], 20) > 0);
+    assert!(
+        assert_inlining_equivalent(&load(&["projects/11/ConvertToBin"]), &[(8000, 1234)], 20) > 0
+    );
+    let sources = load(&["projects/11/Seven", "tools/OS"]);
+    assert!(assert_inlining_equivalent(&sources, &[], 30) > 0);
+}
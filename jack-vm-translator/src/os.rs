@@ -0,0 +1,147 @@
+use std::{collections::BTreeSet, error::Error, path::Path};
+
+use jack_vm_ir::{Command, FunctionName};
+
+use crate::parser::{ParsedSource, Parser};
+use crate::source::Source;
+
+/// The VM code of the standard Jack OS, from `tools/OS`.
+const BUNDLED: [(&str, &str); 8] = [
+    ("Array", include_str!("../../tools/OS/Array.vm")),
+    ("Keyboard", include_str!("../../tools/OS/Keyboard.vm")),
+    ("Math", include_str!("../../tools/OS/Math.vm")),
+    ("Memory", include_str!("../../tools/OS/Memory.vm")),
+    ("Output", include_str!("../../tools/OS/Output.vm")),
+    ("Screen", include_str!("../../tools/OS/Screen.vm")),
+    ("String", include_str!("../../tools/OS/String.vm")),
+    ("Sys", include_str!("../../tools/OS/Sys.vm")),
+];
+
+/// Reads the OS classes in `dir`, or the bundled standard OS.
+pub fn load(dir: Option<&Path>) -> Result<Vec<ParsedSource>, Box<dyn Error>> {
+    let sources = match dir {
+        Some(dir) => Source::read(dir)?,
+        None => BUNDLED
+            .iter()
+            .map(|(name, content)| Source {
+                name: name.to_string(),
+                content: content.to_string(),
+            })
+            .collect(),
+    };
+    Ok(sources
+        .into_iter()
+        .map(Parser::parse)
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Adds the classes of `os` that the program calls but doesn't define itself, and the ones they call.
+/// A program with `Main.main` is a Jack application, which also needs the class of `entry`.
+/// Returns the names of the added classes.
+pub fn link(
+    sources: &mut Vec<ParsedSource>,
+    os: Vec<ParsedSource>,
+    entry: &FunctionName,
+) -> Vec<String> {
+    let mut linked = vec![];
+    let mut os: Vec<_> = os.into_iter().map(Some).collect();
+    loop {
+        let defined: BTreeSet<_> = functions(sources).map(FunctionName::class).collect();
+        let mut needed: BTreeSet<_> = sources
+            .iter()
+            .flat_map(|source| &source.commands)
+            .filter_map(|command| match command {
+                Command::Call { name, .. } => Some(name.class()),
+                _ => None,
+            })
+            .collect();
+        if functions(sources).any(|name| name == "Main.main") {
+            needed.insert(entry.class());
+        }
+        let missing: Vec<_> = os
+            .iter_mut()
+            .filter(|class| {
+                class
+                    .as_ref()
+                    .is_some_and(|class| defines(class, &needed) && !defines(class, &defined))
+            })
+            .filter_map(Option::take)
+            .collect();
+        if missing.is_empty() {
+            return linked;
+        }
+        linked.extend(missing.iter().map(|class| class.name.clone()));
+        sources.extend(missing);
+    }
+}
+
+fn functions(sources: &[ParsedSource]) -> impl Iterator<Item = &FunctionName> {
+    sources
+        .iter()
+        .flat_map(|source| &source.commands)
+        .filter_map(|command| match command {
+            Command::Function { name, .. } => Some(name),
+            _ => None,
+        })
+}
+
+/// Whether `source` defines functions of one of `classes`.
+fn defines(source: &ParsedSource, classes: &BTreeSet<&str>) -> bool {
+    functions(std::slice::from_ref(source)).any(|name| classes.contains(name.class()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::test_util::parse;
+
+    #[test]
+    fn links_the_standard_os() {
+        let mut sources = parse(&[(
+            "Main",
+            "function Main.main 0\npush constant 9\ncall Math.sqrt 1\npop static 0\npush constant 0\nreturn",
+        )]);
+        let linked = link(&mut sources, load(None).unwrap(), &"Sys.init".into());
+        // Sys.init initializes every class
+        assert_eq!(linked.len(), BUNDLED.len());
+        let mut vm = Interpreter::new(&sources).unwrap();
+        vm.run(Some(2_000_000)).unwrap();
+        assert_eq!(vm.peek(16), 3);
+    }
+
+    #[test]
+    fn only_needed_classes_are_linked() {
+        let os = || {
+            parse(&[
+                ("Math", "function Math.abs 0\npush argument 0\nreturn"),
+                ("Memory", "function Memory.peek 0\ncall Math.abs 1\nreturn"),
+                ("Screen", "function Screen.clear 0\npush constant 0\nreturn"),
+                ("Sys", "function Sys.init 0\ncall Main.main 0\nreturn"),
+            ])
+        };
+        let mut sources = parse(&[(
+            "Test",
+            "function Test.run 0\npush constant 9\ncall Memory.peek 1\nreturn",
+        )]);
+        assert_eq!(
+            link(&mut sources, os(), &"Sys.init".into()),
+            ["Memory", "Math"]
+        );
+
+        // Jack applications also need the class of the entry function
+        let mut sources = parse(&[("Main", "function Main.main 0\npush constant 0\nreturn")]);
+        assert_eq!(link(&mut sources, os(), &"Sys.init".into()), ["Sys"]);
+
+        // user classes take precedence
+        let mut sources = parse(&[
+            (
+                "Test",
+                "function Test.run 0\npush constant 9\ncall Memory.peek 1\nreturn",
+            ),
+            ("Memory", "function Memory.peek 0\npush constant 1\nreturn"),
+        ]);
+        assert!(link(&mut sources, os(), &"Sys.init".into()).is_empty());
+        assert_eq!(sources.len(), 2);
+    }
+}
@@ -1,33 +1,111 @@
 use std::str::FromStr;
 
-use crate::command::Command;
-use crate::errors::{ParseCommandError, ParserError};
+use crate::errors::{Diagnostic, Diagnostics, ParseCommandError};
 use crate::source::Source;
+use jack_vm_ir::Command;
 
+#[derive(Clone)]
 pub struct ParsedSource {
     pub commands: Vec<Command>,
+    /// Line number of each command, or of the command it was rewritten from.
+    pub lines: Vec<u32>,
     pub name: String,
 }
 
 pub struct Parser;
 
 impl Parser {
-    pub fn parse(source: Source) -> Result<ParsedSource, ParserError> {
+    /// Parses every line of `source`, reporting all the invalid ones.
+    pub fn parse(source: Source) -> Result<ParsedSource, Diagnostics> {
+        let mut commands = vec![];
+        let mut lines = vec![];
+        let mut errors = vec![];
+        for (id, line) in source.content.lines().enumerate() {
+            let line_number = id as u32 + 1;
+            match Command::from_str(line) {
+                Ok(command) => {
+                    commands.push(command);
+                    lines.push(line_number);
+                }
+                Err(ParseCommandError::NoCommand) => {}
+                Err(err) => errors.push(Diagnostic {
+                    column: Some(Self::column(line, &err)),
+                    ..Diagnostic::error(&source.name, Some(line_number), err.to_string())
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Diagnostics(errors));
+        }
         Ok(ParsedSource {
-            commands: source
-                .content
-                .lines()
-                .enumerate()
-                .filter_map(|(id, line)| {
-                    let result = Command::from_str(line);
-                    if let Err(ParseCommandError::NoCommand) = result {
-                        None
-                    } else {
-                        Some(result.map_err(|err| ParserError::new(err, id as u32 + 1)))
-                    }
-                })
-                .collect::<Result<Vec<_>, _>>()?,
+            commands,
+            lines,
             name: source.name,
         })
     }
+
+    /// Column of the word of `line` that `err` is about.
+    fn column(line: &str, err: &ParseCommandError) -> u32 {
+        let code = line.split("//").next().unwrap();
+        let words: Vec<_> = code.split_whitespace().collect();
+        let end = code.trim_end().len() as u32 + 1;
+        let word = |i: usize| {
+            words.get(i).map_or(end, |word| {
+                (word.as_ptr() as usize - line.as_ptr() as usize) as u32 + 1
+            })
+        };
+        match err {
+            ParseCommandError::InvalidCommandName(_) => word(0),
+            ParseCommandError::ParseSegmentError(_) | ParseCommandError::InvalidName(_) => word(1),
+            ParseCommandError::InvalidArgument(_) => word(2),
+            ParseCommandError::NotEnoughArguments | ParseCommandError::NoCommand => end,
+            ParseCommandError::TooManyArguments => match words[0] {
+                "label" | "goto" | "if-goto" => word(2),
+                "push" | "pop" | "function" | "call" => word(3),
+                _ => word(1),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_errors_are_reported() {
+        let errors = Parser::parse(Source {
+            name: "Main".to_string(),
+            content: r"
+push constant 1
+  pusj constant 2
+push locals 0
+pop temp x // comment
+label A B
+add 1
+call Main.f
+goto 1st
+"
+            .to_string(),
+        })
+        .err()
+        .unwrap();
+        let rendered: Vec<_> = errors.0.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            rendered,
+            [
+                "Main.vm:3:3: error: Invalid command \"pusj\"",
+                "Main.vm:4:6: error: Failed to parse segment \"locals\"",
+                "Main.vm:5:10: error: Invalid argument \"invalid digit found in string\"",
+                "Main.vm:6:9: error: Too many arguments",
+                "Main.vm:7:5: error: Too many arguments",
+                "Main.vm:8:12: error: Not enough arguments",
+                "Main.vm:9:6: error: Invalid name \"1st\"",
+            ]
+        );
+        assert_eq!(
+            errors.0[0].render(Some("\n\n  pusj constant 2")),
+            "Main.vm:3:3: error: Invalid command \"pusj\"\n3 |   pusj constant 2\n  |   ^"
+        );
+    }
 }
@@ -1,4 +1,154 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
 pub struct Source {
     pub content: String,
     pub name: String,
 }
+
+impl Source {
+    /// Reads `input`, or every VM file in it if it is a directory, sorted by name
+    /// so that the program doesn't depend on the order the file system lists them in.
+    pub fn read(input: &Path) -> Result<Vec<Source>, Box<dyn Error>> {
+        let files = if input.is_dir() {
+            Self::vm_files(input)?
+        } else {
+            vec![PathBuf::from(input)]
+        };
+        files.iter().map(|file| Self::read_file(file)).collect()
+    }
+
+    /// The VM files in `dir`, sorted by name.
+    fn vm_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|s| {
+                s.ok().and_then(|entry| {
+                    let path = entry.path();
+                    if path.is_file() && path.extension().is_some_and(|ext| ext == "vm") {
+                        Some(path)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect();
+        if files.is_empty() {
+            return Err(format!(
+                "No source code found in directory {}!",
+                dir.to_string_lossy()
+            )
+            .into());
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Reads the files and directories of `inputs` in order, expanding glob patterns
+    /// like `src/*.vm` into the paths they match, sorted.
+    /// A file given more than once, directly or in a directory, is only read the first time.
+    pub fn read_all(inputs: &[String]) -> Result<Vec<Source>, Box<dyn Error>> {
+        let mut paths: Vec<PathBuf> = vec![];
+        for input in inputs {
+            if input.contains(['*', '?', '[']) {
+                let matches = glob::glob(input)?.collect::<Result<Vec<_>, _>>()?;
+                if matches.is_empty() {
+                    return Err(format!("No file matches {input}").into());
+                }
+                paths.extend(matches);
+            } else {
+                paths.push(input.into());
+            }
+        }
+        let mut files = vec![];
+        for path in paths {
+            if path.is_dir() {
+                files.extend(Self::vm_files(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+        let mut sources = vec![];
+        let mut read = vec![];
+        for file in files {
+            let canonical = fs::canonicalize(&file)
+                .map_err(|err| format!("Error reading {}: {err}", file.to_string_lossy()))?;
+            if !read.contains(&canonical) {
+                read.push(canonical);
+                sources.push(Self::read_file(&file)?);
+            }
+        }
+        Ok(sources)
+    }
+
+    fn read_file(file: &Path) -> Result<Source, Box<dyn Error>> {
+        Ok(Source {
+            content: fs::read_to_string(file)
+                .map_err(|err| format!("Error reading {}: {err}", file.to_string_lossy()))?,
+            name: file.file_stem().unwrap().to_string_lossy().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::translator::{Bootstrap, Options, Translator};
+
+    fn root() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+    }
+
+    fn names(sources: &[Source]) -> Vec<&str> {
+        sources.iter().map(|source| source.name.as_str()).collect()
+    }
+
+    fn translate(sources: Vec<Source>) -> String {
+        let sources: Vec<_> = sources
+            .into_iter()
+            .map(|source| Parser::parse(source).unwrap())
+            .collect();
+        Translator::translate_program(&sources, Some(&Bootstrap::default()), Options::default())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn directories_are_read_in_order() {
+        let sources = Source::read(&root().join("projects/11/Pong")).unwrap();
+        assert_eq!(names(&sources), ["Ball", "Bat", "Main", "PongGame"]);
+    }
+
+    #[test]
+    fn file_lists_and_globs() {
+        let dir = root().join("projects/11/Pong");
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let sources = Source::read_all(&[
+            path("Main.vm"),
+            path("B*.vm"),
+            path("Main.vm"),
+            path("PongGame.vm"),
+        ])
+        .unwrap();
+        assert_eq!(names(&sources), ["Main", "Ball", "Bat", "PongGame"]);
+        assert!(Source::read_all(&[path("*.jack.vm")]).is_err());
+        // files of a directory given again on their own
+        let sources = Source::read_all(&[path(""), path("Main.vm"), path("B*.vm")]).unwrap();
+        assert_eq!(names(&sources), ["Ball", "Bat", "Main", "PongGame"]);
+        let sources = Source::read_all(&[path("Main.vm"), path("")]).unwrap();
+        assert_eq!(names(&sources), ["Main", "Ball", "Bat", "PongGame"]);
+    }
+
+    #[test]
+    fn translation_is_reproducible() {
+        let dir = root().join("projects/11/Pong");
+        let first = translate(Source::read(&dir).unwrap());
+        let second = translate(Source::read(&dir).unwrap());
+        assert!(first == second);
+        let glob = dir.join("*.vm").to_string_lossy().to_string();
+        assert!(translate(Source::read_all(&[glob]).unwrap()) == first);
+    }
+}
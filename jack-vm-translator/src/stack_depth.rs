@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use jack_vm_ir::{Command, FunctionName, Label};
+
+use crate::errors::{Diagnostic, Severity};
+use crate::parser::ParsedSource;
+
+/// Stack usage of a function, found by following every path through its commands.
+#[derive(Debug)]
+pub struct FunctionStack {
+    pub name: FunctionName,
+    /// Largest number of values the function has on its stack at once,
+    /// not counting its locals and the frames of the functions it calls.
+    pub max_depth: usize,
+    /// Depth of the stack before each command of the function, `None` where it can't be reached.
+    pub depths: Vec<Option<usize>>,
+    pub issues: Vec<Diagnostic>,
+}
+
+/// Number of values `command` takes from the stack, and how many it leaves.
+fn effect(command: &Command) -> (usize, usize) {
+    match command {
+        Command::Push { .. } => (0, 1),
+        Command::Pop { .. } | Command::IfGoTo(_) => (1, 0),
+        Command::Add
+        | Command::Sub
+        | Command::Eq
+        | Command::Gt
+        | Command::Lt
+        | Command::And
+        | Command::Or
+        | Command::Mul
+        | Command::Div
+        | Command::Shl
+        | Command::Shr => (2, 1),
+        Command::Neg | Command::Not => (1, 1),
+        Command::Call { n_args, .. } => (*n_args as usize, 1),
+        Command::Return => (1, 0),
+        Command::Label(_) | Command::GoTo(_) | Command::Function { .. } => (0, 0),
+    }
+}
+
+/// Reports the functions of the program whose stack differs in depth where paths join,
+/// that take values they haven't pushed, or that return with an empty stack,
+/// by following every path through their commands.
+pub fn check(sources: &[ParsedSource]) -> Vec<Diagnostic> {
+    sources
+        .iter()
+        .flat_map(analyze)
+        .flat_map(|function| function.issues)
+        .collect()
+}
+
+/// Analyzes the stack of every function of `source`.
+pub fn analyze(source: &ParsedSource) -> Vec<FunctionStack> {
+    let starts = source
+        .commands
+        .iter()
+        .enumerate()
+        .filter(|(_, command)| matches!(command, Command::Function { .. }))
+        .map(|(i, _)| i)
+        .chain([source.commands.len()])
+        .collect::<Vec<_>>();
+    starts
+        .windows(2)
+        .map(|range| analyze_function(source, range[0], range[1]))
+        .collect()
+}
+
+/// Analyzes the function whose commands are `start..end`.
+fn analyze_function(source: &ParsedSource, start: usize, end: usize) -> FunctionStack {
+    let commands = &source.commands[start..end];
+    let Command::Function { name, .. } = &commands[0] else {
+        unreachable!()
+    };
+    let labels: HashMap<&Label, usize> = commands
+        .iter()
+        .enumerate()
+        .filter_map(|(i, command)| match command {
+            Command::Label(label) => Some((label, i)),
+            _ => None,
+        })
+        .collect();
+    let mut issues = vec![];
+    let mut issue = |i: usize, message: String| {
+        issues.push(Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(&source.name, source.lines.get(start + i).copied(), message)
+        })
+    };
+
+    // depth of the stack before each command, of the ones reached so far
+    let mut depths: Vec<Option<usize>> = vec![None; commands.len()];
+    let mut max_depth = 0;
+    let mut pending = vec![(1, 0)];
+    while let Some((i, depth)) = pending.pop() {
+        let Some(command) = commands.get(i) else {
+            // falling through to the next function
+            continue;
+        };
+        match depths[i] {
+            Some(known) if known != depth => {
+                issue(
+                    i,
+                    format!(
+                        "The stack of {name} has {known} or {depth} values here, depending on the path"
+                    ),
+                );
+                continue;
+            }
+            Some(_) => continue,
+            None => depths[i] = Some(depth),
+        }
+        let (taken, left) = effect(command);
+        if depth < taken {
+            let message = if matches!(command, Command::Return) {
+                format!("{name} returns with an empty stack")
+            } else {
+                format!("{command} takes {taken} value(s) from a stack of {depth} in {name}")
+            };
+            issue(i, message);
+            continue;
+        }
+        let depth = depth - taken + left;
+        max_depth = max_depth.max(depth);
+        let target = |label| labels.get(label).copied();
+        match command {
+            Command::Return => {}
+            Command::GoTo(label) => pending.extend(target(label).map(|i| (i, depth))),
+            Command::IfGoTo(label) => {
+                pending.extend(target(label).map(|i| (i, depth)));
+                pending.push((i + 1, depth));
+            }
+            _ => pending.push((i + 1, depth)),
+        }
+    }
+    FunctionStack {
+        name: name.clone(),
+        max_depth,
+        depths,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{parser::Parser, source::Source};
+
+    fn parse(content: &str) -> ParsedSource {
+        Parser::parse(Source {
+            name: "Main".to_string(),
+            content: content.to_string(),
+        })
+        .unwrap()
+    }
+
+    fn issues(content: &str) -> Vec<String> {
+        check(&[parse(content)])
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn max_depth() {
+        let functions = analyze(&parse(
+            r"
+function Main.main 0
+push constant 1
+push constant 2
+push constant 3
+call Main.f 2
+add
+label LOOP
+push constant 0
+if-goto LOOP
+return
+function Main.f 2
+push local 0
+return
+",
+        ));
+        let depths: Vec<_> = functions
+            .iter()
+            .map(|function| (function.name.as_str(), function.max_depth))
+            .collect();
+        assert_eq!(depths, [("Main.main", 3), ("Main.f", 1)]);
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(
+            issues(
+                r"
+function Main.join 0
+push argument 0
+if-goto ONE
+push constant 1
+push constant 2
+goto END
+label ONE
+push constant 1
+label END
+return
+function Main.underflow 0
+push constant 1
+add
+return
+function Main.empty 0
+push constant 1
+pop temp 0
+return
+"
+            ),
+            [
+                "Main.vm:10: warning: The stack of Main.join has 2 or 1 values here, depending on the path",
+                "Main.vm:14: warning: add takes 2 value(s) from a stack of 1 in Main.underflow",
+                "Main.vm:19: warning: Main.empty returns with an empty stack",
+            ]
+        );
+    }
+
+    #[test]
+    fn course_programs_are_fine() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let dirs = [
+            "projects/08/FunctionCalls/FibonacciElement",
+            "projects/08/FunctionCalls/NestedCall",
+            "projects/08/FunctionCalls/StaticsTest",
+            "projects/11/Average",
+            "projects/11/ComplexArrays",
+            "projects/11/ConvertToBin",
+            "projects/11/Pong",
+            "projects/11/Seven",
+            "projects/11/Square",
+            "tools/OS",
+        ];
+        for dir in dirs {
+            let sources: Vec<_> = Source::read(&root.join(dir))
+                .unwrap()
+                .into_iter()
+                .map(|source| Parser::parse(source).unwrap())
+                .collect();
+            assert_eq!(check(&sources), [], "{dir}");
+        }
+    }
+}
@@ -0,0 +1,452 @@
+mod tests;
+
+use std::{error::Error, fs, path::Path};
+
+use crate::errors::ScriptError;
+use crate::interpreter::{Interpreter, ARG, LCL, SP, TEMP, THAT, THIS};
+use crate::parser::{ParsedSource, Parser};
+use crate::source::Source;
+
+/// Something a test script can drive, like the VM emulator of the course.
+pub trait Machine: Sized {
+    /// Loads the program from `sources`.
+    fn load(sources: &[ParsedSource]) -> Result<Self, Box<dyn Error>>;
+    fn peek(&self, addr: usize) -> i16;
+    fn poke(&mut self, addr: usize, value: i16);
+    /// Executes one VM command.
+    fn step(&mut self) -> Result<(), Box<dyn Error>>;
+    /// Called once the script is done.
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl Machine for Interpreter {
+    fn load(sources: &[ParsedSource]) -> Result<Self, Box<dyn Error>> {
+        Ok(Interpreter::new(sources)?)
+    }
+
+    fn peek(&self, addr: usize) -> i16 {
+        Interpreter::peek(self, addr)
+    }
+
+    fn poke(&mut self, addr: usize, value: i16) {
+        Interpreter::poke(self, addr, value)
+    }
+
+    fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(Interpreter::step(self)?)
+    }
+}
+
+/// A memory location a script can set or output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Ram(usize),
+    /// An offset from the address stored in a pointer, like `local[2]`.
+    Indirect {
+        pointer: usize,
+        offset: usize,
+    },
+}
+
+impl Target {
+    fn parse(s: &str) -> Option<Self> {
+        let pointer = |name: &str| match name {
+            "sp" => Some(SP),
+            "local" => Some(LCL),
+            "argument" => Some(ARG),
+            "this" => Some(THIS),
+            "that" => Some(THAT),
+            _ => None,
+        };
+        let Some((name, index)) = s.strip_suffix(']').and_then(|s| s.split_once('[')) else {
+            return pointer(s).map(Self::Ram);
+        };
+        let index: usize = index.parse().ok()?;
+        match name {
+            "RAM" => Some(Self::Ram(index)),
+            "temp" if index < 8 => Some(Self::Ram(TEMP + index)),
+            "pointer" if index < 2 => Some(Self::Ram(THIS + index)),
+            name => pointer(name).map(|pointer| Self::Indirect {
+                pointer,
+                offset: index,
+            }),
+        }
+    }
+
+    fn address(&self, machine: &impl Machine) -> usize {
+        match *self {
+            Self::Ram(addr) => addr,
+            Self::Indirect { pointer, offset } => machine.peek(pointer) as usize + offset,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: String,
+    target: Target,
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+impl Column {
+    /// Parses a column like `RAM[256]%D1.6.1`.
+    fn parse(s: &str) -> Option<Self> {
+        let (name, format) = s.split_once('%').unwrap_or((s, "D1.6.1"));
+        let mut chars = format.chars();
+        let format = chars.next().filter(|c| "DBX".contains(*c))?;
+        let padding: Vec<usize> = chars
+            .as_str()
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let [left, width, right] = padding[..] else {
+            return None;
+        };
+        Some(Self {
+            name: name.to_string(),
+            target: Target::parse(name)?,
+            format,
+            left,
+            width,
+            right,
+        })
+    }
+
+    fn header(&self) -> String {
+        let width = self.left + self.width + self.right;
+        let name: String = self.name.chars().take(width).collect();
+        let padding = width - name.len();
+        let left = padding / 2;
+        format!("{}{name}{}", " ".repeat(left), " ".repeat(padding - left))
+    }
+
+    fn cell(&self, value: i16) -> String {
+        let value = match self.format {
+            'B' => format!("{:016b}", value as u16),
+            'X' => format!("{:04X}", value as u16),
+            _ => value.to_string(),
+        };
+        // like the course tools, keep the least significant digits when the value doesn't fit
+        let value = &value[value.len().saturating_sub(self.width)..];
+        format!(
+            "{}{value:>width$}{}",
+            " ".repeat(self.left),
+            " ".repeat(self.right),
+            width = self.width
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    /// Loads a file, or every VM file of the directory.
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Target, i16),
+    Step,
+    Output,
+    Echo(String),
+    Repeat(usize, Vec<(usize, Statement)>),
+}
+
+/// A test script of the course, like `StackTestVME.tst`.
+pub struct TestScript {
+    statements: Vec<(usize, Statement)>,
+}
+
+/// Splits a script into words, quoted strings, and the punctuation `,;{}`, with their line numbers.
+fn tokenize(script: &str) -> Result<Vec<(usize, String)>, ScriptError> {
+    let mut tokens = vec![];
+    let mut chars = script.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                let start = line;
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            line += (c == '\n') as usize;
+                            last = c;
+                        }
+                        None => return Err(ScriptError::syntax(start, "Unterminated comment")),
+                    }
+                }
+            }
+            ',' | ';' | '{' | '}' => tokens.push((line, c.to_string())),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(ScriptError::syntax(line, "Unterminated string"))
+                        }
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push((line, format!("\"{text}")));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !",;{}\"".contains(*c))
+                {
+                    word.push(c);
+                }
+                tokens.push((line, word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_value(line: usize, s: &str) -> Result<i16, ScriptError> {
+    let invalid = || ScriptError::syntax(line, &format!("Invalid value \"{s}\""));
+    let (radix, digits) = match s.strip_prefix('%') {
+        Some(s) if !s.is_empty() => match s.split_at(1) {
+            ("B", digits) => (2, digits),
+            ("X", digits) => (16, digits),
+            ("D", digits) => (10, digits),
+            _ => return Err(invalid()),
+        },
+        Some(_) => return Err(invalid()),
+        None => (10, s),
+    };
+    if radix == 10 {
+        digits.parse().map_err(|_| invalid())
+    } else {
+        u16::from_str_radix(digits, radix)
+            .map(|value| value as i16)
+            .map_err(|_| invalid())
+    }
+}
+
+impl TestScript {
+    pub fn parse(script: &str) -> Result<Self, ScriptError> {
+        let tokens = tokenize(script)?;
+        let mut tokens = tokens.iter().map(|(line, token)| (*line, token.as_str()));
+        let statements = Self::parse_block(&mut tokens, None)?;
+        Ok(Self { statements })
+    }
+
+    /// Parses statements up to the closing brace of a block, or up to the end if `start` is `None`.
+    fn parse_block<'a>(
+        tokens: &mut impl Iterator<Item = (usize, &'a str)>,
+        start: Option<usize>,
+    ) -> Result<Vec<(usize, Statement)>, ScriptError> {
+        let mut statements = vec![];
+        loop {
+            let Some((line, first)) = tokens.next() else {
+                return match start {
+                    Some(line) => Err(ScriptError::syntax(line, "Unterminated block")),
+                    None => Ok(statements),
+                };
+            };
+            if first == "}" && start.is_some() {
+                return Ok(statements);
+            }
+            if first == "repeat" {
+                let count = tokens
+                    .next()
+                    .and_then(|(_, count)| count.parse().ok())
+                    .ok_or_else(|| ScriptError::syntax(line, "Expected a repeat count"))?;
+                if !matches!(tokens.next(), Some((_, "{"))) {
+                    return Err(ScriptError::syntax(line, "Expected \"{\""));
+                }
+                let body = Self::parse_block(tokens, Some(line))?;
+                statements.push((line, Statement::Repeat(count, body)));
+                continue;
+            }
+            let mut args = vec![];
+            loop {
+                match tokens.next() {
+                    Some((_, "," | ";")) => break,
+                    Some((_, arg)) if arg != "{" && arg != "}" => args.push(arg),
+                    _ => return Err(ScriptError::syntax(line, "Expected \",\" or \";\"")),
+                }
+            }
+            statements.push((line, Self::parse_command(line, first, &args)?));
+        }
+    }
+
+    fn parse_command(line: usize, name: &str, args: &[&str]) -> Result<Statement, ScriptError> {
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(ScriptError::syntax(
+                    line,
+                    &format!("\"{name}\" expects {n} argument(s)"),
+                ))
+            }
+        };
+        Ok(match name {
+            "load" if args.len() > 1 => {
+                return Err(ScriptError::syntax(
+                    line,
+                    "\"load\" expects at most 1 argument",
+                ))
+            }
+            "load" => Statement::Load(args.first().map(|s| s.to_string())),
+            "output-file" => arity(1).map(|_| Statement::OutputFile(args[0].to_string()))?,
+            "compare-to" => arity(1).map(|_| Statement::CompareTo(args[0].to_string()))?,
+            "output-list" => Statement::OutputList(
+                args.iter()
+                    .map(|arg| {
+                        Column::parse(arg).ok_or_else(|| {
+                            ScriptError::syntax(line, &format!("Invalid output column \"{arg}\""))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            "set" => {
+                arity(2)?;
+                let target = Target::parse(args[0]).ok_or_else(|| {
+                    ScriptError::syntax(line, &format!("Invalid variable \"{}\"", args[0]))
+                })?;
+                Statement::Set(target, parse_value(line, args[1])?)
+            }
+            "vmstep" | "ticktock" => arity(0).map(|_| Statement::Step)?,
+            "output" => arity(0).map(|_| Statement::Output)?,
+            "echo" => {
+                arity(1)?;
+                Statement::Echo(args[0].trim_start_matches('"').to_string())
+            }
+            name => return Err(ScriptError::UnknownCommand(line, name.to_string())),
+        })
+    }
+
+    /// Runs the script on machines loaded from files relative to `dir`,
+    /// comparing each output line with the compare file as it is produced.
+    /// Returns the output.
+    pub fn run<M: Machine>(&self, dir: &Path) -> Result<String, ScriptError> {
+        let mut run = Run::<M> {
+            dir,
+            machine: None,
+            columns: vec![],
+            output: String::new(),
+            lines: 0,
+            compare: None,
+        };
+        run.exec(&self.statements)?;
+        if let Some(machine) = run.machine.as_mut() {
+            machine.finish().map_err(ScriptError::Machine)?;
+        }
+        Ok(run.output)
+    }
+}
+
+struct Run<'a, M> {
+    dir: &'a Path,
+    machine: Option<M>,
+    columns: Vec<Column>,
+    output: String,
+    lines: usize,
+    compare: Option<Vec<String>>,
+}
+
+impl<M: Machine> Run<'_, M> {
+    fn machine(&mut self, line: usize) -> Result<&mut M, ScriptError> {
+        self.machine
+            .as_mut()
+            .ok_or_else(|| ScriptError::syntax(line, "No program loaded"))
+    }
+
+    fn exec(&mut self, statements: &[(usize, Statement)]) -> Result<(), ScriptError> {
+        for (line, statement) in statements {
+            let line = *line;
+            match statement {
+                Statement::Load(file) => {
+                    // scripts for the CPU emulator load the translation of the directory
+                    let path = match file {
+                        Some(file) if !file.ends_with(".asm") => self.dir.join(file),
+                        _ => self.dir.to_path_buf(),
+                    };
+                    let machine = Source::read(&path)
+                        .and_then(|sources| {
+                            sources
+                                .into_iter()
+                                .map(|source| Ok(Parser::parse(source)?))
+                                .collect::<Result<Vec<_>, Box<dyn Error>>>()
+                        })
+                        .and_then(|sources| M::load(&sources))
+                        .map_err(ScriptError::Machine)?;
+                    self.machine = Some(machine);
+                }
+                Statement::OutputFile(_) => {}
+                Statement::CompareTo(file) => {
+                    let content = fs::read_to_string(self.dir.join(file))
+                        .map_err(|err| ScriptError::Io(file.to_string(), err))?;
+                    self.compare = Some(content.lines().map(str::to_string).collect());
+                }
+                Statement::OutputList(columns) => {
+                    self.columns = columns.clone();
+                    let header = self.columns.iter().map(Column::header).collect();
+                    self.write_line(header)?;
+                }
+                Statement::Set(target, value) => {
+                    let machine = self.machine(line)?;
+                    let addr = target.address(machine);
+                    machine.poke(addr, *value);
+                }
+                Statement::Step => self.machine(line)?.step().map_err(ScriptError::Machine)?,
+                Statement::Output => {
+                    let machine = self
+                        .machine
+                        .as_ref()
+                        .ok_or_else(|| ScriptError::syntax(line, "No program loaded"))?;
+                    let cells = self
+                        .columns
+                        .iter()
+                        .map(|column| column.cell(machine.peek(column.target.address(machine))))
+                        .collect();
+                    self.write_line(cells)?;
+                }
+                Statement::Echo(_) => {}
+                Statement::Repeat(count, body) => {
+                    for _ in 0..*count {
+                        self.exec(body)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, cells: Vec<String>) -> Result<(), ScriptError> {
+        let line = format!("|{}|", cells.join("|"));
+        self.lines += 1;
+        if let Some(compare) = self.compare.as_ref() {
+            let expected = compare.get(self.lines - 1).map_or("", |s| s.trim_end());
+            let matches = expected.len() == line.len()
+                && expected
+                    .chars()
+                    .zip(line.chars())
+                    .all(|(expected, found)| expected == '*' || expected == found);
+            if !matches {
+                return Err(ScriptError::Comparison {
+                    line: self.lines,
+                    expected: expected.to_string(),
+                    found: line,
+                });
+            }
+        }
+        self.output.push_str(&line);
+        self.output.push('\n');
+        Ok(())
+    }
+}
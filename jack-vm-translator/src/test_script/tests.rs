@@ -0,0 +1,180 @@
+#![cfg(test)]
+
+use std::path::{Path, PathBuf};
+
+use super::*;
+use crate::cpu::Cpu;
+use crate::differential::Lockstep;
+use crate::translator::{Bootstrap, Options, Translator};
+
+/// Every test script for the VM emulator in projects 07 and 08,
+/// or for the CPU emulator with `suffix` empty.
+fn scripts_ending_with(suffix: &str) -> Vec<PathBuf> {
+    let projects = Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects");
+    let mut scripts = vec![];
+    for project in ["07", "08"] {
+        for group in fs::read_dir(projects.join(project)).unwrap() {
+            for dir in fs::read_dir(group.unwrap().path()).unwrap() {
+                let dir = dir.unwrap().path();
+                let name = dir.file_name().unwrap().to_string_lossy().to_string();
+                scripts.push(dir.join(format!("{name}{suffix}.tst")));
+            }
+        }
+    }
+    scripts.sort();
+    scripts
+}
+
+fn scripts() -> Vec<PathBuf> {
+    scripts_ending_with("VME")
+}
+
+/// The program translated with or without caching the top of the stack, on the CPU.
+struct Translated<const CACHE_TOP: bool>(Cpu);
+
+impl<const CACHE_TOP: bool> Machine for Translated<CACHE_TOP> {
+    fn load(sources: &[ParsedSource]) -> Result<Self, Box<dyn Error>> {
+        let options = Options {
+            cache_top: CACHE_TOP,
+            ..Options::default()
+        };
+        let bootstrap = Bootstrap::default();
+        let bootstrap = bootstrap.is_defined_in(sources).then_some(&bootstrap);
+        let (asm, source_map) = Translator::translate_program(sources, bootstrap, options)?;
+        let rom = Translator::assemble(&asm, &source_map)?
+            .lines()
+            .map(|word| u16::from_str_radix(word, 2))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(Cpu::new(&rom)))
+    }
+
+    fn peek(&self, addr: usize) -> i16 {
+        self.0.peek(addr)
+    }
+
+    fn poke(&mut self, addr: usize, value: i16) {
+        self.0.poke(addr, value)
+    }
+
+    fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        self.0.step();
+        Ok(())
+    }
+}
+
+fn run<M: Machine>(script: &Path) -> Result<String, ScriptError> {
+    TestScript::parse(&fs::read_to_string(script).unwrap())
+        .unwrap()
+        .run::<M>(script.parent().unwrap())
+}
+
+#[test]
+fn course_scripts_on_interpreter() {
+    let scripts = scripts();
+    assert_eq!(scripts.len(), 11);
+    for script in scripts {
+        if let Err(err) = run::<Interpreter>(&script) {
+            panic!("{}: {err}", script.display());
+        }
+    }
+}
+
+#[test]
+fn course_scripts_on_cpu() {
+    for script in scripts() {
+        if let Err(err) = run::<Lockstep>(&script) {
+            panic!("{}: {err}", script.display());
+        }
+    }
+}
+
+#[test]
+fn columns() {
+    let column = Column::parse("RAM[256]%D2.6.2").unwrap();
+    assert_eq!(column.header(), " RAM[256] ");
+    assert_eq!(column.cell(-17), "     -17  ");
+    let column = Column::parse("RAM[3006]%D1.6.1").unwrap();
+    assert_eq!(column.target, Target::Ram(3006));
+    assert_eq!(column.header(), "RAM[3006");
+    let column = Column::parse("RAM[11]%D1.6.1").unwrap();
+    assert_eq!(column.header(), "RAM[11] ");
+    let column = Column::parse("local[2]%B0.4.0").unwrap();
+    assert_eq!(
+        column.target,
+        Target::Indirect {
+            pointer: LCL,
+            offset: 2
+        }
+    );
+    assert_eq!(column.cell(6), "0110");
+    assert_eq!(Column::parse("RAM[1]%D1.6"), None);
+    assert_eq!(Column::parse("nothing[1]"), None);
+}
+
+#[test]
+fn syntax() {
+    let script = TestScript::parse(
+        r#"
+/* a block
+   comment */
+set RAM[0] %X0100, // hexadecimal
+repeat 2 {
+    vmstep; vmstep;
+}
+echo "two steps";
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        script.statements,
+        [
+            (4, Statement::Set(Target::Ram(0), 256)),
+            (
+                5,
+                Statement::Repeat(2, vec![(6, Statement::Step), (6, Statement::Step)])
+            ),
+            (8, Statement::Echo("two steps".to_string())),
+        ]
+    );
+    assert!(matches!(
+        TestScript::parse("set sp 256,\nfly;"),
+        Err(ScriptError::UnknownCommand(2, command)) if command == "fly"
+    ));
+    assert!(matches!(
+        TestScript::parse("repeat 3 {\nvmstep;"),
+        Err(ScriptError::Syntax { line: 1, .. })
+    ));
+    assert!(matches!(
+        TestScript::parse("set temp[8] 1;"),
+        Err(ScriptError::Syntax { line: 1, .. })
+    ));
+}
+
+#[test]
+fn comparison_failure() {
+    let dir =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../projects/07/StackArithmetic/SimpleAdd");
+    let script = TestScript::parse(
+        "load SimpleAdd.vm, compare-to SimpleAdd.cmp, output-list RAM[0]%D2.6.2 RAM[256]%D2.6.2;
+         set sp 256, vmstep, vmstep, output;",
+    )
+    .unwrap();
+    assert!(matches!(
+        script.run::<Interpreter>(&dir),
+        Err(ScriptError::Comparison { line: 2, expected, found })
+            if expected == "|     257  |      15  |" && found == "|     258  |       7  |"
+    ));
+}
+
+#[test]
+fn course_cpu_scripts() {
+    let scripts = scripts_ending_with("");
+    for script in &scripts {
+        if let Err(err) = run::<Translated<false>>(script) {
+            panic!("{}: {err}", script.display());
+        }
+        if let Err(err) = run::<Translated<true>>(script) {
+            panic!("{} with the top cached: {err}", script.display());
+        }
+    }
+}
@@ -0,0 +1,19 @@
+#![cfg(test)]
+//! Helpers shared by the tests of several modules.
+
+use crate::parser::{ParsedSource, Parser};
+use crate::source::Source;
+
+/// Parses VM files, given by their names without `.vm` and their contents.
+pub fn parse(files: &[(&str, &str)]) -> Vec<ParsedSource> {
+    files
+        .iter()
+        .map(|(name, content)| {
+            Parser::parse(Source {
+                name: name.to_string(),
+                content: content.to_string(),
+            })
+            .unwrap()
+        })
+        .collect()
+}
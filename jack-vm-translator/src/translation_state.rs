@@ -1,17 +1,23 @@
+use crate::translator::Options;
+
 pub struct TranslationState {
     comparison_counter: u16,
+    options: Options,
     name: String,
     function: Option<String>,
     ret_counter: u16,
+    top_cached: bool,
 }
 
 impl TranslationState {
-    pub fn new(name: &str) -> Self {
+    pub fn new(name: &str, options: Options) -> Self {
         TranslationState {
             comparison_counter: 0,
+            options,
             ret_counter: 0,
             name: name.to_string(),
             function: None,
+            top_cached: false,
         }
     }
 
@@ -25,6 +31,10 @@ impl TranslationState {
         self.comparison_counter
     }
 
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -36,4 +46,13 @@ impl TranslationState {
     pub fn func(&self) -> Option<&String> {
         self.function.as_ref()
     }
+
+    /// Whether the top of the stack is in D instead of memory.
+    pub fn is_top_cached(&self) -> bool {
+        self.top_cached
+    }
+
+    pub fn set_top_cached(&mut self, cached: bool) {
+        self.top_cached = cached;
+    }
 }
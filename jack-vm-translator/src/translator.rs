@@ -1,27 +1,75 @@
-use crate::errors::TranslationError;
+use assembler::Assembler;
+use jack_vm_ir::{Command, FunctionName};
+use serde::Serialize;
+
+use crate::cached;
+use crate::command::ToAsm;
+use crate::errors::{Diagnostic, Diagnostics, TranslationError};
 use crate::parser::ParsedSource;
 use crate::translation_state::TranslationState;
 
-pub struct Translator;
+/// Code generation options.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Jump to the shared `$$CALL` and `$$RETURN` routines instead of inlining calls and returns.
+    pub trampolines: bool,
+    /// Compare with `gt` and `lt` correctly even when `x - y` overflows.
+    /// `eq` is always correct.
+    pub safe_comparisons: bool,
+    /// Keep the top of the stack in D across straight-line code.
+    pub cache_top: bool,
+    /// Precede the code of each command with a comment naming its file, line and text.
+    pub debug: bool,
+    /// Accept the extension commands `mul`, `div`, `shl` and `shr`.
+    pub extensions: bool,
+}
 
-impl Translator {
-    pub fn translate<'a>(parsed: &ParsedSource) -> Result<String, TranslationError> {
-        let ParsedSource { name, commands } = parsed;
-        let mut state = TranslationState::new(name);
-        commands
+/// Where the code of a VM command is in the translated program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceMapEntry {
+    /// First and last line of the code, counting from 1.
+    pub asm_lines: (usize, usize),
+    /// ROM addresses of the instructions of the code, the end excluded.
+    pub rom: (usize, usize),
+    pub file: String,
+    pub line: Option<u32>,
+    pub function: Option<String>,
+    pub command: String,
+}
+
+/// How a program starts: by calling `entry` with an empty stack at `sp`.
+#[derive(Debug, Clone)]
+pub struct Bootstrap {
+    pub entry: FunctionName,
+    pub sp: u16,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Self {
+            entry: "Sys.init".into(),
+            sp: 256,
+        }
+    }
+}
+
+impl Bootstrap {
+    /// Whether `sources` define the entry function. Full programs do,
+    /// while the tests of project 7 and the single functions of project 8 run from the first command.
+    pub fn is_defined_in(&self, sources: &[ParsedSource]) -> bool {
+        sources
             .iter()
-            .map(|command| command.to_asm(&mut state))
-            .collect::<Result<Vec<_>, _>>()
-            .map(|v| v.join("\n"))
+            .flat_map(|source| &source.commands)
+            .any(|command| matches!(command, Command::Function { name, .. } if *name == self.entry))
     }
-    pub const BOOTSTRAP: &str = r"// init SP = 256
-@256
+
+    pub fn to_asm(&self) -> String {
+        format!(
+            r"// init SP = {sp}
+@{sp}
 D=A
 @SP
 M=D
-// // init LCL to SP
-// @LCL
-// M=D
 // return address
 @0
 D=A
@@ -63,7 +111,385 @@ M=D
 D=M
 @LCL
 M=D
-@Sys.init
+@{entry}
+0;JMP
+",
+            sp = self.sp,
+            entry = self.entry
+        )
+    }
+}
+
+pub struct Translator;
+
+impl Translator {
+    pub fn translate(parsed: &ParsedSource, options: Options) -> Result<String, TranslationError> {
+        let ParsedSource { name, commands, .. } = parsed;
+        let mut state = TranslationState::new(name, options);
+        let mut asms = commands
+            .iter()
+            .map(|command| command.to_asm(&mut state))
+            .collect::<Result<Vec<_>, _>>()?;
+        asms.push(cached::flush(&mut state));
+        Ok(asms.join("\n"))
+    }
+
+    /// Translates a whole program, starting with the `bootstrap` code if any,
+    /// and followed by the shared routines the translated code needs.
+    /// Also returns where the code of each command is.
+    /// Reports the commands of all files that can't be translated.
+    pub fn translate_program(
+        sources: &[ParsedSource],
+        bootstrap: Option<&Bootstrap>,
+        options: Options,
+    ) -> Result<(String, Vec<SourceMapEntry>), Diagnostics> {
+        let mut asm = String::new();
+        if let Some(bootstrap) = bootstrap {
+            asm.push_str(&bootstrap.to_asm());
+        }
+        let mut lines = asm.lines().count();
+        let mut rom = Self::instruction_count(&asm);
+        let mut source_map = vec![];
+        let mut errors = vec![];
+        for source in sources {
+            let mut state = TranslationState::new(&source.name, options);
+            for (i, command) in source.commands.iter().enumerate() {
+                let line = source.lines.get(i).copied();
+                let code = match command.to_asm(&mut state) {
+                    Ok(code) => code,
+                    Err(err) => {
+                        errors.push(Diagnostic::error(&source.name, line, err.message));
+                        continue;
+                    }
+                };
+                if options.debug {
+                    let position = line.map(|line| format!(":{line}")).unwrap_or_default();
+                    asm.push_str(&format!("// {}.vm{position}: {command}\n", source.name));
+                }
+                asm.push_str(&code);
+                asm.push('\n');
+                let (start, end) = (lines + 1, asm.lines().count());
+                let size = Self::instruction_count(&code);
+                source_map.push(SourceMapEntry {
+                    asm_lines: (start, end),
+                    rom: (rom, rom + size),
+                    file: source.name.clone(),
+                    line,
+                    function: state.func().cloned(),
+                    command: command.to_string(),
+                });
+                lines = end;
+                rom += size;
+            }
+            // the next file may start with a label
+            let flush = cached::flush(&mut state);
+            if let Some(last) = source_map.last_mut().filter(|_| !flush.is_empty()) {
+                asm.push_str(&flush);
+                asm.push('\n');
+                lines = asm.lines().count();
+                rom += Self::instruction_count(&flush);
+                (last.asm_lines.1, last.rom.1) = (lines, rom);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Diagnostics(errors));
+        }
+        asm.push_str(&Self::runtime(options));
+        Ok((asm, source_map))
+    }
+
+    /// The shared routines, placed after the code of the program.
+    pub fn runtime(options: Options) -> String {
+        if options.trampolines {
+            Self::TRAMPOLINES.to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Assembles the translated program `asm` to machine code, one binary word per line,
+    /// reporting the errors at the VM commands whose code they are in.
+    pub fn assemble(asm: &str, source_map: &[SourceMapEntry]) -> Result<String, Diagnostics> {
+        let lines = asm.lines().map(str::to_string).collect();
+        match Assembler::new(lines).try_compile() {
+            Ok(words) => Ok(words.join("\n")),
+            Err(errors) => Err(Diagnostics(
+                errors
+                    .into_iter()
+                    .map(|error| {
+                        // the bootstrap code and the runtime belong to the nearest command
+                        let entry = source_map
+                            .iter()
+                            .rev()
+                            .find(|entry| entry.asm_lines.0 <= error.line)
+                            .or(source_map.first())
+                            .expect("only the code of VM commands can be invalid");
+                        Diagnostic::error(&entry.file, entry.line, error.message)
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Number of instructions in `asm`, as counted by the assembler.
+    pub fn instruction_count(asm: &str) -> usize {
+        asm.lines()
+            .map(|line| line.split("//").next().unwrap().trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('('))
+            .count()
+    }
+
+    /// `$$CALL` calls the function at R13 with R14 arguments, returning to R15.
+    /// `$$RETURN` returns from the current function, like the inline `return`.
+    pub const TRAMPOLINES: &str = r"// call *R13 with R14 args, returning to *R15
+($$CALL)
+@R15
+D=M
+@SP
+A=M
+M=D
+// push LCL,...etc
+@LCL
+D=M
+@SP
+AM=M+1
+M=D
+@ARG
+D=M
+@SP
+AM=M+1
+M=D
+@THIS
+D=M
+@SP
+AM=M+1
+M=D
+@THAT
+D=M
+@SP
+AM=M+1
+M=D
+@SP
+M=M+1
+// new ARG
+@SP
+D=M
+@5
+D=D-A
+@R14
+D=D-M
+@ARG
+M=D
+// LCL = SP
+@SP
+D=M
+@LCL
+M=D
+// goto *R13
+@R13
+A=M
+0;JMP
+// return
+($$RETURN)
+// R14(return addr) = *(LCL - 5)
+@LCL
+D=M
+@5
+A=D-A
+D=M
+@R14
+M=D
+// *ARG = top()
+@SP
+A=M-1
+D=M
+@ARG
+A=M
+M=D
+// SP = ARG + 1
+@ARG
+D=M+1
+@SP
+M=D
+// THAT = *(LCL - 1),...etc
+@LCL
+AM=M-1
+D=M
+@THAT
+M=D
+@LCL
+AM=M-1
+D=M
+@THIS
+M=D
+@LCL
+AM=M-1
+D=M
+@ARG
+M=D
+@LCL
+A=M-1
+D=M
+@LCL
+M=D
+// goto *R14
+@R14
+A=M
 0;JMP
 ";
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::test_util::parse;
+    use crate::{parser::Parser, source::Source};
+
+    #[test]
+    fn errors_of_all_files_are_reported() {
+        let sources = parse(&[
+            ("Main", "push constant 1\npop constant 0\nreturn"),
+            ("Other", "function Other.f 0\npop constant 1"),
+        ]);
+        let errors = Translator::translate_program(&sources, None, Options::default()).unwrap_err();
+        let errors: Vec<_> = errors.0.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "Main.vm:2: error: Illegal operation: pop constant 0",
+                "Main.vm:3: error: Not in a function",
+                "Other.vm:2: error: Illegal operation: pop constant 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_outside_functions_are_reported_alike() {
+        let sources = parse(&[("Main", "call Main.f 0\nreturn")]);
+        for trampolines in [false, true] {
+            let options = Options {
+                trampolines,
+                ..Options::default()
+            };
+            let errors = Translator::translate_program(&sources, None, options).unwrap_err();
+            let errors: Vec<_> = errors.0.iter().map(|error| error.to_string()).collect();
+            assert_eq!(
+                errors,
+                [
+                    "Main.vm:1: error: Not in a function",
+                    "Main.vm:2: error: Not in a function",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn source_map() {
+        let sources = parse(&[(
+            "Main",
+            "function Main.main 0\n\npush constant 7 // seven\nreturn",
+        )]);
+        let options = Options {
+            debug: true,
+            ..Options::default()
+        };
+        let bootstrap = Bootstrap::default();
+        let (asm, map) =
+            Translator::translate_program(&sources, Some(&bootstrap), options).unwrap();
+        let lines: Vec<_> = asm.lines().collect();
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            lines[map[0].asm_lines.0 - 1],
+            "// Main.vm:1: function Main.main 0"
+        );
+        assert_eq!(
+            lines[map[1].asm_lines.0 - 1],
+            "// Main.vm:3: push constant 7"
+        );
+        assert_eq!(map[1].function.as_deref(), Some("Main.main"));
+        assert_eq!((map[2].line, map[2].command.as_str()), (Some(4), "return"));
+
+        // the entries cover the program between the bootstrap code and the runtime
+        let bootstrap_words = Translator::instruction_count(&bootstrap.to_asm());
+        assert_eq!(map[0].rom.0, bootstrap_words);
+        for (entry, next) in map.iter().zip(&map[1..]) {
+            assert_eq!(entry.asm_lines.1 + 1, next.asm_lines.0);
+            assert_eq!(entry.rom.1, next.rom.0);
+        }
+        for entry in &map {
+            let code = lines[entry.asm_lines.0 - 1..entry.asm_lines.1].join("\n");
+            assert_eq!(
+                Translator::instruction_count(&code),
+                entry.rom.1 - entry.rom.0
+            );
+        }
+        let program = &lines[..map[2].asm_lines.1].join("\n");
+        assert_eq!(Translator::instruction_count(program), map[2].rom.1);
+    }
+
+    #[test]
+    fn assembly_errors_point_at_vm_commands() {
+        let sources = parse(&[("Main", "function Main.main 0\npush constant 40000\nreturn")]);
+        let (asm, map) = Translator::translate_program(&sources, None, Options::default()).unwrap();
+        let errors = Translator::assemble(&asm, &map).unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "Main.vm:2: error: Constant 40000 is out of 0..=32767"
+        );
+    }
+
+    /// Size of Pong with the OS, translated with `options`.
+    fn pong_size(options: Options) -> usize {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let sources: Vec<_> = ["projects/11/Pong", "tools/OS"]
+            .iter()
+            .flat_map(|dir| Source::read(&root.join(dir)).unwrap())
+            .map(|source| Parser::parse(source).unwrap())
+            .collect();
+        let (asm, _) =
+            Translator::translate_program(&sources, Some(&Bootstrap::default()), options).unwrap();
+        Translator::instruction_count(&asm)
+    }
+
+    #[test]
+    fn trampolines_shrink_pong() {
+        let inline = pong_size(Options::default());
+        let shared = pong_size(Options {
+            trampolines: true,
+            ..Options::default()
+        });
+        // 50775 and 38571 words
+        assert!(inline - shared > 12_000, "{shared} vs {inline}");
+    }
+
+    #[test]
+    fn cached_top() {
+        let options = Options {
+            cache_top: true,
+            ..Options::default()
+        };
+        let sources = parse(&[(
+            "Main",
+            "push constant 5\npop local 2\npush constant 1\npush constant 2\nadd",
+        )]);
+        let asm = Translator::translate(&sources[0], options).unwrap();
+        assert_eq!(
+            asm.lines()
+                .filter(|line| !line.starts_with("//"))
+                .collect::<Vec<_>>(),
+            [
+                "@5", "D=A", "@LCL", "A=M+1", "A=A+1", "M=D", // push constant 5, pop local 2
+                "D=1", "@SP", "AM=M+1", "A=A-1", "M=D", "@2", "D=A", // push 1, push 2
+                "@SP", "AM=M-1", "D=D+M", // add
+                "@SP", "AM=M+1", "A=A-1", "M=D", // flush at the end
+            ]
+        );
+
+        let inline = pong_size(Options::default());
+        let cached = pong_size(options);
+        // 50775 and 39053 words
+        assert!(inline - cached > 10_000, "{cached} vs {inline}");
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+
+use jack_vm_ir::{Command, FunctionName, Label, Segment};
+
+use crate::errors::{Diagnostic, Severity};
+use crate::parser::ParsedSource;
+
+/// Number of words between 16 and 255 the assembler allocates static variables in.
+pub const STATIC_SIZE: usize = 240;
+
+/// Checks what can't be checked line by line, across all the files of a program.
+/// Calls to undefined functions are errors in a `whole_program`,
+/// and only warnings otherwise, as the files left out may define them.
+pub fn validate(sources: &[ParsedSource], whole_program: bool) -> Vec<Diagnostic> {
+    let mut issues = vec![];
+    let mut defined = HashMap::<&FunctionName, (&str, Option<u32>)>::new();
+    let mut calls = vec![];
+    let mut statics = HashSet::new();
+    let has_functions = sources
+        .iter()
+        .flat_map(|source| &source.commands)
+        .any(|command| matches!(command, Command::Function { .. }));
+    for source in sources {
+        let mut issue = |severity, index: usize, message: String| {
+            issues.push(Diagnostic {
+                severity,
+                ..Diagnostic::error(&source.name, source.lines.get(index).copied(), message)
+            })
+        };
+        // labels and jumps of the current function, or of the code before the first one
+        let mut labels = HashSet::<&Label>::new();
+        let mut jumps = vec![];
+        for (index, command) in source.commands.iter().enumerate() {
+            if index == 0 && has_functions && !matches!(command, Command::Function { .. }) {
+                issue(
+                    Severity::Warning,
+                    index,
+                    "Commands outside of functions are never executed after the bootstrap code"
+                        .to_string(),
+                );
+            }
+            match command {
+                Command::Function { name, .. } => {
+                    check_jumps(&labels, &mut jumps, &mut issue);
+                    labels.clear();
+                    match defined.get(name) {
+                        Some((file, line)) => issue(
+                            Severity::Error,
+                            index,
+                            format!(
+                                "Function {name} is already defined in {file}.vm{}",
+                                line.map(|line| format!(":{line}")).unwrap_or_default()
+                            ),
+                        ),
+                        None => {
+                            defined.insert(name, (&source.name, source.lines.get(index).copied()));
+                        }
+                    }
+                }
+                Command::Label(label) => {
+                    labels.insert(label);
+                }
+                Command::GoTo(label) | Command::IfGoTo(label) => jumps.push((index, label)),
+                Command::Call { name, n_args } => calls.push((source, index, name, *n_args)),
+                Command::Push { segment, i } | Command::Pop { segment, i } => match segment {
+                    Segment::Temp if *i > 7 => issue(
+                        Severity::Error,
+                        index,
+                        format!("Temp index {i} is out of 0..=7"),
+                    ),
+                    Segment::Pointer if *i > 1 => issue(
+                        Severity::Error,
+                        index,
+                        format!("Pointer index {i} is out of 0..=1"),
+                    ),
+                    Segment::Static => {
+                        let new = statics.insert((&source.name, *i));
+                        if new && statics.len() == STATIC_SIZE + 1 {
+                            issue(
+                                Severity::Error,
+                                index,
+                                format!(
+                                    "The static variables of the program take more than the {STATIC_SIZE} words of the static area"
+                                ),
+                            )
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        check_jumps(&labels, &mut jumps, &mut issue);
+    }
+
+    let mut n_args = HashMap::<&FunctionName, u16>::new();
+    for (source, index, name, count) in calls {
+        let mut issue = |severity, message| {
+            issues.push(Diagnostic {
+                severity,
+                ..Diagnostic::error(&source.name, source.lines.get(index).copied(), message)
+            })
+        };
+        if !defined.contains_key(name) {
+            let severity = if whole_program {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            issue(severity, format!("Function {name} is not defined"));
+        }
+        match n_args.get(name) {
+            Some(&expected) if expected != count => issue(
+                Severity::Warning,
+                format!(
+                    "{name} is called with {count} argument(s) here, but with {expected} elsewhere"
+                ),
+            ),
+            Some(_) => {}
+            None => {
+                n_args.insert(name, count);
+            }
+        }
+    }
+    issues
+}
+
+/// Reports the jumps of a function to labels it doesn't define.
+fn check_jumps(
+    labels: &HashSet<&Label>,
+    jumps: &mut Vec<(usize, &Label)>,
+    issue: &mut impl FnMut(Severity, usize, String),
+) {
+    for (index, label) in jumps.drain(..) {
+        if !labels.contains(label) {
+            issue(
+                Severity::Error,
+                index,
+                format!("Label {label} is not defined"),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::test_util::parse;
+    use crate::translator::{Bootstrap, Options, Translator};
+    use crate::{parser::Parser, source::Source};
+
+    fn validate_files(files: &[(&str, &str)]) -> Vec<String> {
+        validate(&parse(files), true)
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn valid_program() {
+        let issues = validate_files(&[
+            (
+                "Sys",
+                "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END",
+            ),
+            (
+                "Main",
+                "function Main.main 1\nlabel LOOP\npush temp 7\npop pointer 1\nif-goto LOOP\npush static 3\nreturn",
+            ),
+        ]);
+        assert!(issues.is_empty(), "{issues:?}");
+        // the tests of project 7 have no functions
+        assert!(validate_files(&[("Test", "push constant 1\npop temp 0")]).is_empty());
+    }
+
+    #[test]
+    fn labels_are_scoped_by_function() {
+        let issues = validate_files(&[(
+            "Main",
+            r"
+function Main.a 0
+label LOOP
+goto LOOP
+function Main.b 0
+goto LOOP
+if-goto END
+label END
+return
+",
+        )]);
+        assert_eq!(issues, ["Main.vm:6: error: Label LOOP is not defined"]);
+    }
+
+    #[test]
+    fn functions_and_calls() {
+        let issues = validate_files(&[
+            (
+                "Main",
+                r"
+function Main.main 0
+push constant 1
+call Main.f 1
+call Main.f 2
+call Main.missing 0
+return
+function Main.f 0
+return
+",
+            ),
+            ("Other", "function Main.f 0\nreturn"),
+        ]);
+        assert_eq!(
+            issues,
+            [
+                "Other.vm:1: error: Function Main.f is already defined in Main.vm:8",
+                "Main.vm:5: warning: Main.f is called with 2 argument(s) here, but with 1 elsewhere",
+                "Main.vm:6: error: Function Main.missing is not defined",
+            ]
+        );
+    }
+
+    #[test]
+    fn segments() {
+        let issues = validate_files(&[
+            (
+                "Main",
+                "push constant 0\nfunction Main.main 0\npush temp 8\npop pointer 2\nreturn",
+            ),
+            ("Other", "function Other.f 0\nreturn"),
+        ]);
+        assert_eq!(
+            issues,
+            [
+                "Main.vm:1: warning: Commands outside of functions are never executed after the bootstrap code",
+                "Main.vm:3: error: Temp index 8 is out of 0..=7",
+                "Main.vm:4: error: Pointer index 2 is out of 0..=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn static_area_overflow() {
+        let pops = |n: u16| {
+            (0..n)
+                .map(|i| format!("push constant 0\npop static {i}\n"))
+                .collect::<String>()
+        };
+        let function =
+            |class: &str, statics| format!("function {class}.f 0\n{}return", pops(statics));
+        let (a, b) = (function("A", 120), function("B", 120));
+        let over = function("B", 121);
+        assert!(validate_files(&[("A", &a), ("B", &b)]).is_empty());
+        assert_eq!(
+            validate_files(&[("A", &a), ("B", &over)]),
+            ["B.vm:243: error: The static variables of the program take more than the 240 words of the static area"]
+        );
+    }
+
+    #[test]
+    fn files_of_a_program_can_be_translated_alone() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../projects/08/FunctionCalls/StaticsTest/Sys.vm");
+        let sources: Vec<_> = Source::read(&path)
+            .unwrap()
+            .into_iter()
+            .map(|source| Parser::parse(source).unwrap())
+            .collect();
+        let severities = |whole_program| -> Vec<_> {
+            validate(&sources, whole_program)
+                .iter()
+                .map(|issue| issue.severity)
+                .collect()
+        };
+        // Class1 and Class2 are in the other files of the test
+        assert_eq!(severities(false), [Severity::Warning; 4]);
+        assert_eq!(severities(true), [Severity::Error; 4]);
+        Translator::translate_program(&sources, Some(&Bootstrap::default()), Options::default())
+            .unwrap();
+    }
+}